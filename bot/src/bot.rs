@@ -0,0 +1,206 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+use rand::Rng;
+use aeonetica_client::client_runtime::{ClientRuntime, HandleCreator};
+use aeonetica_client::data_store::DataStore;
+use aeonetica_client::networking::Traffic;
+use aeonetica_client::networking::messaging::{ClientHandle, ClientMessenger};
+use aeonetica_client::networking::sockets::SocketTransport;
+use aeonetica_client::renderer::Renderer;
+use aeonetica_engine::{EntityId, Id, TypeId};
+use aeonetica_engine::error::{Error, ErrorResult, Fatality};
+use aeonetica_engine::error::builtin::NetworkError;
+use aeonetica_engine::networking::SendMode;
+use aeonetica_engine::networking::messaging::ClientEntity;
+use aeonetica_engine::networking::rpc::RequestError;
+use aeonetica_engine::networking::transport::ClientTransport;
+use aeonetica_engine::util::id_map::IdMap;
+use aeonetica_engine::util::nullable::Nullable;
+use aeonetica_engine::util::type_to_id;
+use player_mod::messages::{PlayerHandleType, PlayerState, ReceiveState, SendInputs, SetControlling};
+use player_mod::movement::{MAX_INPUT_DELTA, MovementState, PlayerInput};
+
+/// Bots run at the highest frame rate the player mod accepts inputs for.
+const FRAME_TIME: Duration = Duration::from_millis((MAX_INPUT_DELTA * 1000.0) as u64);
+const PING_INTERVAL: Duration = Duration::from_secs(1);
+/// Pings without a pong after this long count as lost.
+const PING_TIMEOUT: Duration = Duration::from_secs(5);
+/// Like the player mod, every message repeats the latest unacknowledged inputs.
+pub(crate) const RESENT_INPUTS: usize = 8;
+pub(crate) const MAX_PENDING_INPUTS: usize = 120;
+/// Bots keep pressing the same keys for up to this many seconds.
+const MAX_WALK_TIME: f32 = 2.0;
+
+/// Headless clients have no layers.
+const NO_LAYER: TypeId = Id::from_u64(0);
+
+/// A random walk of the player the bot controls.
+#[derive(Default)]
+pub(crate) struct Walk {
+    /// set once the server lets the bot control its player
+    player: Option<EntityId>,
+    keys: PlayerInput,
+    /// seconds until other keys are pressed
+    remaining: f32,
+    sequence: u32,
+    /// inputs the server did not acknowledge yet
+    pub(crate) pending_inputs: VecDeque<PlayerInput>
+}
+
+impl Walk {
+    /// Returns the inputs to send for a frame of `delta` seconds.
+    pub(crate) fn step(&mut self, delta: f32) -> Vec<PlayerInput> {
+        let mut rng = rand::thread_rng();
+        self.remaining -= delta;
+        if self.remaining <= 0.0 {
+            self.remaining = rng.gen_range(0.0..MAX_WALK_TIME);
+            let direction = rng.gen_range(-1..=1);
+            self.keys.left = direction < 0;
+            self.keys.right = direction > 0;
+            self.keys.hover = rng.gen_bool(0.3);
+        }
+        if self.pending_inputs.len() < MAX_PENDING_INPUTS {
+            self.sequence += 1;
+            self.pending_inputs.push_back(PlayerInput {
+                sequence: self.sequence,
+                delta,
+                ..self.keys
+            });
+        }
+        self.pending_inputs.iter().rev().take(RESENT_INPUTS).rev().copied().collect()
+    }
+
+    fn is_controlled(&self, messenger: &ClientMessenger) -> bool {
+        self.player == Some(messenger.entity_id())
+    }
+}
+
+/// Stands in for the player mod's `PlayerHandle`, which needs a renderer.
+/// Only the handle of the bot's own player does anything.
+struct BotPlayer {
+    walk: Rc<RefCell<Walk>>
+}
+
+impl BotPlayer {
+    fn set_controlling(&mut self, messenger: &mut ClientMessenger, _renderer: Nullable<&mut Renderer>, _store: &mut DataStore, is_controlling: bool) {
+        if is_controlling {
+            self.walk.borrow_mut().player = Some(messenger.entity_id());
+        }
+    }
+
+    fn receive_position(&mut self, messenger: &mut ClientMessenger, _renderer: Nullable<&mut Renderer>, _store: &mut DataStore, _state: &PlayerState, teleporting: bool) {
+        let mut walk = self.walk.borrow_mut();
+        if teleporting && walk.is_controlled(messenger) {
+            walk.pending_inputs.clear();
+        }
+    }
+
+    fn receive_state(&mut self, messenger: &mut ClientMessenger, _renderer: Nullable<&mut Renderer>, _store: &mut DataStore, (sequence, _state): (u32, MovementState)) {
+        let mut walk = self.walk.borrow_mut();
+        if walk.is_controlled(messenger) {
+            walk.pending_inputs.retain(|i| i.sequence > sequence);
+        }
+    }
+}
+
+impl ClientEntity for BotPlayer {}
+
+impl ClientHandle for BotPlayer {
+    fn owning_layer(&self) -> TypeId {
+        NO_LAYER
+    }
+
+    fn start(&mut self, messenger: &mut ClientMessenger, _renderer: Nullable<&mut Renderer>, _store: &mut DataStore) {
+        messenger.on::<SetControlling, _>(Self::set_controlling);
+        messenger.register_replicated(Self::receive_position);
+        messenger.on::<ReceiveState, _>(Self::receive_state);
+    }
+
+    fn remove(&mut self, messenger: &mut ClientMessenger, _renderer: Nullable<&mut Renderer>, _store: &mut DataStore) {
+        let mut walk = self.walk.borrow_mut();
+        if walk.is_controlled(messenger) {
+            walk.player = None;
+            walk.pending_inputs.clear();
+        }
+    }
+}
+
+/// What a bot measured while it was connected.
+#[derive(Default)]
+pub(crate) struct BotReport {
+    pub(crate) rtts: Vec<Duration>,
+    pub(crate) pings: usize,
+    /// pings without a pong within [`PING_TIMEOUT`], the ones in flight when the bot stopped are not counted
+    pub(crate) lost_pings: usize,
+    pub(crate) traffic: Traffic,
+    pub(crate) duration: Duration,
+    pub(crate) errors: Vec<String>
+}
+
+/// Connects a bot to `server`, lets it walk around for `duration` and logs it out again.
+pub(crate) fn run_bot(server: &str, duration: Duration) -> BotReport {
+    run_with(|| SocketTransport::connect("0.0.0.0:0", server), duration)
+}
+
+/// Like [`run_bot`], but connects through the transport `connect` returns.
+pub(crate) fn run_with<T: ClientTransport + 'static>(connect: impl FnOnce() -> ErrorResult<T>, duration: Duration) -> BotReport {
+    let mut report = BotReport::default();
+    let started = Instant::now();
+    if let Err(e) = run(connect, duration, &mut report) {
+        report.errors.push(e.value().to_string());
+    }
+    report.duration = started.elapsed();
+    report
+}
+
+fn run<T: ClientTransport + 'static>(connect: impl FnOnce() -> ErrorResult<T>, duration: Duration, report: &mut BotReport) -> ErrorResult<()> {
+    let walk = Rc::new(RefCell::new(Walk::default()));
+    let player_walk = walk.clone();
+    let mut handles = IdMap::<HandleCreator>::default();
+    handles.insert(type_to_id::<PlayerHandleType>(), Box::new(move || Box::new(BotPlayer { walk: player_walk.clone() }) as Box<dyn ClientHandle>));
+
+    // the handshake is part of the measured time
+    let started = Instant::now();
+    let mut client = ClientRuntime::create_headless(Id::new(), connect()?, handles)?;
+    client.login()?;
+    let mut store = DataStore::new();
+    let pongs: Rc<RefCell<Vec<Result<Duration, RequestError>>>> = Default::default();
+    let mut next_ping = Instant::now();
+
+    while started.elapsed() < duration {
+        let frame = Instant::now();
+        if let Err(e) = client.update_headless(&mut store) {
+            report.errors.push(e.value().to_string());
+        }
+        if let Some(reason) = client.unregistered() {
+            return Err(Error::new(NetworkError(format!("unregistered by the server: {reason}")), Fatality::DEFAULT, false))
+        }
+
+        if frame >= next_ping {
+            let pongs = pongs.clone();
+            client.ping(PING_TIMEOUT, move |pong| pongs.borrow_mut().push(pong))?;
+            report.pings += 1;
+            next_ping = frame + PING_INTERVAL;
+        }
+
+        let player = walk.borrow().player;
+        if let Some(player) = player {
+            let inputs = walk.borrow_mut().step(FRAME_TIME.as_secs_f32());
+            if let Some(messenger) = client.messenger(&player) {
+                messenger.send::<SendInputs>(inputs, SendMode::Quick);
+            }
+        }
+
+        for pong in pongs.borrow_mut().drain(..) {
+            match pong {
+                Ok(rtt) => report.rtts.push(rtt),
+                Err(_) => report.lost_pings += 1
+            }
+        }
+        report.traffic = client.traffic();
+        std::thread::sleep(FRAME_TIME.saturating_sub(frame.elapsed()));
+    }
+    client.logout()
+}
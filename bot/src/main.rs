@@ -0,0 +1,115 @@
+use std::time::Duration;
+use aeonetica_engine::error::{Error, ErrorResult, Fatality};
+use aeonetica_engine::error::builtin::ValueError;
+use aeonetica_engine::log;
+use crate::bot::{run_bot, BotReport};
+
+mod bot;
+#[cfg(test)]
+mod tests;
+
+const HELP: &str = "Usage:
+    aeonetica-bot [<server ip>] [options]   connects headless bots to a server (default: 127.0.0.1:6090)
+    aeonetica-bot --help
+
+Options:
+    -n, --bots <n>          number of bots (default: 10)
+    -d, --duration <secs>   how long every bot stays connected (default: 60)
+    -i, --interval <ms>     time between two bots joining (default: 100)
+
+Bots do not download mods, they random walk through the player mod and
+report their round trip times, bandwidth and errors when they are done.";
+
+struct BotOptions {
+    server: String,
+    bots: usize,
+    duration: Duration,
+    interval: Duration
+}
+
+fn main() {
+    aeonetica_engine::enable_ansi_support::enable_ansi_support().unwrap_or_else(|_| eprintln!("ansi not supported in this console"));
+    let args: Vec<_> = std::env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == "--help") {
+        log!("{HELP}");
+        return
+    }
+    let options = parse_options(&args).unwrap_or_else(|e| e.log_exit());
+
+    log!("starting {} bots on {} for {}s", options.bots, options.server, options.duration.as_secs());
+    let threads: Vec<_> = (0..options.bots).map(|i| {
+        if i > 0 {
+            std::thread::sleep(options.interval);
+        }
+        let server = options.server.clone();
+        let duration = options.duration;
+        std::thread::spawn(move || run_bot(&server, duration))
+    }).collect();
+    let reports: Vec<_> = threads.into_iter()
+        .map(|t| t.join().unwrap_or_else(|_| BotReport { errors: vec!["the bot panicked".to_string()], ..Default::default() }))
+        .collect();
+    print_reports(&reports);
+}
+
+fn parse_options(args: &[String]) -> ErrorResult<BotOptions> {
+    let mut options = BotOptions {
+        server: "127.0.0.1:6090".to_string(),
+        bots: 10,
+        duration: Duration::from_secs(60),
+        interval: Duration::from_millis(100)
+    };
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next()
+            .and_then(|v| v.parse::<u64>().ok())
+            .ok_or_else(|| Error::new(ValueError(format!("missing or invalid value for {arg}")), Fatality::FATAL, false));
+        match arg.as_str() {
+            "-n" | "--bots" => options.bots = value()? as usize,
+            "-d" | "--duration" => options.duration = Duration::from_secs(value()?),
+            "-i" | "--interval" => options.interval = Duration::from_millis(value()?),
+            _ if !arg.starts_with('-') => options.server = arg.clone(),
+            _ => return Err(Error::new(ValueError(format!("unknown argument {arg}; use `--help` for help")), Fatality::FATAL, false))
+        }
+    }
+    Ok(options)
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+fn print_reports(reports: &[BotReport]) {
+    log!("bot   rtt min/avg/max ms    lost/pings   sent kB/s   recv kB/s   sent pkts   recv pkts   errors");
+    for (i, report) in reports.iter().enumerate() {
+        let rtt = match (report.rtts.iter().min(), report.rtts.iter().max()) {
+            (Some(min), Some(max)) => {
+                let avg = report.rtts.iter().sum::<Duration>() / report.rtts.len() as u32;
+                format!("{:.1}/{:.1}/{:.1}", millis(*min), millis(avg), millis(*max))
+            }
+            _ => "-".to_string()
+        };
+        let secs = report.duration.as_secs_f64().max(f64::EPSILON);
+        let traffic = report.traffic;
+        log!("{i:<5} {rtt:<21} {:<12} {:<11.2} {:<11.2} {:<11} {:<11} {}",
+            format!("{}/{}", report.lost_pings, report.pings),
+            traffic.sent_bytes as f64 / secs / 1000.0,
+            traffic.received_bytes as f64 / secs / 1000.0,
+            traffic.sent_packets,
+            traffic.received_packets,
+            report.errors.len());
+    }
+
+    let rtts: Vec<_> = reports.iter().flat_map(|r| r.rtts.iter().copied()).collect();
+    let failed = reports.iter().filter(|r| !r.errors.is_empty()).count();
+    let sent: u64 = reports.iter().map(|r| r.traffic.sent_bytes).sum();
+    let received: u64 = reports.iter().map(|r| r.traffic.received_bytes).sum();
+    let avg_rtt = if rtts.is_empty() { 0.0 } else { millis(rtts.iter().sum::<Duration>() / rtts.len() as u32) };
+    log!("total: {} bots, {failed} with errors, avg rtt {avg_rtt:.1}ms, {:.1}kB sent, {:.1}kB received",
+        reports.len(), sent as f64 / 1000.0, received as f64 / 1000.0);
+
+    for (i, report) in reports.iter().enumerate() {
+        for error in &report.errors {
+            log!(ERROR, "bot {i}: {error}");
+        }
+    }
+}
@@ -0,0 +1,79 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
+use aeonetica_client::singleplayer::EmbeddedServer;
+use aeonetica_engine::{ClientId, EntityId};
+use aeonetica_engine::networking::SendMode;
+use aeonetica_server::{ServerMod, ServerModBox};
+use aeonetica_server::ecs::Engine;
+use aeonetica_server::ecs::events::ConnectionListener;
+use aeonetica_server::ecs::messaging::Messenger;
+use player_mod::messages::{PlayerHandleType, ReceiveState, SendInputs, SetControlling};
+use player_mod::movement::{MovementState, PlayerInput};
+use crate::bot::{MAX_PENDING_INPUTS, RESENT_INPUTS, run_with, Walk};
+
+/// The highest input sequence [`Players`] acknowledged.
+static ACKNOWLEDGED: AtomicU32 = AtomicU32::new(0);
+
+/// Gives every client a player to control like the player mod does,
+/// but acknowledges all inputs without moving it.
+struct Players;
+
+impl ServerMod for Players {
+    fn start(&mut self, engine: &mut Engine) {
+        let eid = engine.new_entity();
+        engine.mut_entity(&eid).unwrap().add_module(ConnectionListener::new(join, |_, _, _| ()));
+    }
+}
+
+fn join(_id: &EntityId, engine: &mut Engine, client: &ClientId) {
+    let pid = engine.new_entity();
+    engine.tie_to_client(&pid, Some(*client));
+    engine.mut_entity(&pid).add_module(Messenger::new::<PlayerHandleType>());
+    let mut messenger = engine.mut_module_of::<Messenger>(&pid);
+    messenger.on::<SendInputs>(acknowledge);
+    messenger.add_client(*client);
+    messenger.send_to::<SetControlling>(client, true, SendMode::Safe);
+}
+
+fn acknowledge(id: &EntityId, engine: &mut Engine, client: &ClientId, inputs: Vec<PlayerInput>) {
+    let Some(last) = inputs.last() else { return };
+    ACKNOWLEDGED.fetch_max(last.sequence, Ordering::Relaxed);
+    engine.mut_module_of::<Messenger>(id).send_to::<ReceiveState>(client, (last.sequence, MovementState::new(Default::default())), SendMode::Quick);
+}
+
+#[test]
+fn bots_walk_and_measure_round_trip_times() {
+    let (server, transport) = EmbeddedServer::start_linked(|| vec![ServerModBox::without_library("bot:players", Box::new(Players))]);
+    let report = run_with(|| Ok(transport), Duration::from_millis(1500));
+    drop(server);
+
+    assert!(report.errors.is_empty(), "{:?}", report.errors);
+    // the server only gets inputs once the bot controls its player
+    assert!(ACKNOWLEDGED.load(Ordering::Relaxed) > 0);
+    assert!(!report.rtts.is_empty());
+    assert!(report.rtts.len() <= report.pings);
+    assert_eq!(report.lost_pings, 0);
+    assert!(report.traffic.sent_packets > 0 && report.traffic.received_packets > 0);
+}
+
+#[test]
+fn walks_resend_the_latest_unacknowledged_inputs() {
+    let sequences = |inputs: Vec<PlayerInput>| inputs.iter().map(|i| i.sequence).collect::<Vec<_>>();
+    let mut walk = Walk::default();
+    let mut inputs = vec![];
+    for _ in 0..20 {
+        inputs = walk.step(0.05);
+    }
+    assert_eq!(sequences(inputs), (20 - RESENT_INPUTS as u32 + 1..=20).collect::<Vec<_>>());
+
+    // acknowledged inputs are not sent again
+    walk.pending_inputs.retain(|i| i.sequence > 18);
+    assert_eq!(sequences(walk.step(0.05)), vec![19, 20, 21]);
+
+    // without acknowledgements the bot stops adding inputs instead of piling them up
+    for _ in 0..MAX_PENDING_INPUTS * 2 {
+        walk.step(0.05);
+    }
+    assert_eq!(walk.pending_inputs.len(), MAX_PENDING_INPUTS);
+    assert_eq!(sequences(walk.step(0.05)).last(), Some(&(MAX_PENDING_INPUTS as u32 + 18)));
+}
@@ -36,7 +36,7 @@ if __name__ == '__main__':
     ]
     
     for mod in server.fetch_mods('server/mods/mods.ron'):
-        process = subprocess.Popen([sys.executable, 'build.py', '-w', mod, '-d', '../server/mods', *mode],
+        process = subprocess.Popen(['cargo', 'run', '-p', 'aeonetica-pack', '--', 'build', mod, '-d', '../server/mods', *mode],
                                    cwd=dname+'/mods', 
                                    stdout=subprocess.DEVNULL, stderr=subprocess.DEVNULL)
         processes.append((f'mods/{mod}', process))
@@ -67,7 +67,7 @@ if __name__ == '__main__':
         print(f'{GREEN} -> generating `{client_package}`{ENDC}')
         
         with zipfile.ZipFile(client_package, 'w', zipfile.ZIP_DEFLATED) as zipf:
-            zipf.write(f'client/target/{mode}/{client_bin}', client_bin)
+            zipf.write(f'target/{mode}/{client_bin}', client_bin)
         
         # package server
         server_bin = 'server' + binary_ext
@@ -75,7 +75,7 @@ if __name__ == '__main__':
         print(f'{GREEN} -> generating `{server_package}`{ENDC}')
         
         with zipfile.ZipFile(server_package, 'w', zipfile.ZIP_DEFLATED) as zipf:
-            zipf.write(f'server/target/{mode}/{server_bin}', server_bin)
+            zipf.write(f'target/{mode}/{server_bin}', server_bin)
             
             for file in os.listdir('server/mods'):
                 filename = 'mods/' + file
@@ -1,8 +1,7 @@
+use std::process::exit;
 use std::time::Instant;
 use aeonetica_engine::*;
 use aeonetica_engine::error::ErrorResult;
-use aeonetica_engine::networking::client_packets::{ClientMessage, ClientPacket};
-use aeonetica_engine::networking::SendMode;
 use aeonetica_engine::time::Time;
 use crate::client_runtime::ClientRuntime;
 use crate::data_store::DataStore;
@@ -11,12 +10,8 @@ use crate::renderer::window::Window;
 
 const FULL_SEC: usize = 1_000_000_000;
 
-pub fn run(mut client: ClientRuntime, client_id: ClientId, store: &mut DataStore) -> ErrorResult<()> {
-    let _ = client.nc.borrow().send(&ClientPacket {
-        client_id,
-        conv_id: Id::new(),
-        message: ClientMessage::Login,
-    }, SendMode::Safe);
+pub fn run(mut client: ClientRuntime, store: &mut DataStore) -> ErrorResult<()> {
+    let _ = client.login();
 
     log!("sent login");
 
@@ -32,17 +27,24 @@ pub fn run(mut client: ClientRuntime, client_id: ClientId, store: &mut DataStore
 
     let mut context = RenderContext::new();
 
-    client.loaded_mods.iter()
-        .for_each(|loaded_mod| { loaded_mod.client_mod.start(store, window.context_provider().with_render(&mut context)); });
+    client.start_mods(store, &window, &mut context);
 
     while !window.should_close() {
         let t = Instant::now();
 
         window.poll_events(&mut client, &mut context, store);
+
+        let _ = client.reload_pending_mods(store, &window, &mut context).map_err(|e| {
+            log!(ERROR, "{e}")
+        });
         
-        let _ = client.handle_queued(store, &mut context).map_err(|e| {
+        let _ = client.handle_queued(store, Some(&context)).map_err(|e| {
             log!(ERROR, "{e}")
         });
+        if client.unregistered().is_some() {
+            exit(0)
+        }
+        client.timeout_requests(store, Some(&context));
         
         window.on_render(&mut context, &mut client, store, time);
         
@@ -64,9 +66,5 @@ pub fn run(mut client: ClientRuntime, client_id: ClientId, store: &mut DataStore
     log!("shutting down client after {}s", time.time);
     context.finish(store);
     window.finish();
-    client.nc.borrow().send(&ClientPacket {
-        client_id: client.client_id,
-        conv_id: Id::new(),
-        message: ClientMessage::Logout,
-    }, SendMode::Safe)
+    client.logout()
 }
@@ -1,48 +1,65 @@
 
 use std::cell::RefCell;
-use std::collections::HashMap;
-use std::fs::File;
-use std::io::{Read, Cursor, Write};
 use std::process::exit;
 use std::thread;
 use std::rc::Rc;
-use std::time::Duration;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
 use aeonetica_engine::error::{Error, Fatality, ErrorResult};
-use aeonetica_engine::error::builtin::ModError;
-use aeonetica_engine::libloading::{Library, Symbol};
-use aeonetica_engine::nanoserde::SerBin;
-use aeonetica_engine::{ENGINE_VERSION, Id, log, MAX_CLIENT_TIMEOUT, MOD_TARGET};
+use aeonetica_engine::error::builtin::{ModError, NetworkError};
+use aeonetica_engine::libloading::Library;
+use aeonetica_engine::abi::{mod_entry, Side};
+use aeonetica_engine::{ENGINE_VERSION, EntityId, Id, log, MOD_TARGET, TypeId};
 use aeonetica_engine::networking::client_packets::{ClientInfo, ClientMessage, ClientPacket};
-use aeonetica_engine::networking::server_packets::{ServerMessage, ServerPacket};
-use aeonetica_engine::networking::{MOD_DOWNLOAD_CHUNK_SIZE, NetResult, SendMode};
+use aeonetica_engine::networking::server_packets::{ModInfo, ServerMessage, ServerPacket};
+use aeonetica_engine::networking::{NetResult, SendMode};
+use aeonetica_engine::networking::rpc::RequestError;
+use aeonetica_engine::networking::transport::ClientTransport;
+use aeonetica_engine::sha2::{Digest, Sha256};
+use aeonetica_server::server::unpacked_client_zip;
 use aeonetica_engine::util::id_map::IdMap;
 use crate::networking::messaging::{ClientHandle, ClientMessenger};
-use aeonetica_engine::util::unzip_archive;
 use crate::{ClientMod, ClientModBox};
-use crate::networking::NetworkClient;
+use crate::networking::{NetworkClient, Traffic};
 
 
 mod paths_util {
-    #[cfg(target_os = "windows")]
-    pub(crate) const MOD_FILE_EXTENSION: &str = "dll";
-    #[cfg(target_os = "linux")]
-    pub(crate) const MOD_FILE_EXTENSION: &str = "so";
-    pub(crate) fn client_lib(path: &str, name: &str) -> String {
-        format!("runtime/{path}/{name}_client.{MOD_FILE_EXTENSION}")
+    use aeonetica_engine::{mod_layout, MOD_TARGET};
+    pub(crate) const MOD_CACHE_DIR: &str = "runtime/mods";
+    pub(crate) const MOD_CACHE_INDEX: &str = "runtime/mods/index";
+    pub(crate) fn cached_mod(hash: &str) -> String {
+        format!("{MOD_CACHE_DIR}/{hash}")
     }
-    pub(crate) fn mod_hash(path: &str) -> String {
-        format!("runtime/{path}.hash")
+    pub(crate) fn client_lib(hash: &str, name: &str) -> String {
+        format!("{MOD_CACHE_DIR}/{hash}/{}", mod_layout::client_lib(name, MOD_TARGET))
+    }
+    pub(crate) fn client_wasm(hash: &str, name: &str) -> String {
+        format!("{MOD_CACHE_DIR}/{hash}/{}", mod_layout::client_lib(name, mod_layout::WASM_MOD_TARGET))
+    }
+    pub(crate) fn partial_download(hash: &str) -> String {
+        format!("{MOD_CACHE_DIR}/{hash}.part")
+    }
+    pub(crate) fn partial_download_meta(hash: &str) -> String {
+        format!("{MOD_CACHE_DIR}/{hash}.part.meta")
     }
 }
 pub(crate) use paths_util::*;
 use crate::data_store::DataStore;
+use crate::mod_cache::{DEFAULT_MOD_CACHE_CAP, ModCache, normalize_hash};
+use crate::trust::{TrustPolicy, TrustStore};
+use crate::downloads::{CHUNK_TIMEOUT, DownloadProgress, log_progress, MAX_CHUNKS_IN_FLIGHT, PartialDownload, ProgressCallback, retry_chunk};
+#[cfg(feature = "window")]
 use crate::renderer::context::RenderContext;
+#[cfg(feature = "window")]
+use crate::renderer::window::Window;
 
 #[derive(Debug, PartialEq)]
 pub(crate) enum ClientState {
     Start,
     Registered,
-    DownloadedMods
+    DownloadedMods,
+    /// kicked or timed out by the server, with the reason
+    Unregistered(String)
 }
 
 pub struct ClientRuntime {
@@ -51,72 +68,199 @@ pub struct ClientRuntime {
     pub(crate) mod_profile_version: String,
     pub(crate) nc: Rc<RefCell<NetworkClient>>,
     pub(crate) awaiting_replies: IdMap<Box<dyn Fn(&mut ClientRuntime, &ServerPacket)>>,
+    /// deadlines of the pings in `awaiting_replies`, with what to call once they passed
+    pub(crate) ping_timeouts: IdMap<(Instant, Box<dyn FnOnce()>)>,
     pub(crate) loaded_mods: Vec<ClientModBox>,
-    pub(crate) registered_handles: IdMap<fn() -> Box<dyn ClientHandle>>,
+    pub(crate) registered_handles: IdMap<HandleCreator>,
     pub(crate) handles: IdMap<ClientHandleBox>,
-    pub(crate) state: ClientState
+    pub(crate) state: ClientState,
+    /// mods the server reloaded
+    pub(crate) pending_reloads: Vec<ModInfo>,
+    /// packets received while a reload was pending, handled after the reload
+    pub(crate) deferred_packets: Vec<ServerPacket>,
+    pub(crate) download_progress: ProgressCallback,
+    pub(crate) mod_cache: ModCache,
+    pub(crate) trust_store: TrustStore
+}
+
+/// How the client handles mods, see [`ClientRuntime::create_with_options`].
+pub struct ClientOptions {
+    /// also called when the server reloads a mod
+    pub download_progress: ProgressCallback,
+    pub mod_cache: ModCache,
+    pub trust_store: TrustStore
+}
+
+impl Default for ClientOptions {
+    fn default() -> Self {
+        Self {
+            download_progress: log_progress(),
+            mod_cache: ModCache::open(DEFAULT_MOD_CACHE_CAP),
+            trust_store: TrustStore::open(TrustPolicy::default())
+        }
+    }
 }
 
 pub(crate) struct LoadingMod{
-    #[allow(unused)]
-    name: String,
-    path: String,
-    flags: Vec<String>,
-    hash: String,
-    total_size: u64,
-    size: u64,
-    data: Vec<u8>,
+    info: ModInfo,
     available: bool
 }
 
+impl LoadingMod {
+    /// Only mods missing from the cache get downloaded.
+    /// Fails if the server sent a hash that is not a sha256.
+    fn new(mut info: ModInfo, cache: &ModCache) -> ErrorResult<Self> {
+        info.hash = normalize_hash(&info.hash)?;
+        Ok(Self {
+            available: cache.contains(&info.hash),
+            info
+        })
+    }
+}
+
+pub type HandleCreator = Box<dyn Fn() -> Box<dyn ClientHandle>>;
+
 pub(crate) struct ClientHandleBox {
+    pub(crate) handle_type: TypeId,
     pub(crate) handle: Box<dyn ClientHandle>,
     pub(crate) messenger: ClientMessenger
 }
 
-type LoadingModList = Rc<RefCell<HashMap<String, Rc<RefCell<LoadingMod>>>>>;
+/// in the load order advertised by the server
+type LoadingModList = Rc<RefCell<Vec<(String, Rc<RefCell<LoadingMod>>)>>>;
 
 impl ClientRuntime {
     pub fn create(client_id: Id, addr: &str, server_addr: &str, store: &mut DataStore) -> ErrorResult<Self>{
+        Self::create_with_options(client_id, addr, server_addr, store, ClientOptions::default())
+    }
+
+    pub fn create_with_options(client_id: Id, addr: &str, server_addr: &str, store: &mut DataStore, options: ClientOptions) -> ErrorResult<Self>{
         let nc = NetworkClient::start(addr, server_addr).map_err(|e| {
             e.log_exit();
         }).unwrap();
         log!("started client {addr} and initiating handshake to {server_addr}");
+        Self::create_with(client_id, nc, store, options, false)
+    }
+
+    /// Connects to the embedded server of a singleplayer game, see [`crate::singleplayer::EmbeddedServer`].
+    /// Mods are taken from the server's runtime directory instead of being downloaded.
+    pub fn create_singleplayer(client_id: Id, transport: impl ClientTransport + 'static, store: &mut DataStore, options: ClientOptions) -> ErrorResult<Self>{
+        log!("started singleplayer client and initiating handshake to the embedded server");
+        Self::create_with(client_id, NetworkClient::with_transport(Box::new(transport)), store, options, true)
+    }
+
+    /// Connects without downloading or loading any mods, for clients without a window like bots.
+    /// Handles of the types in `handles` are created when the server adds them, all others are ignored.
+    /// The client is driven with [`ClientRuntime::update_headless`], its handles never get a renderer.
+    pub fn create_headless(client_id: Id, transport: impl ClientTransport + 'static, handles: IdMap<HandleCreator>) -> ErrorResult<Self>{
+        let (mut client, _) = Self::connect(client_id, NetworkClient::with_transport(Box::new(transport)), ClientOptions::default())?;
+        client.registered_handles = handles;
+        Ok(client)
+    }
+
+    fn create_with(client_id: Id, nc: NetworkClient, store: &mut DataStore, options: ClientOptions, singleplayer: bool) -> ErrorResult<Self>{
+        let (mut client, mod_list) = Self::connect(client_id, nc, options)?;
+        if singleplayer {
+            // the player picked the mods of their own server
+            let _ = client.copy_local_mods(&mod_list).map_err(|e| client.gracefully_abort(e));
+        } else {
+            let _ = client.check_trust(&mod_list).map_err(|e| client.gracefully_abort(e));
+            let _ = client.download_mods(&mod_list).map_err(|e| client.gracefully_abort(e));
+        }
+
+        let _ = client.enable_mods(&mod_list, store).map_err(|e| client.gracefully_abort(e));
+
+        log!("finished client creation");
+        Ok(client)
+    }
+
+    /// Registers at the server and returns the mods it uses.
+    fn connect(client_id: Id, nc: NetworkClient, options: ClientOptions) -> ErrorResult<(Self, LoadingModList)>{
         let mut client = Self {
             client_id,
             nc: Rc::new(RefCell::new(nc)),
             mod_profile: String::new(),
             mod_profile_version: String::new(),
             awaiting_replies: Default::default(),
+            ping_timeouts: Default::default(),
             registered_handles: Default::default(),
             handles: Default::default(),
             loaded_mods: vec![],
             state: ClientState::Start,
+            pending_reloads: vec![],
+            deferred_packets: vec![],
+            download_progress: options.download_progress,
+            mod_cache: options.mod_cache,
+            trust_store: options.trust_store,
         };
         let mod_list = client.register()?;
-        let timeout_socket = client.nc.borrow().udp.try_clone()?;
-        thread::spawn(move || {
-            loop {
-                let data = SerBin::serialize_bin(&ClientPacket {
-                    client_id,
-                    conv_id: Id::new(),
-                    message: ClientMessage::KeepAlive,
-                });
-                let _ = timeout_socket.send(data.as_slice()).map_err(|e|{
-                    let e: Box<Error> = e.into();
-                    log!(ERROR, "{e}");
-                    exit(1);
-                });
-                std::thread::sleep(Duration::from_millis((MAX_CLIENT_TIMEOUT / 2) as u64))
-            }
-        });
+        client.nc.borrow().spawn_keep_alive(client_id)?;
         log!("started timeout preventer");
-        let _ = client.download_mods(&mod_list).map_err(|e| client.gracefully_abort(e));
+        Ok((client, mod_list))
+    }
 
-        let _ = client.enable_mods(&mod_list, store).map_err(|e| client.gracefully_abort(e));
+    pub fn login(&self) -> ErrorResult<()> {
+        self.nc.borrow().send(&ClientPacket {
+            client_id: self.client_id,
+            conv_id: Id::new(),
+            message: ClientMessage::Login,
+        }, SendMode::Safe)
+    }
 
-        log!("finished client creation");
-        Ok(client)
+    pub fn logout(&self) -> ErrorResult<()> {
+        self.nc.borrow().send(&ClientPacket {
+            client_id: self.client_id,
+            conv_id: Id::new(),
+            message: ClientMessage::Logout,
+        }, SendMode::Safe)
+    }
+
+    /// Handles the received packets of a client created with [`ClientRuntime::create_headless`].
+    pub fn update_headless(&mut self, store: &mut DataStore) -> ErrorResult<()> {
+        let result = self.handle_queued(store, None);
+        // there are no mods to reload, the deferred packets are handled next time
+        self.pending_reloads.clear();
+        self.timeout_requests(store, None);
+        result
+    }
+
+    /// The reason the server gave for unregistering the client, e.g. a timeout.
+    pub fn unregistered(&self) -> Option<&str> {
+        match &self.state {
+            ClientState::Unregistered(reason) => Some(reason),
+            _ => None
+        }
+    }
+
+    /// Measures the round trip time to the server. `on_pong` is called with it once the answer
+    /// arrived, or with [`RequestError::Timeout`] if it did not arrive within `timeout`.
+    pub fn ping(&mut self, timeout: Duration, on_pong: impl Fn(Result<Duration, RequestError>) + 'static) -> ErrorResult<()> {
+        let sent = Instant::now();
+        let conv_id = Id::new();
+        let on_pong = Rc::new(on_pong);
+        let on_timeout = on_pong.clone();
+        self.ping_timeouts.insert(conv_id, (sent + timeout, Box::new(move || on_timeout(Err(RequestError::Timeout)))));
+        self.request_response(&ClientPacket {
+            client_id: self.client_id,
+            conv_id,
+            message: ClientMessage::Ping(String::new()),
+        }, move |client, resp| {
+            client.ping_timeouts.remove(&conv_id);
+            match &resp.message {
+                ServerMessage::Pong(_) => on_pong(Ok(sent.elapsed())),
+                e => log!(ERROR, "invalid response to ping: {e:?}")
+            }
+        }, SendMode::Safe)
+    }
+
+    /// Bytes and packets sent and received so far, without keep-alives.
+    pub fn traffic(&self) -> Traffic {
+        self.nc.borrow().traffic.get()
+    }
+
+    /// The messenger of the handle of `entity`, if the server added one.
+    pub fn messenger(&mut self, entity: &EntityId) -> Option<&mut ClientMessenger> {
+        self.handles.get_mut(entity).map(|h| &mut h.messenger)
     }
 
     pub(crate) fn handles(&mut self) -> &mut IdMap<ClientHandleBox> {
@@ -130,8 +274,10 @@ impl ClientRuntime {
     }
 
     fn register(&mut self) -> ErrorResult<LoadingModList>{
-        let mod_list = Rc::new(RefCell::new(HashMap::new()));
+        let mod_list = Rc::new(RefCell::new(vec![]));
         let mod_list_filler = mod_list.clone();
+        let refused: Rc<RefCell<Option<String>>> = Default::default();
+        let refused_filler = refused.clone();
         self.request_response(&ClientPacket {
             client_id: self.client_id,
             conv_id: Id::new(),
@@ -146,153 +292,297 @@ impl ClientRuntime {
                     match res {
                         NetResult::Ok(info) => {
                             log!("successfully connected to server");
-                            log!("registered client");
-                            client.state = ClientState::Registered;
                             client.mod_profile = info.mod_profile.clone();
                             client.mod_profile_version = info.mod_version.clone();
                             log!("server has mod profile {} v{} with {} mod(s):", client.mod_profile, client.mod_profile_version, info.mods.len());
-                            let local_mod_list: HashMap<_, _> = info.mods.clone().into_iter()
-                                .map(|(name_path, flags, hash, size)| {
-                                    let (path, name) = name_path.split_once(':').unwrap();
-                                    let mut local_hash = String::new();
-                                    let _ = File::open(mod_hash(path)).map(|mut f| f.read_to_string(&mut local_hash));
-                                    let available = local_hash.trim() == hash;
-                                    log!("  - {name_path}");
-                                    if !available {
-                                        let _ = std::fs::remove_dir_all(format!("runtime/{path}"));
-                                        (name_path.clone(),  Rc::new(RefCell::new(LoadingMod {
-                                            name: name.to_string(),
-                                            path: path.to_string(),
-                                            flags,
-                                            hash,
-                                            total_size: size,
-                                            size: 0,
-                                            data: vec![0;size as usize],
-                                            available: false,
-                                        })))
-                                    } else {
-                                        (name_path.clone(), Rc::new(RefCell::new(LoadingMod {
-                                            name: name.to_string(),
-                                            path: name.to_string(),
-                                            flags,
-                                            hash,
-                                            total_size: size,
-                                            size, // already fully available, is redundant
-                                            data: vec![],
-                                            available: true,
-                                        })))
-                                    }
-                                }).collect();
-                            mod_list_filler.replace(local_mod_list);
+                            let local_mod_list = info.mods.clone().into_iter()
+                                .map(|info| {
+                                    log!("  - {}", info.name_path);
+                                    let name_path = info.name_path.clone();
+                                    LoadingMod::new(info, &client.mod_cache).map(|lm| (name_path, Rc::new(RefCell::new(lm))))
+                                }).collect::<ErrorResult<Vec<_>>>();
+                            match local_mod_list {
+                                Ok(local_mod_list) => {
+                                    log!("registered client");
+                                    client.state = ClientState::Registered;
+                                    mod_list_filler.replace(local_mod_list);
+                                }
+                                Err(e) => {
+                                    refused_filler.replace(Some(format!("server sent an invalid mod list: {e}")));
+                                }
+                            }
                         }
                         NetResult::Err(msg) => {
-                            log!(ERROR, "server did not accept connection: {msg}");
-                            exit(1);
+                            refused_filler.replace(Some(format!("server did not accept connection: {msg}")));
                         }
                     }
                 },
                 e => {
-                    log!(ERROR, "invalid response: {e:?}");
-                    exit(1);
+                    refused_filler.replace(Some(format!("invalid response: {e:?}")));
                 }
             }
         }, SendMode::Safe)?;
         while self.state != ClientState::Registered {
-            let packets = self.nc.borrow_mut().queued_packets();
-            for packet in packets {
-                self.handle_packet(&packet, &mut DataStore::new(), &mut RenderContext::new())?;
+            if let Some(msg) = refused.take() {
+                return Err(Error::new(NetworkError(msg), Fatality::FATAL, false))
             }
+            self.handle_replies();
+            thread::sleep(Duration::from_millis(1));
         }
         Ok(mod_list)
     }
 
+    /// Only handles replies to requests, all other packets are deferred
+    /// until the next call to [`ClientRuntime::handle_queued`].
+    fn handle_replies(&mut self) {
+        let packets = self.nc.borrow_mut().queued_packets();
+        for packet in packets {
+            if let Some(handler) = self.awaiting_replies.remove(&packet.conv_id) {
+                handler(self, &packet);
+            } else {
+                self.deferred_packets.push(packet);
+            }
+        }
+    }
+
+    /// Downloads all mods that are not available locally, with at most [`MAX_CHUNKS_IN_FLIGHT`]
+    /// chunk requests at once. Timed out chunks are requested again and every zip is
+    /// verified against its advertised hash before it gets unpacked.
     fn download_mods(&mut self, mod_list: &LoadingModList) -> ErrorResult<()>{
-        log!("downloading {} mod(s)", mod_list.borrow().values().filter(|m| !m.borrow().available).count());
-        let mut borrowed_ml = mod_list.borrow_mut();
-        for (name_path, lm) in borrowed_ml.iter_mut() {
-            let lmb = lm.borrow_mut();
-            if lmb.available {
-                continue
+        let missing: Vec<_> = mod_list.borrow().iter()
+            .filter(|(_, m)| !m.borrow().available)
+            .map(|(name_path, m)| (name_path.clone(), m.clone()))
+            .collect();
+        log!("downloading {} mod(s)", missing.len());
+        let mut downloads = missing.iter()
+            .map(|(name_path, lm)| {
+                let lm = lm.borrow();
+                PartialDownload::open(name_path, &lm.info.hash, lm.info.size).map(Some)
+            })
+            .collect::<ErrorResult<Vec<_>>>()?;
+        let mut progress = DownloadProgress {
+            name_path: String::new(),
+            downloaded: downloads.iter().flatten().map(|d| d.downloaded()).sum(),
+            total: downloads.iter().flatten().map(|d| d.total_size()).sum(),
+            finished_mods: 0,
+            total_mods: missing.len()
+        };
+
+        // (mod index, offset, retries)
+        let mut queue: VecDeque<(usize, u64, u32)> = downloads.iter().enumerate()
+            .filter_map(|(i, d)| d.as_ref().map(|d| (i, d)))
+            .flat_map(|(i, d)| d.missing_chunks().map(move |offset| (i, offset, 0)).collect::<Vec<_>>())
+            .collect();
+        let mut in_flight: IdMap<(usize, u64, u32, Instant)> = Default::default();
+        let received: Rc<RefCell<Vec<(Id, Vec<u8>)>>> = Default::default();
+
+        loop {
+            for (i, download) in downloads.iter_mut().enumerate() {
+                if download.as_ref().is_some_and(|d| d.is_complete()) {
+                    let (name_path, lm) = &missing[i];
+                    Self::finish_download(&mut self.mod_cache, &mut lm.borrow_mut(), download.take().unwrap())?;
+                    progress.finished_mods += 1;
+                    progress.name_path = name_path.clone();
+                    (self.download_progress)(&progress);
+                    log!("finished downloading mod {name_path}");
+                }
             }
-            let total_size = lmb.total_size;
-            drop(lmb);
-            for i in (0..total_size).step_by(MOD_DOWNLOAD_CHUNK_SIZE) {
-                let lm = lm.clone();
+            if queue.is_empty() && in_flight.is_empty() {
+                break
+            }
+
+            while in_flight.len() < MAX_CHUNKS_IN_FLIGHT && let Some((i, offset, retries)) = queue.pop_front() {
+                let conv_id = Id::new();
+                let received = received.clone();
                 self.request_response(&ClientPacket {
                     client_id: self.client_id,
-                    conv_id: Id::new(),
-                    message: ClientMessage::DownloadMod(name_path.clone(), MOD_TARGET.to_string(), i),
+                    conv_id,
+                    message: ClientMessage::DownloadMod(missing[i].0.clone(), MOD_TARGET.to_string(), offset),
                 }, move |_client, resp| {
-                    let mut lmb = lm.borrow_mut();
                     match &resp.message {
-                        ServerMessage::RawData(data) => {
-                            lmb.size += data.len() as u64;
-                            lmb.data.splice(i as usize..(i as usize+data.len()), data.to_owned());
-                        },
-                        e => {
-                            log!(ERROR, "invalid response: {e:?}");
-                            exit(1);
-                        }
+                        ServerMessage::RawData(data) => received.borrow_mut().push((conv_id, data.clone())),
+                        e => log!(ERROR, "invalid response to mod download: {e:?}")
                     }
-                }, SendMode::Safe).map_err(|e| {
-                    e.log_exit();
-                }).unwrap();
-                //std::thread::sleep(Duration::from_nanos(20));
-                //let packets = self.nc.borrow_mut().queued_packets();
-                //for packet in packets {
-                //    self.handle_packet(&packet)?;
-                //}
+                }, SendMode::Safe)?;
+                in_flight.insert(conv_id, (i, offset, retries, Instant::now()));
             }
-        }
-        log!("(sent all mod download requests)");
-        let mut p = 0.0;
-        while self.state != ClientState::DownloadedMods {
-            let packets = self.nc.borrow_mut().queued_packets();
-            for packet in packets {
-                self.handle_packet(&packet, &mut DataStore::new(), &mut RenderContext::new())?;
+
+            self.handle_replies();
+            let chunks = std::mem::take(&mut *received.borrow_mut());
+            if chunks.is_empty() {
+                thread::sleep(Duration::from_millis(1));
             }
-            let mut total = 0;
-            let mut downloaded = 0;
-            for (key, lm) in borrowed_ml.iter_mut(){
-                let mut lm = lm.borrow_mut();
-                downloaded += lm.size;
-                total += lm.total_size;
-                if !lm.available && lm.size == lm.total_size {
-                    lm.available = true;
-                    log!("unzipping...");
-                    unzip_archive(Cursor::new(&lm.data), &format!("runtime/{}", lm.path))?;
-                    File::create(mod_hash(&lm.path)).unwrap().write_all(lm.hash.as_bytes())?;
-                    log!("finished downloading mod {}", key)
+            for (conv_id, data) in chunks {
+                let Some((i, offset, retries, _)) = in_flight.remove(&conv_id) else { continue };
+                let Some(download) = downloads[i].as_mut() else { continue };
+                let before = download.downloaded();
+                if download.write_chunk(offset, &data)? {
+                    progress.downloaded += download.downloaded() - before;
+                    progress.name_path = missing[i].0.clone();
+                    (self.download_progress)(&progress);
+                } else {
+                    log!(WARN, "received chunk of {} at {offset} with unexpected length {}", missing[i].0, data.len());
+                    retry_chunk(&mut queue, &missing[i].0, i, offset, retries)?;
                 }
             }
-            if downloaded as f32 / total as f32 - p > 0.2 {
-                p = downloaded as f32 / total as f32;
-                log!("progress: {:02.1}%", p * 100.0);
-            }
-            if downloaded == total {
-                log!("progress: 100%");
-                self.state = ClientState::DownloadedMods
+
+            let now = Instant::now();
+            let timed_out: Vec<_> = in_flight.iter()
+                .filter(|(_, (.., sent))| now - *sent > CHUNK_TIMEOUT)
+                .map(|(id, _)| *id)
+                .collect();
+            for id in timed_out {
+                // a late reply is ignored from now on
+                self.awaiting_replies.remove(&id);
+                let (i, offset, retries, _) = in_flight.remove(&id).unwrap();
+                log!(WARN, "chunk of {} at {offset} timed out", missing[i].0);
+                retry_chunk(&mut queue, &missing[i].0, i, offset, retries)?;
             }
         }
+        self.state = ClientState::DownloadedMods;
         log!("downloaded all missing mods");
+        let in_use = mod_list.borrow().iter().map(|(_, lm)| lm.borrow().info.hash.clone())
+            .chain(self.loaded_mods.iter().map(|m| m.hash.clone()))
+            .collect();
+        self.mod_cache.evict(&in_use)
+    }
+
+    /// Puts the client zips the embedded server unpacked into the mod cache instead of downloading them.
+    /// Fails if a zip changed since the server advertised its hash.
+    fn copy_local_mods(&mut self, mod_list: &LoadingModList) -> ErrorResult<()> {
+        for (name_path, lm) in mod_list.borrow().iter() {
+            let mut lm = lm.borrow_mut();
+            if lm.available {
+                continue
+            }
+            let data = std::fs::read(unpacked_client_zip(name_path, MOD_TARGET))?;
+            let hash = format!("{:x}", Sha256::digest(&data));
+            if hash != lm.info.hash {
+                return Err(Error::new(ModError(format!("client zip of mod {name_path} changed while loading it")), Fatality::FATAL, false))
+            }
+            self.mod_cache.insert(&hash, &data)?;
+            lm.available = true;
+        }
+        Ok(())
+    }
+
+    /// Checks all mods against the trust store before anything gets downloaded or loaded.
+    fn check_trust(&mut self, mod_list: &LoadingModList) -> ErrorResult<()> {
+        for (_, lm) in mod_list.borrow().iter() {
+            self.trust_store.check(&lm.borrow().info)?;
+        }
+        Ok(())
+    }
+
+    fn finish_download(cache: &mut ModCache, lm: &mut LoadingMod, download: PartialDownload) -> ErrorResult<()> {
+        let data = download.verify()?;
+        log!("unzipping...");
+        cache.insert(&lm.info.hash, &data)?;
+        lm.available = true;
         Ok(())
     }
 
     fn enable_mods(&mut self, mod_list: &LoadingModList, store: &mut DataStore) -> ErrorResult<()>{
         for (name_path, lm) in mod_list.borrow_mut().iter_mut() {
-            log!("loading mod {} ...", name_path);
-            let mut loaded_mod = load_mod(name_path)?;
-            loaded_mod.init(&lm.borrow().flags);
-            let mut handles = Default::default();
-            loaded_mod.register_handlers(&mut handles, store);
-            self.registered_handles.extend(handles);
+            let lm = lm.borrow();
+            let loaded_mod = self.enable_mod(name_path, &lm.info.hash, lm.info.flags.clone(), store)?;
             self.loaded_mods.push(loaded_mod);
-            log!("loaded mod {} ...", name_path);
         }
         log!("successfully loaded {} mods from profile {} v{}", self.loaded_mods.len(), self.mod_profile, self.mod_profile_version);
         Ok(())
     }
 
+    fn enable_mod(&mut self, name_path: &str, hash: &str, flags: Vec<String>, store: &mut DataStore) -> ErrorResult<ClientModBox> {
+        let mut loaded_mod = self.load_cached_mod(name_path, hash)?;
+        self.register_mod(&mut loaded_mod, flags, store);
+        Ok(loaded_mod)
+    }
+
+    /// Loads the library of a mod in the cache, without running any of its code yet.
+    fn load_cached_mod(&mut self, name_path: &str, hash: &str) -> ErrorResult<ClientModBox> {
+        log!("loading mod {} ...", name_path);
+        self.mod_cache.touch(hash)?;
+        let mut loaded_mod = load_mod(name_path, hash)?;
+        loaded_mod.hash = hash.to_string();
+        Ok(loaded_mod)
+    }
+
+    /// Initializes a loaded mod and registers its handles and stores.
+    fn register_mod(&mut self, loaded_mod: &mut ClientModBox, flags: Vec<String>, store: &mut DataStore) {
+        loaded_mod.init(&flags);
+        loaded_mod.flags = flags;
+        let stores = store.stores();
+        let mut handles = Default::default();
+        loaded_mod.register_handlers(&mut handles, store);
+        loaded_mod.stores = store.stores().into_iter().filter(|s| !stores.contains(s)).collect();
+        loaded_mod.handles = handles.keys().copied().collect();
+        self.registered_handles.extend(handles.into_iter().map(|(ty, create)| (ty, Box::new(create) as HandleCreator)));
+        #[cfg(feature = "wasm")]
+        if let Some(wasm) = &loaded_mod.wasm {
+            let creators = wasm.handle_creators();
+            loaded_mod.handles.extend(creators.keys().copied());
+            self.registered_handles.extend(creators);
+        }
+        log!("loaded mod {} ...", loaded_mod.name_path);
+    }
+
+    #[cfg(feature = "window")]
+    pub(crate) fn start_mods(&mut self, store: &mut DataStore, window: &Window, context: &mut RenderContext) {
+        for loaded_mod in self.loaded_mods.iter_mut() {
+            start_mod(loaded_mod, store, window, context)
+        }
+    }
+
+    /// Replaces all mods the server reloaded with their new version.
+    ///
+    /// The new version is downloaded and loaded first, the old one stays if that fails.
+    /// Then all client handles, layers and stores of the old version are removed,
+    /// the server recreates the handles of the new version on its own.
+    #[cfg(feature = "window")]
+    pub(crate) fn reload_pending_mods(&mut self, store: &mut DataStore, window: &Window, context: &mut RenderContext) -> ErrorResult<()> {
+        for info in std::mem::take(&mut self.pending_reloads) {
+            let name_path = info.name_path.clone();
+            log!("server reloaded mod {name_path}, reloading...");
+            let mod_list: LoadingModList = Rc::new(RefCell::new(vec![
+                (name_path.clone(), Rc::new(RefCell::new(LoadingMod::new(info, &self.mod_cache)?)))
+            ]));
+            // the old version stays loaded if the new one is refused
+            self.check_trust(&mod_list)?;
+            let index = self.loaded_mods.iter().position(|m| m.name_path == name_path)
+                .ok_or_else(|| Error::new(ModError(format!("server reloaded mod {name_path} which is not loaded")), Fatality::DEFAULT, false))?;
+            self.download_mods(&mod_list)?;
+            let hash = mod_list.borrow()[0].1.borrow().info.hash.clone();
+            let mut loaded_mod = self.load_cached_mod(&name_path, &hash)?;
+
+            let old = self.loaded_mods.remove(index);
+            let handles = self.handles.iter()
+                .filter(|(_, h)| old.handles.contains(&h.handle_type))
+                .map(|(id, _)| *id)
+                .collect::<Vec<_>>();
+            for id in &handles {
+                self.remove_handle(id, store, Some(&*context));
+            }
+            for ty in &old.handles {
+                self.registered_handles.remove(ty);
+            }
+            for layer in &old.layers {
+                context.remove_layer(layer, store);
+            }
+            for ty in &old.stores {
+                store.remove_store_type(ty);
+            }
+            let flags = old.flags.clone();
+            // unloads the old library
+            drop(old);
+
+            self.register_mod(&mut loaded_mod, flags, store);
+            start_mod(&mut loaded_mod, store, window, context);
+            self.loaded_mods.insert(index, loaded_mod);
+            log!("reloaded mod {name_path}");
+        }
+        Ok(())
+    }
+
     fn gracefully_abort<E: Into<Box<Error>>>(&self, e: E) -> !{
         let err = e.into();
         err.log();
@@ -301,12 +591,36 @@ impl ClientRuntime {
     }
 }
 
-pub(crate) fn load_mod(name_path: &str) -> ErrorResult<ClientModBox> {
-    let (path, name) = name_path.split_once(':').unwrap();
-    let client_lib = unsafe { Library::new(client_lib(path, name))
+pub(crate) fn load_mod(name_path: &str, hash: &str) -> ErrorResult<ClientModBox> {
+    let (_, name) = name_path.split_once(':').unwrap();
+    if std::path::Path::new(&client_wasm(hash, name)).exists() {
+        return load_wasm_mod(name_path, &client_wasm(hash, name))
+    }
+    let client_lib = unsafe { Library::new(client_lib(hash, name))
         .map_err(|e| Error::new(ModError(format!("could not load mod: {e}")), Fatality::FATAL, false))? };
-    let _create_mod_client: Symbol<fn() -> Box<dyn ClientMod>> = unsafe { client_lib.get("_create_mod_client".as_ref())
-        .map_err(|e| Error::new(ModError(format!("could not load mod create function: {e}")), Fatality::FATAL, false))? };
-    let mod_client = _create_mod_client();
-    Ok(ClientModBox::new(mod_client, client_lib))
+    let create_mod_client = mod_entry(&client_lib, Side::Client, crate::abi_fingerprint(), name_path)?;
+    let mod_client = *unsafe { Box::from_raw(create_mod_client() as *mut Box<dyn ClientMod>) };
+    Ok(ClientModBox::new(name_path, mod_client, client_lib))
+}
+
+#[cfg(feature = "wasm")]
+fn load_wasm_mod(name_path: &str, file: &str) -> ErrorResult<ClientModBox> {
+    log!(DEBUG, "loading wasm mod: {file}");
+    let wasm_mod = crate::wasm::WasmClientMod::load(name_path, &std::fs::read(file)?)?;
+    Ok(ClientModBox::from_wasm(name_path, wasm_mod))
+}
+
+#[cfg(not(feature = "wasm"))]
+fn load_wasm_mod(name_path: &str, _file: &str) -> ErrorResult<ClientModBox> {
+    Err(Error::new(ModError(format!("could not load mod {name_path}: it is a wasm mod, but the client was built without the wasm feature")), Fatality::FATAL, false))
+}
+
+/// Starts the mod and records which layers it pushed.
+#[cfg(feature = "window")]
+fn start_mod(loaded_mod: &mut ClientModBox, store: &mut DataStore, window: &Window, context: &mut RenderContext) {
+    let layers = context.layer_stack.layer_map.keys().copied().collect::<Vec<_>>();
+    let stores = store.stores();
+    loaded_mod.client_mod.start(store, window.context_provider().with_render(context));
+    loaded_mod.layers = context.layer_stack.layer_map.keys().filter(|l| !layers.contains(l)).copied().collect();
+    loaded_mod.stores.extend(store.stores().into_iter().filter(|s| !stores.contains(s)));
 }
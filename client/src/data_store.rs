@@ -43,6 +43,10 @@ impl DataStore {
         self.stores.remove(&type_to_id::<T>()).is_some()
     }
     #[inline]
+    pub(crate) fn remove_store_type(&mut self, ty: &TypeId) -> bool {
+        self.stores.remove(ty).is_some()
+    }
+    #[inline]
     pub fn get_store<T: Sized + 'static>(&self) -> Nullable<&T> {
         self.stores.get(&type_to_id::<T>()).map(|m| unsafe { &*std::mem::transmute::<&Box<_>, &(*const T, usize)>(m).0 } ).into()
     }
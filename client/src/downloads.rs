@@ -0,0 +1,192 @@
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::time::Duration;
+use aeonetica_engine::error::{Error, Fatality, ErrorResult};
+use aeonetica_engine::error::builtin::ModError;
+use aeonetica_engine::log;
+use aeonetica_engine::networking::MOD_DOWNLOAD_CHUNK_SIZE;
+use aeonetica_engine::sha2::{Digest, Sha256};
+use crate::client_runtime::{partial_download, partial_download_meta};
+
+/// Number of chunk requests that are sent without having received a reply.
+pub const MAX_CHUNKS_IN_FLIGHT: usize = 32;
+/// Time after which a chunk request is sent again.
+pub const CHUNK_TIMEOUT: Duration = Duration::from_secs(2);
+/// Number of times a single chunk is requested again before the download fails.
+pub const MAX_CHUNK_RETRIES: u32 = 5;
+
+/// Progress of all mod downloads, passed to the progress callback of the [`crate::client_runtime::ClientRuntime`].
+#[derive(Debug, Clone)]
+pub struct DownloadProgress {
+    /// the mod the last chunk belonged to
+    pub name_path: String,
+    pub downloaded: u64,
+    pub total: u64,
+    pub finished_mods: usize,
+    pub total_mods: usize
+}
+
+impl DownloadProgress {
+    pub fn fraction(&self) -> f32 {
+        if self.total == 0 {
+            1.0
+        } else {
+            self.downloaded as f32 / self.total as f32
+        }
+    }
+}
+
+pub type ProgressCallback = Box<dyn FnMut(&DownloadProgress)>;
+
+/// The default progress callback, logs roughly every 20%.
+pub fn log_progress() -> ProgressCallback {
+    let mut last = 0.0;
+    Box::new(move |progress| {
+        let p = progress.fraction();
+        if p - last > 0.2 || (p == 1.0 && last != 1.0) {
+            last = p;
+            log!("progress: {:02.1}% ({}/{} mods)", p * 100.0, progress.finished_mods, progress.total_mods);
+        }
+    })
+}
+
+/// A mod zip that is downloaded into `runtime/mods/<hash>.part`.
+///
+/// The offsets of all received chunks are appended to `runtime/mods/<hash>.part.meta`,
+/// which starts with the hash of the zip, so an interrupted download of the same
+/// version continues where it stopped.
+pub(crate) struct PartialDownload {
+    name_path: String,
+    hash: String,
+    total_size: u64,
+    file: File,
+    meta: File,
+    received: Vec<bool>,
+    downloaded: u64
+}
+
+impl PartialDownload {
+    pub(crate) fn open(name_path: &str, hash: &str, total_size: u64) -> ErrorResult<Self> {
+        let chunks = (total_size as usize).div_ceil(MOD_DOWNLOAD_CHUNK_SIZE);
+        let mut received = vec![false; chunks];
+        let mut downloaded = 0;
+
+        let resumable = File::open(partial_download_meta(hash)).ok()
+            .map(|f| BufReader::new(f).lines().map_while(Result::ok).collect::<Vec<_>>())
+            .filter(|lines| lines.first().map(|h| h.trim()) == Some(hash))
+            .and_then(|lines| {
+                let offsets = lines.iter().skip(1)
+                    .map(|l| l.trim().parse::<u64>().ok().filter(|offset| chunk_len(*offset, total_size).is_some()))
+                    .collect::<Option<Vec<_>>>();
+                if offsets.is_none() {
+                    log!(WARN, "discarding the partial download of {name_path}, its metadata does not fit a zip of {total_size} bytes");
+                }
+                offsets
+            });
+        if let Some(offsets) = &resumable {
+            for &offset in offsets {
+                let chunk = offset as usize / MOD_DOWNLOAD_CHUNK_SIZE;
+                if !received[chunk] {
+                    received[chunk] = true;
+                    downloaded += chunk_len(offset, total_size).unwrap_or(0);
+                }
+            }
+            log!("resuming download of {name_path} at {downloaded}/{total_size} bytes");
+        }
+
+        if let Some(parent) = std::path::Path::new(&partial_download(hash)).parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new().read(true).write(true).create(true).truncate(resumable.is_none()).open(partial_download(hash))?;
+        file.set_len(total_size)?;
+        let mut meta = OpenOptions::new().append(true).create(true).open(partial_download_meta(hash))?;
+        if resumable.is_none() {
+            meta.set_len(0)?;
+            writeln!(meta, "{hash}")?;
+        }
+
+        Ok(Self {
+            name_path: name_path.to_string(),
+            hash: hash.to_string(),
+            total_size,
+            file,
+            meta,
+            received,
+            downloaded
+        })
+    }
+
+    /// The offsets of all chunks that were not received yet.
+    pub(crate) fn missing_chunks(&self) -> impl Iterator<Item=u64> + '_ {
+        self.received.iter().enumerate()
+            .filter(|(_, r)| !**r)
+            .map(|(i, _)| (i * MOD_DOWNLOAD_CHUNK_SIZE) as u64)
+    }
+
+    pub(crate) fn downloaded(&self) -> u64 {
+        self.downloaded
+    }
+
+    pub(crate) fn total_size(&self) -> u64 {
+        self.total_size
+    }
+
+    pub(crate) fn is_complete(&self) -> bool {
+        self.received.iter().all(|r| *r)
+    }
+
+    /// Writes the chunk at `offset`, returns false if no chunk starts there or it has an unexpected length.
+    pub(crate) fn write_chunk(&mut self, offset: u64, data: &[u8]) -> ErrorResult<bool> {
+        let chunk = offset as usize / MOD_DOWNLOAD_CHUNK_SIZE;
+        if chunk_len(offset, self.total_size) != Some(data.len() as u64) {
+            return Ok(false)
+        }
+        if self.received[chunk] {
+            return Ok(true)
+        }
+        self.file.seek(SeekFrom::Start(offset))?;
+        self.file.write_all(data)?;
+        writeln!(self.meta, "{offset}")?;
+        self.received[chunk] = true;
+        self.downloaded += data.len() as u64;
+        Ok(true)
+    }
+
+    /// Reads the complete zip and checks it against the advertised hash.
+    /// The partial download is removed from disk either way.
+    pub(crate) fn verify(mut self) -> ErrorResult<Vec<u8>> {
+        let mut data = Vec::with_capacity(self.total_size as usize);
+        self.file.flush()?;
+        self.file.seek(SeekFrom::Start(0))?;
+        self.file.read_to_end(&mut data)?;
+        let digest = format!("{:X}", Sha256::digest(&data));
+        self.discard();
+        if !digest.eq_ignore_ascii_case(&self.hash) {
+            return Err(Error::new(ModError(format!("downloaded mod {} has hash {digest}, but the server advertised {}", self.name_path, self.hash)), Fatality::FATAL, false))
+        }
+        Ok(data)
+    }
+
+    /// Removes the partial download from disk.
+    pub(crate) fn discard(&self) {
+        let _ = std::fs::remove_file(partial_download(&self.hash));
+        let _ = std::fs::remove_file(partial_download_meta(&self.hash));
+    }
+}
+
+/// The length of the chunk at `offset`, `None` if no chunk starts there.
+fn chunk_len(offset: u64, total_size: u64) -> Option<u64> {
+    (offset < total_size && offset % MOD_DOWNLOAD_CHUNK_SIZE as u64 == 0)
+        .then(|| (total_size - offset).min(MOD_DOWNLOAD_CHUNK_SIZE as u64))
+}
+
+/// Queues the chunk again, fails after [`MAX_CHUNK_RETRIES`] retries.
+/// The queue holds the index of the download, the offset and the number of retries.
+pub(crate) fn retry_chunk(queue: &mut VecDeque<(usize, u64, u32)>, name_path: &str, i: usize, offset: u64, retries: u32) -> ErrorResult<()> {
+    if retries >= MAX_CHUNK_RETRIES {
+        return Err(Error::new(ModError(format!("could not download chunk of mod {name_path} at {offset} after {retries} retries")), Fatality::FATAL, false))
+    }
+    queue.push_back((i, offset, retries + 1));
+    Ok(())
+}
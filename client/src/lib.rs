@@ -5,6 +5,7 @@
 
 use std::ops::{Deref, DerefMut};
 use aeonetica_engine::libloading::Library;
+use aeonetica_engine::TypeId;
 use aeonetica_engine::util::id_map::IdMap;
 use crate::data_store::DataStore;
 use crate::networking::messaging::ClientHandle;
@@ -13,9 +14,23 @@ use crate::renderer::window::OpenGlRenderContextProvider;
 
 pub mod networking;
 pub mod client_runtime;
+#[cfg(feature = "window")]
 pub mod client;
+#[cfg(feature = "window")]
+pub mod renderer;
+#[cfg(not(feature = "window"))]
+#[path = "renderer/headless.rs"]
 pub mod renderer;
 pub mod data_store;
+pub mod downloads;
+pub mod mod_cache;
+pub mod trust;
+pub mod singleplayer;
+#[cfg(feature = "wasm")]
+mod wasm;
+
+#[cfg(test)]
+mod tests;
 
 pub trait ClientMod {
     #[allow(unused_variables)]
@@ -26,16 +41,63 @@ pub trait ClientMod {
     fn start<'a>(&self, store: &mut DataStore, provider: OpenGlRenderContextProvider<'a>) -> &'a mut RenderContext { provider.make_context() }
 }
 
+/// Fingerprint of the client side of the mod boundary, mods with a different one are not loaded.
+pub fn abi_fingerprint() -> u64 {
+    use aeonetica_engine::abi::{fingerprint, layout_of};
+    fingerprint(&[
+        layout_of::<Box<dyn ClientMod>>(),
+        layout_of::<Box<dyn ClientHandle>>(),
+        layout_of::<DataStore>(),
+        layout_of::<RenderContext>(),
+        layout_of::<networking::messaging::ClientMessenger>(),
+        layout_of::<renderer::Renderer>()
+    ])
+}
+
 pub struct ClientModBox {
+    pub(crate) name_path: String,
+    pub(crate) flags: Vec<String>,
+    /// sha256 of the zip, the key in the mod cache
+    pub(crate) hash: String,
+    // handle types, layers and stores registered by this mod, removed again when the mod gets reloaded
+    pub(crate) handles: Vec<TypeId>,
+    pub(crate) layers: Vec<TypeId>,
+    pub(crate) stores: Vec<TypeId>,
     client_mod: Box<dyn ClientMod>,
-    _library: Library
+    #[cfg(feature = "wasm")]
+    pub(crate) wasm: Option<wasm::WasmClientMod>,
+    /// `None` for wasm mods
+    _library: Option<Library>
 }
 
 impl ClientModBox {
-    pub fn new(client_mod: Box<dyn ClientMod>, library: Library) -> Self{
+    pub fn new(name_path: &str, client_mod: Box<dyn ClientMod>, library: Library) -> Self{
         Self {
+            name_path: name_path.to_string(),
+            flags: vec![],
+            hash: String::new(),
+            handles: vec![],
+            layers: vec![],
+            stores: vec![],
             client_mod,
-            _library: library,
+            #[cfg(feature = "wasm")]
+            wasm: None,
+            _library: Some(library),
+        }
+    }
+
+    #[cfg(feature = "wasm")]
+    pub(crate) fn from_wasm(name_path: &str, wasm_mod: wasm::WasmClientMod) -> Self {
+        Self {
+            name_path: name_path.to_string(),
+            flags: vec![],
+            hash: String::new(),
+            handles: vec![],
+            layers: vec![],
+            stores: vec![],
+            client_mod: Box::new(wasm_mod.clone()),
+            wasm: Some(wasm_mod),
+            _library: None,
         }
     }
 }
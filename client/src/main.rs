@@ -7,7 +7,7 @@
 use std::net::SocketAddr;
 
 use aeonetica_engine::{log, Id};
-use client::{client::run, data_store::DataStore, client_runtime::ClientRuntime};
+use client::{client::run, data_store::DataStore, client_runtime::{ClientOptions, ClientRuntime}, singleplayer::EmbeddedServer};
 
 mod defaults {
     pub(crate) const CLIENT_IP: &str = "127.0.0.1:9000";
@@ -26,7 +26,11 @@ fn main() {
 
     match args.as_slice() {
         [a, ..] if a == "--help" => {
-            log!("Usage: {} [<client ip>] [<server ip>] | --help", std::env::args().next().unwrap());
+            log!("Usage: {} [<client ip>] [<server ip>] | --singleplayer | --help", std::env::args().next().unwrap());
+            return;
+        }
+        [a] if a == "--singleplayer" => {
+            run_singleplayer();
             return;
         }
         [c_ip, _] if SocketAddr::parse_ascii(c_ip.as_bytes()).is_err() => {
@@ -55,7 +59,23 @@ fn main() {
         e.log_exit();
     }).unwrap();
 
-    if let Err(err) = run(client, client_id, &mut store) {
+    if let Err(err) = run(client, &mut store) {
+        err.log_exit()
+    }
+}
+
+/// Plays alone with a server in the same process, which loads the mods in `mods/`.
+fn run_singleplayer() {
+    let (server, transport) = EmbeddedServer::start();
+    let client_id = Id::new();
+    let mut store = DataStore::new();
+    let client = ClientRuntime::create_singleplayer(client_id, transport, &mut store, ClientOptions::default()).map_err(|e| {
+        e.log_exit();
+    }).unwrap();
+
+    let result = run(client, &mut store);
+    drop(server);
+    if let Err(err) = result {
         err.log_exit()
     }
 }
@@ -0,0 +1,148 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Cursor, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use aeonetica_engine::error::{Error, ErrorResult, Fatality};
+use aeonetica_engine::error::builtin::ModError;
+use aeonetica_engine::log;
+use aeonetica_engine::util::unzip_archive;
+use crate::client_runtime::{cached_mod, MOD_CACHE_DIR, MOD_CACHE_INDEX};
+
+/// Size the mod cache gets evicted down to, unless set otherwise with [`ModCache::set_cap`].
+pub const DEFAULT_MOD_CACHE_CAP: u64 = 1 << 30;
+
+struct CacheEntry {
+    size: u64,
+    /// milliseconds since the unix epoch
+    last_used: u128
+}
+
+/// Unpacked client mods, stored in `runtime/mods/<hash>` by the sha256 of their zip,
+/// so different versions of the same mod (e.g. used by different servers) live side by side.
+///
+/// The least recently used mods are removed once the cache grows beyond its cap.
+pub struct ModCache {
+    cap: u64,
+    entries: HashMap<String, CacheEntry>
+}
+
+impl ModCache {
+    /// Reads the index at `runtime/mods/index`, entries without a directory are dropped.
+    pub fn open(cap: u64) -> Self {
+        let entries = File::open(MOD_CACHE_INDEX).ok()
+            .map(|f| BufReader::new(f).lines().map_while(Result::ok).collect::<Vec<_>>())
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|line| {
+                let mut parts = line.split_whitespace();
+                let hash = normalize_hash(parts.next()?).ok()?;
+                let size = parts.next()?.parse().ok()?;
+                let last_used = parts.next()?.parse().ok()?;
+                Path::new(&cached_mod(&hash)).is_dir().then_some((hash, CacheEntry { size, last_used }))
+            })
+            .collect();
+        Self {
+            cap,
+            entries
+        }
+    }
+
+    pub fn contains(&self, hash: &str) -> bool {
+        self.entries.contains_key(hash)
+    }
+
+    /// Total size of all unpacked mods in bytes.
+    pub fn size(&self) -> u64 {
+        self.entries.values().map(|e| e.size).sum()
+    }
+
+    pub fn cap(&self) -> u64 {
+        self.cap
+    }
+
+    /// Sets the cap and evicts all mods above it, except the ones in `in_use`.
+    pub fn set_cap(&mut self, cap: u64, in_use: &HashSet<String>) -> ErrorResult<()> {
+        self.cap = cap;
+        self.evict(in_use)
+    }
+
+    /// Unpacks a verified mod zip into the cache.
+    pub(crate) fn insert(&mut self, hash: &str, zip: &[u8]) -> ErrorResult<()> {
+        let dir = cached_mod(hash);
+        let _ = std::fs::remove_dir_all(&dir);
+        unzip_archive(Cursor::new(zip), &dir)?;
+        self.entries.insert(hash.to_string(), CacheEntry {
+            size: dir_size(Path::new(&dir)),
+            last_used: now()
+        });
+        self.save()
+    }
+
+    /// Marks the mod as recently used.
+    pub(crate) fn touch(&mut self, hash: &str) -> ErrorResult<()> {
+        if let Some(entry) = self.entries.get_mut(hash) {
+            entry.last_used = now();
+        }
+        self.save()
+    }
+
+    /// Removes the least recently used mods until the cache fits its cap.
+    pub(crate) fn evict(&mut self, in_use: &HashSet<String>) -> ErrorResult<()> {
+        let mut candidates: Vec<_> = self.entries.iter()
+            .filter(|(hash, _)| !in_use.contains(*hash))
+            .map(|(hash, e)| (e.last_used, hash.clone()))
+            .collect();
+        candidates.sort();
+        let mut size = self.size();
+        let mut evicted = false;
+        for (_, hash) in candidates {
+            if size <= self.cap {
+                break
+            }
+            log!("evicting mod {hash} from cache");
+            let _ = std::fs::remove_dir_all(cached_mod(&hash));
+            size -= self.entries.remove(&hash).unwrap().size;
+            evicted = true;
+        }
+        if evicted {
+            self.save()?;
+        }
+        Ok(())
+    }
+
+    fn save(&self) -> ErrorResult<()> {
+        std::fs::create_dir_all(MOD_CACHE_DIR)?;
+        let mut index = File::create(MOD_CACHE_INDEX)?;
+        for (hash, e) in &self.entries {
+            writeln!(index, "{hash} {} {}", e.size, e.last_used)?;
+        }
+        Ok(())
+    }
+}
+
+/// The sha256 of a mod zip as used in the paths of the cache: 64 lowercase hex digits.
+/// Hashes come from the server, anything else could point outside of `runtime/mods`.
+pub(crate) fn normalize_hash(hash: &str) -> ErrorResult<String> {
+    if hash.len() == 64 && hash.bytes().all(|b| b.is_ascii_hexdigit()) {
+        Ok(hash.to_ascii_lowercase())
+    } else {
+        Err(Error::new(ModError(format!("invalid mod hash {hash:?}")), Fatality::FATAL, false))
+    }
+}
+
+fn now() -> u128 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis()).unwrap_or_default()
+}
+
+fn dir_size(path: &Path) -> u64 {
+    std::fs::read_dir(path).into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| match entry.metadata() {
+            Ok(m) if m.is_dir() => dir_size(&entry.path()),
+            Ok(m) => m.len(),
+            Err(_) => 0
+        })
+        .sum()
+}
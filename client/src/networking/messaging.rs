@@ -1,9 +1,12 @@
 use std::cell::RefCell;
 use std::rc::Rc;
-use aeonetica_engine::{ClientId, EntityId, Id, TypeId};
+use std::time::{Duration, Instant};
+use aeonetica_engine::{ClientId, EntityId, Id, TypeId, log};
 use aeonetica_engine::nanoserde::{DeBin, SerBin};
 use aeonetica_engine::networking::client_packets::{ClientMessage, ClientPacket};
 use aeonetica_engine::networking::messaging::ClientEntity;
+use aeonetica_engine::networking::replication::{replication_id, Snapshot, SnapshotMessage};
+use aeonetica_engine::networking::rpc::{decode_args, decode_reply, handle_request, register_rpc, Request, RequestError, Rpc};
 use aeonetica_engine::networking::SendMode;
 use aeonetica_engine::util::id_map::IdMap;
 use aeonetica_engine::util::nullable::Nullable;
@@ -34,7 +37,15 @@ pub struct ClientMessenger {
     nc: Rc<RefCell<NetworkClient>>,
     client_id: ClientId,
     entity_id: EntityId,
-    pub(crate) client_receivers: IdMap<Box<dyn Fn(&mut dyn ClientHandle, &mut ClientMessenger, Nullable<&mut Renderer>,  &mut DataStore, &Vec<u8>)>>
+    pub(crate) client_receivers: IdMap<Box<dyn Fn(&mut dyn ClientHandle, &mut ClientMessenger, Nullable<&mut Renderer>,  &mut DataStore, &Vec<u8>)>>,
+    pub(crate) request_handlers: IdMap<Box<dyn Fn(&mut dyn ClientHandle, &mut ClientMessenger, Nullable<&mut Renderer>,  &mut DataStore, &Vec<u8>) -> Result<Vec<u8>, String>>>,
+    /// requests sent to the server by their conv_id
+    pub(crate) pending_requests: IdMap<PendingRequest>
+}
+
+pub(crate) struct PendingRequest {
+    pub(crate) deadline: Instant,
+    pub(crate) callback: Box<dyn FnOnce(&mut dyn ClientHandle, &mut ClientMessenger, Nullable<&mut Renderer>, &mut DataStore, Result<Vec<u8>, String>, bool)>
 }
 
 impl ClientMessenger {
@@ -43,12 +54,27 @@ impl ClientMessenger {
             nc,
             client_id,
             entity_id,
-            client_receivers: Default::default()
+            client_receivers: Default::default(),
+            request_handlers: Default::default(),
+            pending_requests: Default::default()
         }
     }
 }
 
 impl ClientMessenger {
+    pub fn entity_id(&self) -> EntityId {
+        self.entity_id
+    }
+
+    /// Sends already serialized data to the receiver `rid` on the server side of this entity.
+    pub(crate) fn send_raw(&self, rid: Id, data: Vec<u8>, mode: SendMode) {
+        let _ = self.nc.borrow().send(&ClientPacket {
+            client_id: self.client_id,
+            conv_id: Id::new(),
+            message: ClientMessage::ModMessage(self.entity_id, rid, data),
+        }, mode);
+    }
+
     pub fn register_receiver<F: Fn(&mut T, &mut ClientMessenger, Nullable<&mut Renderer>, &mut DataStore, M) + 'static, T: ClientHandle, M: SerBin + DeBin>(&mut self, f: F) {
         let m = move |handle: &mut dyn ClientHandle, messenger: &mut ClientMessenger, renderer: Nullable<&mut Renderer>, store: &mut DataStore, data: &Vec<u8>|
             f(unsafe { &mut *std::mem::transmute::<_, &(*mut T, usize)>(Box::new(handle)).0 }, messenger, renderer, store, M::deserialize_bin(data).unwrap());
@@ -59,6 +85,92 @@ impl ClientMessenger {
         self.client_receivers.remove(&type_to_id::<F>());
     }
 
+    /// Receives the state of a replicated server module, see `aeonetica_server::ecs::replication::Replicated`.
+    /// `f` is called with the whole, up to date snapshot every time it changed,
+    /// `full` is `true` if the complete state was sent, e.g. because this client just got added.
+    pub fn register_replicated<F: Fn(&mut T, &mut ClientMessenger, Nullable<&mut Renderer>, &mut DataStore, &S, bool) + 'static, T: ClientHandle, S: Snapshot>(&mut self, f: F) {
+        let state = RefCell::new(None::<S>);
+        let m = move |handle: &mut dyn ClientHandle, messenger: &mut ClientMessenger, renderer: Nullable<&mut Renderer>, store: &mut DataStore, data: &Vec<u8>| {
+            let mut state = state.borrow_mut();
+            let full = match SnapshotMessage::deserialize_bin(data) {
+                Ok(SnapshotMessage::Full(full)) => match S::deserialize_bin(&full) {
+                    Ok(snapshot) => {
+                        *state = Some(snapshot);
+                        true
+                    }
+                    Err(e) => {
+                        log!(ERROR, "invalid snapshot: {e:?}");
+                        return
+                    }
+                }
+                // the full state is sent reliably, but may arrive after a quick delta
+                Ok(SnapshotMessage::Delta(delta)) => match state.as_mut() {
+                    Some(snapshot) => match snapshot.apply_delta(&delta) {
+                        Ok(()) => false,
+                        Err(e) => {
+                            log!(ERROR, "invalid snapshot delta: {e:?}");
+                            return
+                        }
+                    }
+                    None => return
+                }
+                Err(e) => {
+                    log!(ERROR, "invalid snapshot message: {e:?}");
+                    return
+                }
+            };
+            f(unsafe { &mut *std::mem::transmute::<_, &(*mut T, usize)>(Box::new(handle)).0 }, messenger, renderer, store, state.as_ref().unwrap(), full)
+        };
+        self.client_receivers.insert(replication_id::<S>(), Box::new(m));
+    }
+
+    /// Registers `f` as the receiver of the rpc `R` sent by the server.
+    pub fn on<R: Rpc, T: ClientHandle>(&mut self, f: impl Fn(&mut T, &mut ClientMessenger, Nullable<&mut Renderer>, &mut DataStore, R::Args) + 'static) {
+        register_rpc::<R>();
+        let m = move |handle: &mut dyn ClientHandle, messenger: &mut ClientMessenger, renderer: Nullable<&mut Renderer>, store: &mut DataStore, data: &Vec<u8>| {
+            if let Some(args) = decode_args::<R>(data) {
+                f(unsafe { &mut *std::mem::transmute::<_, &(*mut T, usize)>(Box::new(handle)).0 }, messenger, renderer, store, args)
+            }
+        };
+        self.client_receivers.insert(R::id(), Box::new(m));
+    }
+
+    /// Registers `f` to answer the request `R` sent by the server.
+    pub fn on_request<R: Request, T: ClientHandle>(&mut self, f: impl Fn(&mut T, &mut ClientMessenger, Nullable<&mut Renderer>, &mut DataStore, R::Args) -> R::Reply + 'static) {
+        register_rpc::<R>();
+        let m = move |handle: &mut dyn ClientHandle, messenger: &mut ClientMessenger, renderer: Nullable<&mut Renderer>, store: &mut DataStore, data: &Vec<u8>|
+            handle_request::<R>(data, |args| f(unsafe { &mut *std::mem::transmute::<_, &(*mut T, usize)>(Box::new(handle)).0 }, messenger, renderer, store, args));
+        self.request_handlers.insert(R::id(), Box::new(m));
+    }
+
+    /// Sends the request `R` to the server side of this entity.
+    /// `callback` is called once with the reply, or with [`RequestError::Timeout`] if none arrived within `timeout`.
+    pub fn request<R: Request, T: ClientHandle>(&mut self, args: R::Args, timeout: Duration, callback: impl FnOnce(&mut T, &mut ClientMessenger, Nullable<&mut Renderer>, &mut DataStore, Result<R::Reply, RequestError>) + 'static) {
+        let conv_id = Id::new();
+        let callback = move |handle: &mut dyn ClientHandle, messenger: &mut ClientMessenger, renderer: Nullable<&mut Renderer>, store: &mut DataStore, reply: Result<Vec<u8>, String>, timed_out: bool| {
+            let reply = if timed_out { Err(RequestError::Timeout) } else { decode_reply::<R>(reply) };
+            callback(unsafe { &mut *std::mem::transmute::<_, &(*mut T, usize)>(Box::new(handle)).0 }, messenger, renderer, store, reply)
+        };
+        self.pending_requests.insert(conv_id, PendingRequest {
+            deadline: Instant::now() + timeout,
+            callback: Box::new(callback)
+        });
+        let _ = self.nc.borrow().send(&ClientPacket {
+            client_id: self.client_id,
+            conv_id,
+            message: ClientMessage::ModRequest(self.entity_id, R::id(), args.serialize_bin()),
+        }, SendMode::Safe);
+    }
+
+    /// Calls the rpc `R` on the server side of this entity.
+    pub fn send<R: Rpc>(&mut self, args: R::Args, mode: SendMode) {
+        let _ = self.nc.borrow().send(&ClientPacket {
+            client_id: self.client_id,
+            conv_id: Id::new(),
+            message: ClientMessage::ModMessage(self.entity_id, R::id(), args.serialize_bin()),
+        }, mode);
+    }
+
     pub fn call_server_fn<F: Fn(&EntityId, &mut Engine, &ClientId, M), M: SerBin + DeBin>(&mut self, _: F, message: M, mode: SendMode) {
         let id = type_to_id::<F>();
         let _ = self.nc.borrow().send(&ClientPacket {
@@ -1,100 +1,93 @@
-use std::cell::RefCell;
-use std::io::{Read, Write};
-use std::net::{TcpStream, UdpSocket};
-use std::sync::{Arc, Mutex};
+use std::cell::Cell;
+use std::time::Duration;
 use aeonetica_engine::error::{Error, Fatality, ErrorResult};
 use aeonetica_engine::error::builtin::NetworkError;
-use aeonetica_engine::{log};
+use aeonetica_engine::{ClientId, Id, log, MAX_CLIENT_TIMEOUT};
 use aeonetica_engine::nanoserde::{SerBin, DeBin};
 use aeonetica_engine::networking::{MAX_PACKET_SIZE, SendMode};
-use aeonetica_engine::networking::client_packets::{ClientPacket};
+use aeonetica_engine::networking::client_packets::{ClientMessage, ClientPacket};
 use aeonetica_engine::networking::server_packets::ServerPacket;
+use aeonetica_engine::networking::transport::ClientTransport;
 
 mod protocol;
+pub mod sockets;
 pub mod messaging;
 
 pub(crate) struct NetworkClient {
-    pub(crate) udp: UdpSocket,
-    pub(crate) tcp: RefCell<TcpStream>,
-    received: Arc<Mutex<Vec<ServerPacket>>>
+    transport: Box<dyn ClientTransport>,
+    pub(crate) traffic: Cell<Traffic>
+}
+
+/// What a client sent and received, see [`crate::client_runtime::ClientRuntime::traffic`].
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Traffic {
+    pub sent_bytes: u64,
+    pub sent_packets: u64,
+    pub received_bytes: u64,
+    pub received_packets: u64
 }
 
 impl NetworkClient {
     pub(crate) fn start(addr: &str, server: &str) -> ErrorResult<Self>{
-        let tcp = TcpStream::connect(server)?;
-        tcp.set_nonblocking(false).unwrap();
-        let udp = UdpSocket::bind(addr)?;
-        udp.connect(server)?;
-        let udp_sock = udp.try_clone()?;
-        let mut tcp_sock = tcp.try_clone()?;
-        let received = Arc::new(Mutex::new(vec![]));
-        let recv_udp = received.clone();
-        let recv_tcp = received.clone();
-        std::thread::spawn(move || {
-            let mut buf = [0u8; MAX_PACKET_SIZE];
-            loop {
-                match udp_sock.recv_from(&mut buf) {
-                    Ok((len, src)) => match DeBin::deserialize_bin(&buf[..len]) {
-                       Ok(packet) => recv_udp.lock().unwrap().push(packet),
-                       Err(e) => log!(ERROR, "invalid server packet from {src}: {e}")
-                    },
-                    Err(e) => {
-                        log!(ERROR, "couldn't recieve a datagram: {}", e);
-                    }
-                }
-            }
+        Ok(Self::with_transport(Box::new(sockets::SocketTransport::connect(addr, server)?)))
+    }
+
+    pub(crate) fn with_transport(transport: Box<dyn ClientTransport>) -> Self {
+        Self {
+            transport,
+            traffic: Default::default()
+        }
+    }
+
+    /// Keeps sending `KeepAlive` in the background, so the server does not time the client out.
+    pub(crate) fn spawn_keep_alive(&self, client_id: ClientId) -> ErrorResult<()> {
+        let packet = || SerBin::serialize_bin(&ClientPacket {
+            client_id,
+            conv_id: Id::new(),
+            message: ClientMessage::KeepAlive,
         });
+        let interval = Duration::from_millis((MAX_CLIENT_TIMEOUT / 2) as u64);
+        let mut send = self.transport.detached_sender()?;
         std::thread::spawn(move || {
             loop {
-                let mut size = [0u8;4];
-                tcp_sock.read_exact(&mut size).unwrap();
-                let size = u32::from_le_bytes(size);
-                let mut buffer: Vec<u8> = vec![0;size as usize];
-                tcp_sock.read_exact(&mut buffer[..]).unwrap();
-                match DeBin::deserialize_bin(&buffer[..]) {
-                    Ok(packet) => recv_tcp.lock().unwrap().push(packet),
-                    Err(e) => log!(ERROR, "invalid server packet: {e}")
+                if let Err(e) = send(packet()) {
+                    if matches!(e.fatality(), Fatality::FATAL) {
+                        e.log_exit()
+                    }
+                    // e.g. an embedded server that stopped
+                    return e.log()
                 }
+                std::thread::sleep(interval)
             }
         });
-        Ok(Self {
-            udp,
-            tcp: RefCell::new(tcp),
-            received
-        })
+        Ok(())
     }
 
     pub(crate) fn queued_packets(&mut self) -> Vec<ServerPacket> {
-        let mut packets = vec![];
-        std::mem::swap(&mut self.received.lock().unwrap() as &mut Vec<ServerPacket>, &mut packets);
-        packets
+        let received = self.transport.receive();
+        let traffic = self.traffic.get_mut();
+        traffic.received_packets += received.len() as u64;
+        traffic.received_bytes += received.iter().map(|data| data.len() as u64).sum::<u64>();
+        received.into_iter().filter_map(|data| match DeBin::deserialize_bin(&data) {
+            Ok(packet) => Some(packet),
+            Err(e) => {
+                log!(ERROR, "invalid server packet: {e}");
+                None
+            }
+        }).collect()
     }
 
     pub(crate) fn send(&self, packet: &ClientPacket, mode: SendMode) -> ErrorResult<()> {
         let data = SerBin::serialize_bin(packet);
-        match mode {
-            SendMode::Quick => {
-                if data.len() > MAX_PACKET_SIZE {
-                    return Err(Error::new(NetworkError(format!("Packet is too large: {} > {}", data.len(), MAX_PACKET_SIZE)), Fatality::WARN, false))
-                }
-                let sock = self.udp.try_clone()?;
-                std::thread::spawn(move || sock.send(&data[..]).map_err(|e| {
-                    let e: Box<Error> = e.into();
-                    e.log();
-                }));
-            }
-            SendMode::Safe => {
-                let mut tcp = self.tcp.borrow_mut();
-                let _ = tcp.write_all(&(data.len() as u32).to_le_bytes()).map_err(|e| {
-                    let e: Box<Error> = e.into();
-                    e.log();
-                });
-                let _ = tcp.write_all(&data[..]).map_err(|e| {
-                    let e: Box<Error> = e.into();
-                    e.log();
-                });
-            }
+        if let SendMode::Quick = mode && data.len() > MAX_PACKET_SIZE {
+            return Err(Error::new(NetworkError(format!("Packet is too large: {} > {}", data.len(), MAX_PACKET_SIZE)), Fatality::WARN, false))
         }
+        let len = data.len() as u64;
+        self.transport.send(data, mode)?;
+        let mut traffic = self.traffic.get();
+        traffic.sent_bytes += len;
+        traffic.sent_packets += 1;
+        self.traffic.set(traffic);
         Ok(())
     }
-}
\ No newline at end of file
+}
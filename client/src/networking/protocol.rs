@@ -1,27 +1,90 @@
-use std::process::exit;
+use std::cell::RefMut;
+use std::time::Instant;
 use aeonetica_engine::error::ErrorResult;
-use aeonetica_engine::log;
+use aeonetica_engine::{Id, log};
 use aeonetica_engine::networking::client_packets::{ClientMessage, ClientPacket};
-use aeonetica_engine::networking::SendMode;
+use aeonetica_engine::networking::rpc::rpc_name;
+use aeonetica_engine::networking::{NetResult, SendMode};
 use aeonetica_engine::networking::server_packets::{ServerMessage, ServerPacket};
 use aeonetica_engine::util::nullable::Nullable::{Null, Value};
-use crate::client_runtime::{ClientHandleBox, ClientRuntime};
+use crate::client_runtime::{ClientHandleBox, ClientRuntime, ClientState};
 use crate::data_store::DataStore;
-use crate::networking::messaging::ClientMessenger;
+use crate::networking::messaging::{ClientHandle, ClientMessenger};
+use crate::renderer::Renderer;
 use crate::renderer::context::RenderContext;
 
+/// The renderer of the layer a handle renders to, headless clients have no layers.
+#[cfg(feature = "window")]
+fn renderer_of<'a>(context: Option<&'a RenderContext>, handle: &dyn ClientHandle) -> Option<RefMut<'a, Renderer>> {
+    let layer = context?.layer_stack.layer_map.get(&handle.owning_layer())?;
+    Some(RefMut::map(layer.borrow_mut(), |layer| &mut layer.renderer))
+}
+
+#[cfg(not(feature = "window"))]
+fn renderer_of<'a>(context: Option<&'a RenderContext>, _handle: &dyn ClientHandle) -> Option<RefMut<'a, Renderer>> {
+    context.map(|context| match *context {})
+}
+
 impl ClientRuntime {
-    pub(crate) fn handle_queued(&mut self, store: &mut DataStore, context: &mut RenderContext) -> ErrorResult<()> {
-        let packets = self.nc.borrow_mut().queued_packets();
-        packets.into_iter().map(|packet| self.handle_packet(&packet, store, context))
-        .reduce(|acc, r| {
-            acc?;
-            r?;
-            Ok(())
-        }).unwrap_or(Ok(()))
+    pub(crate) fn handle_queued(&mut self, store: &mut DataStore, context: Option<&RenderContext>) -> ErrorResult<()> {
+        let mut packets = std::mem::take(&mut self.deferred_packets);
+        packets.extend(self.nc.borrow_mut().queued_packets());
+        let mut result = Ok(());
+        let mut packets = packets.into_iter();
+        while let Some(packet) = packets.next() {
+            let r = self.handle_packet(&packet, store, context);
+            if result.is_ok() {
+                result = r;
+            }
+            // everything after a reload belongs to the new version of the mod
+            if !self.pending_reloads.is_empty() {
+                self.deferred_packets.extend(packets);
+                break
+            }
+        }
+        result
+    }
+
+    pub(crate) fn remove_handle(&mut self, id: &Id, store: &mut DataStore, context: Option<&RenderContext>) {
+        if let Some(mut h) = self.handles.remove(id) {
+            if let Some(mut renderer) = renderer_of(context, &*h.handle) {
+                h.handle.remove(&mut h.messenger, Value(&mut *renderer), store);
+            } else {
+                h.handle.remove(&mut h.messenger, Null, store);
+            }
+        }
+    }
+
+    /// Calls the callbacks of all requests whose reply did not arrive in time.
+    pub(crate) fn timeout_requests(&mut self, store: &mut DataStore, context: Option<&RenderContext>) {
+        let now = Instant::now();
+        let expired: Vec<_> = self.ping_timeouts.iter()
+            .filter(|(_, (deadline, _))| *deadline <= now)
+            .map(|(id, _)| *id)
+            .collect();
+        for id in expired {
+            // a late pong is ignored from now on
+            self.awaiting_replies.remove(&id);
+            let (_, on_timeout) = self.ping_timeouts.remove(&id).unwrap();
+            on_timeout();
+        }
+        for h in self.handles.values_mut() {
+            let expired: Vec<_> = h.messenger.pending_requests.iter()
+                .filter(|(_, pending)| pending.deadline <= now)
+                .map(|(id, _)| *id)
+                .collect();
+            for id in expired {
+                let pending = h.messenger.pending_requests.remove(&id).unwrap();
+                if let Some(mut renderer) = renderer_of(context, &*h.handle) {
+                    (pending.callback)(&mut *h.handle, &mut h.messenger, Value(&mut *renderer), store, Err(String::new()), true)
+                } else {
+                    (pending.callback)(&mut *h.handle, &mut h.messenger, Null, store, Err(String::new()), true)
+                }
+            }
+        }
     }
 
-    pub(crate) fn handle_packet(&mut self, packet: &ServerPacket, store: &mut DataStore, context: &mut RenderContext) -> ErrorResult<()>{
+    pub(crate) fn handle_packet(&mut self, packet: &ServerPacket, store: &mut DataStore, context: Option<&RenderContext>) -> ErrorResult<()>{
         if let Some(handler) = self.awaiting_replies.remove(&packet.conv_id) {
             handler(self, packet);
             return Ok(())
@@ -34,7 +97,7 @@ impl ClientRuntime {
             }, SendMode::Safe)?,
             ServerMessage::Unregister(reason) => {
                 log!("server unregistered client: {reason}");
-                exit(0)
+                self.state = ClientState::Unregistered(reason.clone())
             }
             ServerMessage::AddClientHandle(eid, handle_id) => {
                 log!("added client handle: {handle_id}");
@@ -42,12 +105,13 @@ impl ClientRuntime {
                     let mut handle = creator();
                     handle.init();
                     let mut messenger = ClientMessenger::new(self.nc.clone(), self.client_id, *eid);
-                    if let Some(layer) = context.layer_stack.layer_map.get(&handle.owning_layer()) {
-                        handle.start(&mut messenger, Value(&mut layer.borrow_mut().renderer), store);
+                    if let Some(mut renderer) = renderer_of(context, &*handle) {
+                        handle.start(&mut messenger, Value(&mut *renderer), store);
                     } else {
                         handle.start(&mut messenger, Null, store);
                     }
                     self.handles.insert(*eid, ClientHandleBox {
+                        handle_type: *handle_id,
                         handle,
                         messenger,
                     })
@@ -55,26 +119,60 @@ impl ClientRuntime {
             }
             ServerMessage::RemoveClientHandle(id) => {
                 log!("remove client handle");
-                if let Some(mut h) = self.handles.remove(id) {
-                    if let Some(layer) = context.layer_stack.layer_map.get(&h.handle.owning_layer()) {
-                        h.handle.remove(&mut h.messenger, Value(&mut layer.borrow_mut().renderer), store);
-                    } else {
-                        h.handle.remove(&mut h.messenger, Null, store);
-                    }
-                }
+                self.remove_handle(id, store, context)
             }
             ServerMessage::ModMessage(eid, rid, data) => {
                 if let Some(h) = self.handles.get_mut(eid) {
                     if let Some(f) = h.messenger.client_receivers.remove(rid) {
-                        if let Some(layer) = context.layer_stack.layer_map.get(&h.handle.owning_layer()) {
-                            f(&mut *h.handle, &mut h.messenger, Value(&mut layer.borrow_mut().renderer), store, data)
+                        if let Some(mut renderer) = renderer_of(context, &*h.handle) {
+                            f(&mut *h.handle, &mut h.messenger, Value(&mut *renderer), store, data)
                         } else {
                             f(&mut *h.handle, &mut h.messenger, Null, store, data)
                         }
                         h.messenger.client_receivers.insert(*rid, f);
+                    } else {
+                        log!(ERROR, "server called rpc {rid} ({}) which handle {eid} has no receiver for", rpc_name(rid).as_deref().unwrap_or("unknown"));
+                    }
+                }
+            }
+            ServerMessage::ModRequest(eid, rid, data) => {
+                let Some(h) = self.handles.get_mut(eid) else { return Ok(()) };
+                let reply = if let Some(f) = h.messenger.request_handlers.remove(rid) {
+                    let reply = if let Some(mut renderer) = renderer_of(context, &*h.handle) {
+                        f(&mut *h.handle, &mut h.messenger, Value(&mut *renderer), store, data)
+                    } else {
+                        f(&mut *h.handle, &mut h.messenger, Null, store, data)
+                    };
+                    h.messenger.request_handlers.insert(*rid, f);
+                    reply
+                } else {
+                    log!(ERROR, "server sent request {rid} ({}) which handle {eid} has no handler for", rpc_name(rid).as_deref().unwrap_or("unknown"));
+                    Err(format!("no handler for request {}", rpc_name(rid).as_deref().unwrap_or("unknown")))
+                };
+                self.nc.borrow().send(&ClientPacket {
+                    client_id: self.client_id,
+                    conv_id: packet.conv_id,
+                    message: ClientMessage::ModReply(*eid, reply.into()),
+                }, SendMode::Safe)?;
+            }
+            ServerMessage::ModReply(eid, reply) => {
+                if let Some(h) = self.handles.get_mut(eid) {
+                    if let Some(pending) = h.messenger.pending_requests.remove(&packet.conv_id) {
+                        let reply = match reply {
+                            NetResult::Ok(data) => Ok(data.clone()),
+                            NetResult::Err(e) => Err(e.clone())
+                        };
+                        if let Some(mut renderer) = renderer_of(context, &*h.handle) {
+                            (pending.callback)(&mut *h.handle, &mut h.messenger, Value(&mut *renderer), store, reply, false)
+                        } else {
+                            (pending.callback)(&mut *h.handle, &mut h.messenger, Null, store, reply, false)
+                        }
                     }
                 }
             }
+            ServerMessage::ReloadMod(info) => {
+                self.pending_reloads.push(info.clone())
+            }
             _ => ()
         }
         Ok(())
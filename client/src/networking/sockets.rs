@@ -0,0 +1,96 @@
+use std::cell::RefCell;
+use std::io::{Read, Write};
+use std::net::{TcpStream, UdpSocket};
+use std::sync::{Arc, Mutex};
+use aeonetica_engine::error::{Error, Fatality, ErrorResult};
+use aeonetica_engine::error::builtin::NetworkError;
+use aeonetica_engine::log;
+use aeonetica_engine::networking::{MAX_PACKET_SIZE, SendMode};
+use aeonetica_engine::networking::transport::{ClientTransport, DetachedSender};
+
+/// Quick packets are sent over udp, safe packets over tcp.
+pub struct SocketTransport {
+    udp: UdpSocket,
+    tcp: RefCell<TcpStream>,
+    received: Arc<Mutex<Vec<Vec<u8>>>>
+}
+
+impl SocketTransport {
+    /// Binds the udp socket to `addr` and connects both sockets to `server`.
+    pub fn connect(addr: &str, server: &str) -> ErrorResult<Self> {
+        let tcp = TcpStream::connect(server)?;
+        tcp.set_nonblocking(false).unwrap();
+        let udp = UdpSocket::bind(addr)?;
+        udp.connect(server)?;
+        let udp_sock = udp.try_clone()?;
+        let mut tcp_sock = tcp.try_clone()?;
+        let received = Arc::new(Mutex::new(vec![]));
+        let recv_udp = received.clone();
+        let recv_tcp = received.clone();
+        std::thread::spawn(move || {
+            let mut buf = [0u8; MAX_PACKET_SIZE];
+            loop {
+                match udp_sock.recv_from(&mut buf) {
+                    Ok((len, _src)) => recv_udp.lock().unwrap().push(buf[..len].to_vec()),
+                    Err(e) => {
+                        log!(ERROR, "couldn't recieve a datagram: {}", e);
+                    }
+                }
+            }
+        });
+        std::thread::spawn(move || {
+            loop {
+                let mut size = [0u8;4];
+                tcp_sock.read_exact(&mut size).unwrap();
+                let size = u32::from_le_bytes(size);
+                let mut buffer: Vec<u8> = vec![0;size as usize];
+                tcp_sock.read_exact(&mut buffer[..]).unwrap();
+                recv_tcp.lock().unwrap().push(buffer);
+            }
+        });
+        Ok(Self {
+            udp,
+            tcp: RefCell::new(tcp),
+            received
+        })
+    }
+}
+
+impl ClientTransport for SocketTransport {
+    fn receive(&mut self) -> Vec<Vec<u8>> {
+        std::mem::take(&mut self.received.lock().unwrap() as &mut Vec<Vec<u8>>)
+    }
+
+    fn send(&self, data: Vec<u8>, mode: SendMode) -> ErrorResult<()> {
+        match mode {
+            SendMode::Quick => {
+                let sock = self.udp.try_clone()?;
+                std::thread::spawn(move || sock.send(&data[..]).map_err(|e| {
+                    let e: Box<Error> = e.into();
+                    e.log();
+                }));
+            }
+            SendMode::Safe => {
+                let mut tcp = self.tcp.borrow_mut();
+                let _ = tcp.write_all(&(data.len() as u32).to_le_bytes()).map_err(|e| {
+                    let e: Box<Error> = e.into();
+                    e.log();
+                });
+                let _ = tcp.write_all(&data[..]).map_err(|e| {
+                    let e: Box<Error> = e.into();
+                    e.log();
+                });
+            }
+        }
+        Ok(())
+    }
+
+    fn detached_sender(&self) -> ErrorResult<DetachedSender> {
+        let socket = self.udp.try_clone()?;
+        Ok(Box::new(move |data| {
+            socket.send(&data[..])
+                .map(|_| ())
+                .map_err(|e| Error::new(NetworkError(format!("could not reach the server: {e}")), Fatality::FATAL, false))
+        }))
+    }
+}
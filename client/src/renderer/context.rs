@@ -92,6 +92,19 @@ impl LayerStack {
         self.layer_map.insert(type_to_id::<L>(), l.clone());
         self.layer_stack.insert(self.insert_index, (l, type_to_id::<L>()));
     }
+
+    fn remove(&mut self, id: &TypeId, store: &mut DataStore) {
+        if let Some(layer) = self.layer_map.remove(id) {
+            layer.borrow_mut().quit(store);
+            if let Some(index) = self.layer_stack.iter().position(|(_, l)| l == id) {
+                self.layer_stack.remove(index);
+                // overlays are placed at or after the insert index
+                if index < self.insert_index {
+                    self.insert_index -= 1;
+                }
+            }
+        }
+    }
 }
 
 pub struct RenderContext {
@@ -121,6 +134,10 @@ impl RenderContext {
         Ok(())
     }
 
+    pub(crate) fn remove_layer(&mut self, id: &TypeId, store: &mut DataStore) {
+        self.layer_stack.remove(id, store)
+    }
+
     pub(crate) fn on_event(&mut self, client: &mut ClientRuntime, event: Event, store: &mut DataStore) {
         for (layer_box, id) in self.layer_stack.layer_stack.iter()
             .filter(|(layer_box, _)| layer_box.borrow().layer.active()).rev() {
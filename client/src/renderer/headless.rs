@@ -0,0 +1,30 @@
+//! Stands in for the renderer of clients built without the `window` feature.
+//!
+//! The types only exist so mods and client handles keep their signatures, none of them
+//! can be created. Handles of such clients are always called without a renderer.
+
+use std::convert::Infallible;
+
+pub enum Renderer {}
+
+pub mod context {
+    pub enum RenderContext {}
+}
+
+pub mod window {
+    use std::marker::PhantomData;
+    use super::Infallible;
+    use super::context::RenderContext;
+
+    pub struct OpenGlRenderContextProvider<'a>(Infallible, PhantomData<&'a mut RenderContext>);
+
+    impl<'a> OpenGlRenderContextProvider<'a> {
+        pub fn make_context(self) -> &'a mut RenderContext {
+            match self.0 {}
+        }
+    }
+
+    pub mod events {
+        pub enum Event {}
+    }
+}
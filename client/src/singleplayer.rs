@@ -0,0 +1,48 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::JoinHandle;
+use aeonetica_engine::log;
+use aeonetica_engine::networking::memory::{memory_transport, MemoryClientTransport, MemoryServerTransport};
+use aeonetica_server::ServerModBox;
+
+/// A server running in a background thread of the client, for playing alone without sockets.
+/// It loads the mods from `mods/mods.ron` of the working directory and stops when dropped.
+pub struct EmbeddedServer {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>
+}
+
+impl EmbeddedServer {
+    /// Returns the server and the transport to pass to [`crate::client_runtime::ClientRuntime::create_singleplayer`].
+    pub fn start() -> (Self, MemoryClientTransport) {
+        Self::spawn(aeonetica_server::server::run_local)
+    }
+
+    /// Like [`EmbeddedServer::start`], but with the mods `mods` creates on the server thread
+    /// instead of the ones in `mods/mods.ron`.
+    pub fn start_linked(mods: impl FnOnce() -> Vec<ServerModBox> + Send + 'static) -> (Self, MemoryClientTransport) {
+        Self::spawn(move |transport, stop| aeonetica_server::server::run_linked(mods(), transport, stop))
+    }
+
+    fn spawn(run: impl FnOnce(MemoryServerTransport, &AtomicBool) + Send + 'static) -> (Self, MemoryClientTransport) {
+        let (server_transport, connector) = memory_transport();
+        let client_transport = connector.connect();
+        let stop = Arc::new(AtomicBool::new(false));
+        let server_stop = stop.clone();
+        let thread = std::thread::Builder::new()
+            .name("embedded server".to_string())
+            .spawn(move || run(server_transport, &server_stop))
+            .expect("could not start the embedded server");
+        (Self { stop, thread: Some(thread) }, client_transport)
+    }
+}
+
+impl Drop for EmbeddedServer {
+    fn drop(&mut self) {
+        log!("stopping embedded server");
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() && thread.join().is_err() {
+            log!(ERROR, "embedded server panicked");
+        }
+    }
+}
@@ -0,0 +1,278 @@
+use std::cell::{Cell, RefCell};
+use std::collections::{HashSet, VecDeque};
+use std::io::{Cursor, Write};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+use aeonetica_engine::Id;
+use aeonetica_engine::ed25519_dalek::SigningKey;
+use aeonetica_engine::networking::MOD_DOWNLOAD_CHUNK_SIZE;
+use aeonetica_engine::networking::rpc::RequestError;
+use aeonetica_engine::networking::server_packets::ModInfo;
+use aeonetica_engine::sha2::{Digest, Sha256};
+use aeonetica_engine::signing::ModSignature;
+use crate::client_runtime::{cached_mod, ClientOptions, ClientRuntime, partial_download, partial_download_meta};
+use crate::data_store::DataStore;
+use crate::downloads::{MAX_CHUNK_RETRIES, PartialDownload, retry_chunk};
+use crate::mod_cache::{ModCache, normalize_hash};
+use crate::singleplayer::EmbeddedServer;
+use crate::trust::{TrustAnswer, TrustDecision, TrustPolicy, TrustStore};
+use zip::{CompressionMethod, ZipWriter};
+use zip::write::FileOptions;
+
+const CHUNK: usize = MOD_DOWNLOAD_CHUNK_SIZE;
+
+/// A zip of two and a half chunks, different per `seed` so tests do not share files.
+fn zip(seed: u8) -> (Vec<u8>, String) {
+    let data: Vec<u8> = (0..CHUNK * 5 / 2).map(|i| (i as u8).wrapping_mul(31) ^ seed).collect();
+    let hash = format!("{:x}", Sha256::digest(&data));
+    (data, hash)
+}
+
+fn chunk_at(data: &[u8], offset: u64) -> &[u8] {
+    let start = offset as usize;
+    &data[start..(start + CHUNK).min(data.len())]
+}
+
+fn write_meta(hash: &str, lines: &[String]) {
+    std::fs::create_dir_all(std::path::Path::new(&partial_download_meta(hash)).parent().unwrap()).unwrap();
+    let mut meta = std::fs::File::create(partial_download_meta(hash)).unwrap();
+    for line in lines {
+        writeln!(meta, "{line}").unwrap();
+    }
+}
+
+#[test]
+fn downloads_resume_where_they_stopped() {
+    let (data, hash) = zip(1);
+    let size = data.len() as u64;
+    let mut download = PartialDownload::open("test:resume", &hash, size).unwrap();
+    assert_eq!(download.missing_chunks().collect::<Vec<_>>(), vec![0, CHUNK as u64, 2 * CHUNK as u64]);
+    assert!(download.write_chunk(CHUNK as u64, chunk_at(&data, CHUNK as u64)).unwrap());
+    drop(download);
+
+    let mut download = PartialDownload::open("test:resume", &hash, size).unwrap();
+    assert_eq!(download.downloaded(), CHUNK as u64);
+    assert_eq!(download.missing_chunks().collect::<Vec<_>>(), vec![0, 2 * CHUNK as u64]);
+    for offset in download.missing_chunks().collect::<Vec<_>>() {
+        assert!(download.write_chunk(offset, chunk_at(&data, offset)).unwrap());
+    }
+    assert!(download.is_complete());
+    assert_eq!(download.downloaded(), size);
+    assert_eq!(download.verify().unwrap(), data);
+    assert!(!std::path::Path::new(&partial_download(&hash)).exists());
+    assert!(!std::path::Path::new(&partial_download_meta(&hash)).exists());
+}
+
+#[test]
+fn bad_chunks_are_retried_a_limited_number_of_times() {
+    let (data, hash) = zip(2);
+    let mut download = PartialDownload::open("test:retry", &hash, data.len() as u64).unwrap();
+    // too short, past the end and not at a chunk boundary
+    assert!(!download.write_chunk(0, &data[..CHUNK - 1]).unwrap());
+    assert!(!download.write_chunk(data.len() as u64 + 1, &[0]).unwrap());
+    assert!(!download.write_chunk(1, &data[1..CHUNK + 1]).unwrap());
+    assert_eq!(download.downloaded(), 0);
+    assert_eq!(download.missing_chunks().count(), 3);
+    download.discard();
+
+    let mut queue = VecDeque::new();
+    for retries in 0..MAX_CHUNK_RETRIES {
+        assert!(retry_chunk(&mut queue, "test:retry", 0, 0, retries).is_ok());
+    }
+    assert_eq!(queue.back(), Some(&(0, 0, MAX_CHUNK_RETRIES)));
+    assert!(retry_chunk(&mut queue, "test:retry", 0, 0, MAX_CHUNK_RETRIES).is_err());
+    assert_eq!(queue.len(), MAX_CHUNK_RETRIES as usize);
+}
+
+#[test]
+fn bad_metadata_restarts_the_download() {
+    let (data, hash) = zip(3);
+    let size = data.len() as u64;
+    for offsets in [vec![(size + 1).to_string()], vec![(3 * CHUNK).to_string()], vec!["0".to_string(), "12x".to_string()], vec!["7".to_string()]] {
+        write_meta(&hash, &[vec![hash.clone()], offsets].concat());
+        let download = PartialDownload::open("test:meta", &hash, size).unwrap();
+        assert_eq!(download.downloaded(), 0);
+        assert_eq!(download.missing_chunks().count(), 3);
+    }
+
+    // metadata of another version of the mod
+    let (_, other) = zip(4);
+    write_meta(&hash, &[other, "0".to_string()]);
+    let mut download = PartialDownload::open("test:meta", &hash, size).unwrap();
+    assert_eq!(download.downloaded(), 0);
+    assert!(download.write_chunk(0, chunk_at(&data, 0)).unwrap());
+    drop(download);
+    assert_eq!(PartialDownload::open("test:meta", &hash, size).unwrap().downloaded(), CHUNK as u64);
+    PartialDownload::open("test:meta", &hash, size).unwrap().discard();
+}
+
+/// A zip with a single stored file of `size` bytes.
+fn mod_zip(seed: u8, size: usize) -> (Vec<u8>, String) {
+    let mut zip = ZipWriter::new(Cursor::new(vec![]));
+    zip.start_file("client.so", FileOptions::default().compression_method(CompressionMethod::Stored)).unwrap();
+    zip.write_all(&vec![seed; size]).unwrap();
+    let data = zip.finish().unwrap().into_inner();
+    let hash = format!("{:x}", Sha256::digest(&data));
+    (data, hash)
+}
+
+#[test]
+fn mod_hashes_are_sha256_hex() {
+    let (_, hash) = zip(5);
+    assert_eq!(normalize_hash(&hash.to_ascii_uppercase()).unwrap(), hash);
+    let invalid = [String::new(), "../../../etc/passwd".to_string(), hash[1..].to_string(), format!("{hash}0"), format!("../{}", &hash[3..]), format!("{}g", &hash[1..])];
+    for hash in invalid {
+        assert!(normalize_hash(&hash).is_err(), "{hash}");
+    }
+}
+
+#[test]
+fn the_least_recently_used_mods_are_evicted() {
+    let none = HashSet::new();
+    let mut cache = ModCache::open(u64::MAX);
+    cache.set_cap(0, &none).unwrap();
+    cache.set_cap(u64::MAX, &none).unwrap();
+
+    let mods = (0..3u8).map(|i| mod_zip(i, 1000)).collect::<Vec<_>>();
+    for (data, hash) in &mods {
+        cache.insert(hash, data).unwrap();
+        std::thread::sleep(Duration::from_millis(2));
+    }
+    assert_eq!(cache.size(), 3000);
+    let [first, second, third] = [&mods[0].1, &mods[1].1, &mods[2].1];
+    cache.touch(first).unwrap();
+
+    // the second mod was used the longest time ago
+    cache.set_cap(2000, &none).unwrap();
+    assert!(cache.contains(first) && !cache.contains(second) && cache.contains(third));
+    assert!(!std::path::Path::new(&cached_mod(second)).exists());
+
+    // the index survives reopening the cache
+    let mut cache = ModCache::open(2000);
+    assert!(cache.contains(first) && cache.contains(third));
+    assert_eq!(cache.size(), 2000);
+
+    // mods in use are kept above the cap
+    cache.set_cap(0, &HashSet::from([third.clone()])).unwrap();
+    assert!(!cache.contains(first) && cache.contains(third));
+    cache.set_cap(0, &none).unwrap();
+    assert_eq!(cache.size(), 0);
+}
+
+/// A new publisher every run, so the trusted publishers saved by earlier runs do not matter.
+fn publisher_key() -> SigningKey {
+    let mut bytes = [0; 32];
+    for chunk in bytes.chunks_mut(8) {
+        chunk.copy_from_slice(&Id::new().into_u64().to_le_bytes());
+    }
+    SigningKey::from_bytes(&bytes)
+}
+
+fn mod_info(name_path: &str, hash: &str, publisher: Option<&SigningKey>) -> ModInfo {
+    ModInfo {
+        name_path: name_path.to_string(),
+        flags: vec![],
+        hash: hash.to_string(),
+        size: 0,
+        signature: publisher.map(|key| ModSignature::sign(hash, key))
+    }
+}
+
+/// A store with the same decision for unsigned mods and unknown publishers,
+/// whose prompt always answers `answer` and counts how often it was asked.
+fn trust_store(decision: TrustDecision, answer: TrustAnswer) -> (TrustStore, Rc<Cell<u32>>) {
+    let mut store = TrustStore::open(TrustPolicy { unsigned: decision, unknown_publisher: decision });
+    let asked = Rc::new(Cell::new(0));
+    let counter = asked.clone();
+    store.prompt = Box::new(move |_| {
+        counter.set(counter.get() + 1);
+        answer
+    });
+    (store, asked)
+}
+
+#[test]
+fn trust_store_applies_signatures_and_policies() {
+    let (_, hash) = zip(6);
+    let publisher = publisher_key();
+    let unsigned = mod_info("test:unsigned", &hash, None);
+    let signed = mod_info("test:signed", &hash, Some(&publisher));
+    let mut forged = signed.clone();
+    forged.hash = zip(7).1;
+
+    let (mut store, asked) = trust_store(TrustDecision::Allow, TrustAnswer::Yes);
+    assert!(store.check(&unsigned).is_ok());
+    assert!(store.check(&signed).is_ok());
+    // invalid signatures are refused whatever the policy says
+    assert!(store.check(&forged).is_err());
+    assert_eq!(asked.get(), 0);
+
+    let (mut store, asked) = trust_store(TrustDecision::Refuse, TrustAnswer::Yes);
+    assert!(store.check(&unsigned).is_err());
+    assert!(store.check(&signed).is_err());
+    assert_eq!(asked.get(), 0);
+
+    let (mut store, asked) = trust_store(TrustDecision::Ask, TrustAnswer::No);
+    assert!(store.check(&unsigned).is_err());
+    assert!(store.check(&signed).is_err());
+    assert_eq!(asked.get(), 2);
+
+    let (mut store, asked) = trust_store(TrustDecision::Ask, TrustAnswer::Yes);
+    assert!(store.check(&unsigned).is_ok());
+    assert!(store.check(&signed).is_ok());
+    assert_eq!(asked.get(), 2);
+
+    // unsigned mods have no publisher to trust
+    let (mut store, asked) = trust_store(TrustDecision::Ask, TrustAnswer::Always);
+    assert!(store.check(&unsigned).is_err());
+    assert!(store.check(&signed).is_ok());
+    assert!(store.is_trusted(&signed.signature.as_ref().unwrap().publisher()));
+    assert!(store.check(&signed).is_ok());
+    assert_eq!(asked.get(), 2);
+
+    // trusted publishers are saved and not asked about again
+    let (mut store, asked) = trust_store(TrustDecision::Refuse, TrustAnswer::No);
+    assert!(store.check(&signed).is_ok());
+    assert!(store.check(&forged).is_err());
+    assert_eq!(asked.get(), 0);
+}
+
+#[test]
+fn singleplayer_clients_talk_to_the_embedded_server() {
+    let (server, transport) = EmbeddedServer::start_linked(Vec::new);
+    let mut store = DataStore::new();
+    let mut client = ClientRuntime::create_singleplayer(Id::new(), transport, &mut store, ClientOptions::default()).unwrap();
+
+    let rtt = Rc::new(Cell::new(None));
+    let pong = rtt.clone();
+    client.ping(Duration::from_secs(5), move |rtt| pong.set(rtt.ok())).unwrap();
+    let started = Instant::now();
+    while rtt.get().is_none() && started.elapsed() < Duration::from_secs(5) {
+        client.update_headless(&mut store).unwrap();
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    assert!(rtt.get().is_some(), "the embedded server did not answer the ping");
+    drop(server);
+}
+
+#[test]
+fn unanswered_pings_time_out() {
+    let (server, transport) = EmbeddedServer::start_linked(Vec::new);
+    let mut store = DataStore::new();
+    let mut client = ClientRuntime::create_singleplayer(Id::new(), transport, &mut store, ClientOptions::default()).unwrap();
+
+    let pongs = Rc::new(RefCell::new(Vec::new()));
+    let on_pong = pongs.clone();
+    client.ping(Duration::ZERO, move |pong| on_pong.borrow_mut().push(pong)).unwrap();
+    // times out before the pong is handled
+    client.timeout_requests(&mut store, None);
+    assert_eq!(*pongs.borrow(), vec![Err(RequestError::Timeout)]);
+    assert!(client.awaiting_replies.is_empty());
+    assert!(client.ping_timeouts.is_empty());
+
+    // the late pong is ignored
+    std::thread::sleep(Duration::from_millis(100));
+    client.update_headless(&mut store).unwrap();
+    assert_eq!(pongs.borrow().len(), 1);
+    drop(server);
+}
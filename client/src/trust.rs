@@ -0,0 +1,150 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use aeonetica_engine::error::{Error, ErrorResult, Fatality};
+use aeonetica_engine::error::builtin::ModError;
+use aeonetica_engine::log;
+use aeonetica_engine::networking::server_packets::ModInfo;
+
+pub const TRUST_STORE: &str = "runtime/trusted_publishers";
+
+/// What happens with a mod that is not signed by a trusted publisher.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrustDecision {
+    /// the client aborts instead of loading the mod
+    Refuse,
+    /// the user is asked through the [`TrustPrompt`] of the store
+    Ask,
+    /// the mod is loaded with a warning
+    Allow
+}
+
+/// The answer of the user to a [`TrustQuestion`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrustAnswer {
+    Yes,
+    No,
+    /// loads the mod and trusts its publisher from now on, like `No` for unsigned mods
+    Always
+}
+
+/// A mod the user has to decide about, see [`TrustDecision::Ask`].
+pub struct TrustQuestion<'a> {
+    pub name_path: &'a str,
+    /// the hex public key of the unknown publisher, `None` if the mod is not signed
+    pub publisher: Option<&'a str>
+}
+
+/// Asks the user whether to load a mod.
+///
+/// It runs while the client connects, before any mod is loaded.
+/// The default, [`console_prompt`], blocks until the user answers on stdin,
+/// so clients without a console should set their own.
+pub type TrustPrompt = Box<dyn FnMut(&TrustQuestion) -> TrustAnswer>;
+
+/// The default prompt, asks on the console.
+pub fn console_prompt() -> TrustPrompt {
+    Box::new(|question| {
+        let options = if question.publisher.is_some() { "[y]es / [n]o / [a]lways trust this publisher" } else { "[y]es / [n]o" };
+        log!("load it anyway? {options}");
+        let mut answer = String::new();
+        if std::io::stdin().read_line(&mut answer).is_err() {
+            return TrustAnswer::No
+        }
+        match answer.trim().to_ascii_lowercase().as_str() {
+            "y" | "yes" => TrustAnswer::Yes,
+            "a" | "always" => TrustAnswer::Always,
+            _ => TrustAnswer::No
+        }
+    })
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct TrustPolicy {
+    pub unsigned: TrustDecision,
+    pub unknown_publisher: TrustDecision
+}
+
+impl Default for TrustPolicy {
+    fn default() -> Self {
+        Self {
+            unsigned: TrustDecision::Ask,
+            unknown_publisher: TrustDecision::Ask
+        }
+    }
+}
+
+/// Public keys of publishers whose mods are loaded without asking,
+/// stored in `runtime/trusted_publishers` as `<hex key> <label>` per line.
+///
+/// Mods with a signature that does not match their hash are always refused.
+pub struct TrustStore {
+    pub policy: TrustPolicy,
+    pub prompt: TrustPrompt,
+    publishers: HashMap<String, String>
+}
+
+impl TrustStore {
+    pub fn open(policy: TrustPolicy) -> Self {
+        let publishers = File::open(TRUST_STORE).ok()
+            .map(|f| BufReader::new(f).lines().map_while(Result::ok).collect::<Vec<_>>())
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|line| {
+                let (key, label) = line.trim().split_once(' ').unwrap_or((line.trim(), ""));
+                (!key.is_empty()).then(|| (key.to_ascii_uppercase(), label.trim().to_string()))
+            })
+            .collect();
+        Self {
+            policy,
+            prompt: console_prompt(),
+            publishers
+        }
+    }
+
+    pub fn is_trusted(&self, publisher: &str) -> bool {
+        self.publishers.contains_key(&publisher.to_ascii_uppercase())
+    }
+
+    /// Adds the hex public key of a publisher and saves the store.
+    pub fn trust(&mut self, publisher: &str, label: &str) -> ErrorResult<()> {
+        self.publishers.insert(publisher.to_ascii_uppercase(), label.to_string());
+        std::fs::create_dir_all("runtime")?;
+        let mut f = File::create(TRUST_STORE)?;
+        for (key, label) in &self.publishers {
+            writeln!(f, "{key} {label}")?;
+        }
+        Ok(())
+    }
+
+    /// Decides whether the mod may be downloaded and loaded.
+    pub(crate) fn check(&mut self, info: &ModInfo) -> ErrorResult<()> {
+        let refuse = |reason: String| Err(Error::new(ModError(format!("refusing to load mod {}: {reason}", info.name_path)), Fatality::FATAL, false));
+        let (decision, publisher) = match &info.signature {
+            Some(signature) if !signature.verify(&info.hash) => return refuse("its signature is invalid".to_string()),
+            Some(signature) if self.is_trusted(&signature.publisher()) => return Ok(()),
+            Some(signature) => (self.policy.unknown_publisher, Some(signature.publisher())),
+            None => (self.policy.unsigned, None)
+        };
+        let what = match &publisher {
+            Some(publisher) => format!("signed by the unknown publisher {publisher}"),
+            None => "not signed".to_string()
+        };
+        match decision {
+            TrustDecision::Refuse => refuse(format!("it is {what}")),
+            TrustDecision::Allow => {
+                log!(WARN, "loading mod {} which is {what}", info.name_path);
+                Ok(())
+            }
+            TrustDecision::Ask => {
+                log!(WARN, "the server wants to load mod {} which is {what}. mods run native code on your machine.", info.name_path);
+                let answer = (self.prompt)(&TrustQuestion { name_path: &info.name_path, publisher: publisher.as_deref() });
+                match (answer, &publisher) {
+                    (TrustAnswer::Yes, _) => Ok(()),
+                    (TrustAnswer::Always, Some(publisher)) => self.trust(publisher, &info.name_path),
+                    _ => refuse("not accepted by the user".to_string())
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,148 @@
+use std::cell::RefCell;
+use std::rc::{Rc, Weak};
+use aeonetica_engine::{EntityId, Id, TypeId};
+use aeonetica_engine::error::ErrorResult;
+use aeonetica_engine::networking::messaging::ClientEntity;
+use aeonetica_engine::util::id_map::IdMap;
+use aeonetica_engine::util::nullable::Nullable;
+use aeonetica_engine::wasm_abi::{IMPORT_MODULE, send_mode};
+use aeonetica_server::wasm::{read_guest, WasmInstance};
+use aeonetica_server::wasm::wasmi::{Caller, Linker};
+use aeonetica_server::wasm::wasmi::core::Trap;
+use crate::ClientMod;
+use crate::client_runtime::HandleCreator;
+use crate::data_store::DataStore;
+use crate::networking::messaging::{ClientHandle, ClientMessenger};
+use crate::renderer::Renderer;
+
+/// No layer has this id, wasm handles do not render.
+const NO_LAYER: TypeId = Id::from_u64(0);
+
+pub(crate) struct ClientHost {
+    /// only set while the guest is called by one of its handles
+    messenger: *mut ClientMessenger,
+    instance: Weak<RefCell<WasmInstance<ClientHost>>>,
+    /// registered by the guest in `aeonetica_init`
+    handle_types: Vec<TypeId>
+}
+
+fn id(id: i64) -> Id {
+    Id::from_u64(id as u64)
+}
+
+/// The messenger of the calling handle, if it belongs to `entity`.
+fn messenger<'a>(caller: &Caller<ClientHost>, entity: i64) -> Result<Option<&'a mut ClientMessenger>, Trap> {
+    let messenger = unsafe { caller.data().messenger.as_mut() }
+        .ok_or_else(|| Trap::new("messengers are only available while a handle calls the mod"))?;
+    Ok((messenger.entity_id() == id(entity)).then_some(messenger))
+}
+
+/// Calls the guest on behalf of a handle with its messenger.
+fn call_with_messenger<P: aeonetica_server::wasm::wasmi::WasmParams>(instance: &Rc<RefCell<WasmInstance<ClientHost>>>, messenger: &mut ClientMessenger, name: &str, params: P) {
+    let mut instance = instance.borrow_mut();
+    instance.store.data_mut().messenger = messenger;
+    if let Err(e) = instance.call::<P, ()>(name, params) {
+        e.log()
+    }
+    instance.store.data_mut().messenger = std::ptr::null_mut();
+}
+
+fn receive(instance: &Rc<RefCell<WasmInstance<ClientHost>>>, messenger: &mut ClientMessenger, rid: Id, data: &[u8]) {
+    let (ptr, len) = match instance.borrow_mut().write(data) {
+        Ok(written) => written,
+        Err(e) => return e.log()
+    };
+    let params = (messenger.entity_id().into_u64() as i64, rid.into_u64() as i64, ptr, len);
+    call_with_messenger(instance, messenger, "aeonetica_handle_receive", params)
+}
+
+fn link_client(linker: &mut Linker<ClientHost>) -> Result<(), aeonetica_server::wasm::wasmi::errors::LinkerError> {
+    linker.func_wrap(IMPORT_MODULE, "register_handle", |mut caller: Caller<ClientHost>, handle_type: i64| {
+        caller.data_mut().handle_types.push(id(handle_type));
+    })?;
+    linker.func_wrap(IMPORT_MODULE, "on", |caller: Caller<ClientHost>, entity: i64, rpc: i64| -> Result<i32, Trap> {
+        let instance = caller.data().instance.clone();
+        let Some(messenger) = messenger(&caller, entity)? else { return Ok(0) };
+        let rid = id(rpc);
+        messenger.client_receivers.insert(rid, Box::new(move |_: &mut dyn ClientHandle, messenger: &mut ClientMessenger, _: Nullable<&mut Renderer>, _: &mut DataStore, data: &Vec<u8>| {
+            if let Some(instance) = instance.upgrade() {
+                receive(&instance, messenger, rid, data)
+            }
+        }));
+        Ok(1)
+    })?;
+    linker.func_wrap(IMPORT_MODULE, "send", |caller: Caller<ClientHost>, entity: i64, rpc: i64, ptr: i32, len: i32, mode: i32| -> Result<(), Trap> {
+        let data = read_guest(&caller, ptr, len)?;
+        if let Some(messenger) = messenger(&caller, entity)? {
+            messenger.send_raw(id(rpc), data, send_mode(mode));
+        }
+        Ok(())
+    })?;
+    Ok(())
+}
+
+struct WasmHandle {
+    instance: Weak<RefCell<WasmInstance<ClientHost>>>,
+    handle_type: TypeId
+}
+
+impl ClientEntity for WasmHandle {}
+
+impl ClientHandle for WasmHandle {
+    fn owning_layer(&self) -> TypeId {
+        NO_LAYER
+    }
+
+    fn start(&mut self, messenger: &mut ClientMessenger, _renderer: Nullable<&mut Renderer>, _store: &mut DataStore) {
+        if let Some(instance) = self.instance.upgrade() {
+            let params = (messenger.entity_id().into_u64() as i64, self.handle_type.into_u64() as i64);
+            call_with_messenger(&instance, messenger, "aeonetica_handle_start", params)
+        }
+    }
+
+    fn remove(&mut self, messenger: &mut ClientMessenger, _renderer: Nullable<&mut Renderer>, _store: &mut DataStore) {
+        if let Some(instance) = self.instance.upgrade() {
+            let entity: EntityId = messenger.entity_id();
+            call_with_messenger(&instance, messenger, "aeonetica_handle_remove", entity.into_u64() as i64)
+        }
+    }
+}
+
+/// The client side of a wasm mod.
+#[derive(Clone)]
+pub(crate) struct WasmClientMod {
+    instance: Rc<RefCell<WasmInstance<ClientHost>>>
+}
+
+impl WasmClientMod {
+    pub(crate) fn load(name_path: &str, code: &[u8]) -> ErrorResult<Self> {
+        let host = ClientHost {
+            messenger: std::ptr::null_mut(),
+            instance: Weak::new(),
+            handle_types: vec![]
+        };
+        let instance = Rc::new(RefCell::new(WasmInstance::new(name_path, code, host, link_client)?));
+        instance.borrow_mut().store.data_mut().instance = Rc::downgrade(&instance);
+        Ok(Self { instance })
+    }
+
+    /// Creators of the handle types the guest registered in `aeonetica_init`.
+    pub(crate) fn handle_creators(&self) -> IdMap<HandleCreator> {
+        let handle_types = self.instance.borrow().store.data().handle_types.clone();
+        handle_types.into_iter()
+            .map(|handle_type| {
+                let instance = Rc::downgrade(&self.instance);
+                let creator: HandleCreator = Box::new(move || Box::new(WasmHandle { instance: instance.clone(), handle_type }) as Box<dyn ClientHandle>);
+                (handle_type, creator)
+            })
+            .collect()
+    }
+}
+
+impl ClientMod for WasmClientMod {
+    fn init(&mut self, flags: &Vec<String>) {
+        if let Err(e) = self.instance.borrow_mut().init(flags) {
+            e.log()
+        }
+    }
+}
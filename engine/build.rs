@@ -0,0 +1,12 @@
+use std::process::Command;
+
+fn main() {
+    // part of the abi fingerprint, see src/abi.rs
+    let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    let version = Command::new(rustc).arg("-vV").output()
+        .map(|o| String::from_utf8_lossy(&o.stdout).lines().take(3).collect::<Vec<_>>().join(" "))
+        .unwrap_or_default();
+    println!("cargo:rustc-env=AEONETICA_RUSTC_VERSION={version}");
+    println!("cargo:rerun-if-env-changed=RUSTC");
+    println!("cargo:rerun-if-changed=build.rs");
+}
@@ -0,0 +1,100 @@
+use std::any::type_name;
+use std::ffi::c_void;
+#[allow(deprecated)]
+use std::hash::{Hasher, SipHasher};
+use libloading::{Library, Symbol};
+use crate::{ENGINE_VERSION, MOD_TARGET};
+use crate::error::{Error, ErrorResult, Fatality};
+use crate::error::builtin::ModError;
+use crate::networking::rpc::rpc_registry;
+
+/// `rustc -vV` of the compiler that built the engine, set by the build script.
+pub const RUSTC_VERSION: &str = env!("AEONETICA_RUSTC_VERSION");
+
+/// Size and alignment of a type that is passed between the host and a mod.
+#[derive(Debug, Clone, Copy)]
+pub struct TypeLayout {
+    pub name: &'static str,
+    pub size: usize,
+    pub align: usize
+}
+
+pub fn layout_of<T>() -> TypeLayout {
+    TypeLayout {
+        name: type_name::<T>(),
+        size: std::mem::size_of::<T>(),
+        align: std::mem::align_of::<T>()
+    }
+}
+
+/// Hash of the compiler, the engine version, the mod target and the layouts of `types`.
+///
+/// The host and every mod compute this with their own copy of the engine,
+/// so a mod built by a different toolchain or against a different engine gets a different fingerprint.
+pub fn fingerprint(types: &[TypeLayout]) -> u64 {
+    #[allow(deprecated)]
+    let mut s = SipHasher::new();
+    s.write(RUSTC_VERSION.as_bytes());
+    s.write(ENGINE_VERSION.as_bytes());
+    s.write(MOD_TARGET.as_bytes());
+    for t in types {
+        s.write(t.name.as_bytes());
+        s.write_usize(t.size);
+        s.write_usize(t.align);
+    }
+    s.finish()
+}
+
+/// Version of [`ModEntry`], raised whenever its layout or meaning changes.
+pub const MOD_ABI_VERSION: u32 = 2;
+pub const MOD_ENTRY_SYMBOL: &str = "_aeonetica_mod_entry";
+
+/// The entry point every mod exports through [`crate::register`] as
+/// `extern "C" fn _aeonetica_mod_entry() -> *const ModEntry`.
+///
+/// Only `abi_version` may be read before it was checked. The create functions return
+/// a `Box<Box<dyn ClientMod>>`/`Box<Box<dyn ServerMod>>` as a raw pointer, which is only
+/// sound once the fingerprint of the side matches the one of the host.
+/// `use_rpc_registry` hands the host's [`crate::networking::rpc::RpcRegistry`] to the mod's copy of the engine.
+#[repr(C)]
+pub struct ModEntry {
+    pub abi_version: u32,
+    pub client_fingerprint: Option<extern "C" fn() -> u64>,
+    pub create_client: Option<extern "C" fn() -> *mut c_void>,
+    pub server_fingerprint: Option<extern "C" fn() -> u64>,
+    pub create_server: Option<extern "C" fn() -> *mut c_void>,
+    pub use_rpc_registry: extern "C" fn(*const c_void)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Client,
+    Server
+}
+
+/// Reads the entry of a mod library and checks its version and the fingerprint of `side`
+/// against `fingerprint` of the host, before anything else of the library is used.
+/// Returns the create function of the side, after the mod got the host's rpc registry.
+pub fn mod_entry(library: &Library, side: Side, fingerprint: u64, name_path: &str) -> ErrorResult<extern "C" fn() -> *mut c_void> {
+    let error = |msg: String| Error::new(ModError(format!("could not load mod {name_path}: {msg}")), Fatality::FATAL, false);
+    let entry: Symbol<extern "C" fn() -> *const ModEntry> = unsafe { library.get(MOD_ENTRY_SYMBOL.as_bytes()) }
+        .map_err(|_| error(format!("it has no {MOD_ENTRY_SYMBOL}, rebuild it with engine {ENGINE_VERSION}")))?;
+    let entry = unsafe { entry().as_ref() }
+        .ok_or_else(|| error("its entry is null".to_string()))?;
+    if entry.abi_version != MOD_ABI_VERSION {
+        return Err(error(format!("it has mod abi version {}, but the engine expects {MOD_ABI_VERSION}", entry.abi_version)))
+    }
+    let (found, create) = match side {
+        Side::Client => (entry.client_fingerprint, entry.create_client),
+        Side::Server => (entry.server_fingerprint, entry.create_server)
+    };
+    let (Some(found), Some(create)) = (found, create) else {
+        return Err(error(format!("it was built without the {side:?} side")))
+    };
+    let found = found();
+    if found != fingerprint {
+        return Err(error(format!("it was built with a different compiler or engine version (abi fingerprint {found:016x}, expected {fingerprint:016x}), rebuild it with {RUSTC_VERSION} and engine {ENGINE_VERSION}")))
+    }
+    (entry.use_rpc_registry)(rpc_registry());
+    Ok(create)
+}
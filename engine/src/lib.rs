@@ -27,6 +27,7 @@ pub use libloading;
 pub use chrono;
 use nanoserde::{DeBin, DeRon, SerBin, SerRon};
 pub use sha2;
+pub use ed25519_dalek;
 use uuid::Uuid;
 pub extern crate colored;
 use lazy_static::lazy_static;
@@ -37,6 +38,11 @@ pub mod util;
 pub mod collections;
 pub mod math;
 pub mod time;
+pub mod manifest;
+pub mod signing;
+pub mod mod_layout;
+pub mod abi;
+pub mod wasm_abi;
 
 pub use enable_ansi_support;
 
@@ -178,17 +184,54 @@ macro_rules! log {
 #[macro_export]
 macro_rules! register {
     ($client_mod:expr, $server_mod:expr) => {
+        #[allow(unused_imports)]
         use aeonetica_client as _a_c;
+        #[allow(unused_imports)]
         use aeonetica_server as _a_s;
-        #[no_mangle]
         #[cfg(feature = "client")]
-        pub fn _create_mod_client() -> Box<dyn _a_c::ClientMod> {
-            Box::new($client_mod)
+        extern "C" fn _client_abi_fingerprint() -> u64 {
+            _a_c::abi_fingerprint()
+        }
+        #[cfg(feature = "client")]
+        extern "C" fn _create_mod_client() -> *mut std::ffi::c_void {
+            let client_mod: Box<dyn _a_c::ClientMod> = Box::new($client_mod);
+            Box::into_raw(Box::new(client_mod)) as *mut std::ffi::c_void
         }
-        #[no_mangle]
         #[cfg(feature = "server")]
-        pub fn _create_mod_server() -> Box<dyn _a_s::ServerMod> {
-            Box::new($server_mod)
+        extern "C" fn _server_abi_fingerprint() -> u64 {
+            _a_s::abi_fingerprint()
+        }
+        #[cfg(feature = "server")]
+        extern "C" fn _create_mod_server() -> *mut std::ffi::c_void {
+            let server_mod: Box<dyn _a_s::ServerMod> = Box::new($server_mod);
+            Box::into_raw(Box::new(server_mod)) as *mut std::ffi::c_void
+        }
+        // only the library built for one side exports an entry, mods linking
+        // this one as a dependency would otherwise get a duplicate symbol
+        #[cfg(any(feature = "client", feature = "server"))]
+        #[no_mangle]
+        pub extern "C" fn _aeonetica_mod_entry() -> *const $crate::abi::ModEntry {
+            static ENTRY: $crate::abi::ModEntry = $crate::abi::ModEntry {
+                abi_version: $crate::abi::MOD_ABI_VERSION,
+                #[cfg(feature = "client")]
+                client_fingerprint: Some(_client_abi_fingerprint),
+                #[cfg(not(feature = "client"))]
+                client_fingerprint: None,
+                #[cfg(feature = "client")]
+                create_client: Some(_create_mod_client),
+                #[cfg(not(feature = "client"))]
+                create_client: None,
+                #[cfg(feature = "server")]
+                server_fingerprint: Some(_server_abi_fingerprint),
+                #[cfg(not(feature = "server"))]
+                server_fingerprint: None,
+                #[cfg(feature = "server")]
+                create_server: Some(_create_mod_server),
+                #[cfg(not(feature = "server"))]
+                create_server: None,
+                use_rpc_registry: $crate::networking::rpc::use_rpc_registry
+            };
+            &ENTRY
         }
     };
 }
\ No newline at end of file
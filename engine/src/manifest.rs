@@ -0,0 +1,127 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use nanoserde::{DeBin, DeRon, SerBin, SerRon};
+use semver::{Version, VersionReq};
+use crate::ENGINE_VERSION;
+use crate::error::{Error, ErrorResult, Fatality};
+use crate::error::builtin::ModError;
+
+pub const MANIFEST_FILE: &str = "manifest.ron";
+
+/// Describes a mod and what it needs to run.
+/// Every mod zip carries one of these as `manifest.ron` at its root.
+///
+/// ```ron
+/// (
+///     name: "worms",
+///     version: "0.1.0",
+///     engine_version: "^0.1.0",
+///     dependencies: {
+///         "player": "^0.1",
+///         "world": "^0.1"
+///     }
+/// )
+/// ```
+#[derive(Debug, Clone, SerRon, DeRon, SerBin, DeBin)]
+pub struct ModManifest {
+    pub name: String,
+    pub version: String,
+    /// semver range of engine versions this mod works with
+    pub engine_version: String,
+    /// key: mod name, value: semver range
+    pub dependencies: HashMap<String, String>
+}
+
+impl ModManifest {
+    pub fn load<P: AsRef<Path>>(path: P) -> ErrorResult<Self> {
+        let mut data = String::new();
+        File::open(path.as_ref())
+            .map_err(|e| Error::new(ModError(format!("could not open manifest {}: {e}", path.as_ref().display())), Fatality::FATAL, false))?
+            .read_to_string(&mut data)?;
+        let manifest: ModManifest = DeRon::deserialize_ron(&data)?;
+        manifest.parsed_version()?;
+        Ok(manifest)
+    }
+
+    pub fn parsed_version(&self) -> ErrorResult<Version> {
+        Version::parse(&self.version)
+            .map_err(|e| Error::new(ModError(format!("mod {} has invalid version '{}': {e}", self.name, self.version)), Fatality::FATAL, false))
+    }
+
+    /// Returns an error if the mod does not support the running engine version.
+    pub fn check_engine_version(&self) -> ErrorResult<()> {
+        let req = parse_req(&self.name, "engine", &self.engine_version)?;
+        let engine = Version::parse(ENGINE_VERSION).expect("engine version is not valid semver");
+        if !req.matches(&engine) {
+            return Err(Error::new(ModError(format!("mod {} v{} requires engine {}, but engine is v{ENGINE_VERSION}", self.name, self.version, self.engine_version)), Fatality::FATAL, false))
+        }
+        Ok(())
+    }
+}
+
+fn parse_req(mod_name: &str, dependency: &str, req: &str) -> ErrorResult<VersionReq> {
+    VersionReq::parse(req)
+        .map_err(|e| Error::new(ModError(format!("mod {mod_name} has invalid version range '{req}' for {dependency}: {e}")), Fatality::FATAL, false))
+}
+
+/// Checks that all dependencies are present in compatible versions and returns
+/// the keys of `manifests` sorted so that every mod comes after all of its dependencies.
+///
+/// Mods without an ordering constraint between them are sorted by name to keep the order stable.
+pub fn resolve_load_order<K: Clone + Ord>(manifests: &BTreeMap<K, ModManifest>) -> ErrorResult<Vec<K>> {
+    let by_name: HashMap<&str, &K> = manifests.iter().map(|(k, m)| (m.name.as_str(), k)).collect();
+    if by_name.len() != manifests.len() {
+        return Err(Error::new(ModError("mod stack contains multiple mods with the same name".to_string()), Fatality::FATAL, false))
+    }
+
+    for manifest in manifests.values() {
+        manifest.check_engine_version()?;
+        for (dep, req_str) in &manifest.dependencies {
+            let req = parse_req(&manifest.name, dep, req_str)?;
+            let dep_manifest = by_name.get(dep.as_str()).map(|k| &manifests[*k])
+                .ok_or_else(|| Error::new(ModError(format!("mod {} depends on {dep} {req_str}, which is missing from the mod stack", manifest.name)), Fatality::FATAL, false))?;
+            if !req.matches(&dep_manifest.parsed_version()?) {
+                return Err(Error::new(ModError(format!("mod {} depends on {dep} {req_str}, but {dep} is v{}", manifest.name, dep_manifest.version)), Fatality::FATAL, false))
+            }
+        }
+    }
+
+    #[derive(Copy, Clone, PartialEq)]
+    enum Mark { Visiting, Done }
+
+    fn visit<'a, K: Clone + Ord>(key: &'a K, manifests: &'a BTreeMap<K, ModManifest>, by_name: &HashMap<&str, &'a K>,
+                                 marks: &mut BTreeMap<&'a K, Mark>, path: &mut Vec<&'a str>, order: &mut Vec<K>) -> ErrorResult<()> {
+        let manifest = &manifests[key];
+        match marks.get(key) {
+            Some(Mark::Done) => return Ok(()),
+            Some(Mark::Visiting) => {
+                path.push(&manifest.name);
+                let start = path.iter().position(|n| *n == manifest.name).unwrap();
+                return Err(Error::new(ModError(format!("circular mod dependency: {}", path[start..].join(" -> "))), Fatality::FATAL, false))
+            }
+            None => ()
+        }
+        marks.insert(key, Mark::Visiting);
+        path.push(&manifest.name);
+        let mut deps = manifest.dependencies.keys().collect::<Vec<_>>();
+        deps.sort();
+        for dep in deps {
+            visit(by_name[dep.as_str()], manifests, by_name, marks, path, order)?;
+        }
+        path.pop();
+        marks.insert(key, Mark::Done);
+        order.push(key.clone());
+        Ok(())
+    }
+
+    let mut marks = BTreeMap::new();
+    let mut order = Vec::with_capacity(manifests.len());
+    let mut keys = manifests.keys().collect::<Vec<_>>();
+    keys.sort_by(|a, b| manifests[*a].name.cmp(&manifests[*b].name));
+    for key in keys {
+        visit(key, manifests, &by_name, &mut marks, &mut vec![], &mut order)?;
+    }
+    Ok(order)
+}
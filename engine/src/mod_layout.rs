@@ -0,0 +1,46 @@
+// Names of the files inside a mod zip, shared by the server, the client and aeonetica-pack:
+//
+// <path>.zip
+// ├── manifest.ron
+// ├── <name>_server.zip
+// │   └── <name>_server.<ext>             (or <name>_server.wasm)
+// ├── <name>_client-<target>.zip        (one per mod target, or only wasm32 for wasm mods)
+// │   └── <name>_client.<ext>
+// └── <name>_client-<target>.zip.sig    (optional, see `crate::signing`)
+
+#[cfg(target_os = "windows")]
+pub const MOD_FILE_EXTENSION: &str = "dll";
+#[cfg(target_os = "linux")]
+pub const MOD_FILE_EXTENSION: &str = "so";
+
+/// The target of wasm mods, whose one client zip is served to clients of every target,
+/// see `crate::wasm_abi`.
+pub const WASM_MOD_TARGET: &str = "wasm32";
+
+/// The extension of shared libraries on a mod target like `x86_64-unix`.
+pub fn lib_extension(target: &str) -> &'static str {
+    if target == WASM_MOD_TARGET { "wasm" }
+    else if target.ends_with("-windows") { "dll" }
+    else { "so" }
+}
+
+pub fn server_zip(name: &str) -> String {
+    format!("{name}_server.zip")
+}
+
+pub fn client_zip(name: &str, target: &str) -> String {
+    format!("{name}_client-{target}.zip")
+}
+
+/// The server library is always built for the target of the server.
+pub fn server_lib(name: &str) -> String {
+    format!("{name}_server.{MOD_FILE_EXTENSION}")
+}
+
+pub fn server_wasm(name: &str) -> String {
+    format!("{name}_server.wasm")
+}
+
+pub fn client_lib(name: &str, target: &str) -> String {
+    format!("{name}_client.{}", lib_extension(target))
+}
@@ -4,6 +4,7 @@ use std::fmt::{Debug};
 use crate::{ClientId, EntityId, Id, TypeId};
 use crate::nanoserde;
 use crate::nanoserde::{SerBin, DeBin};
+use crate::networking::NetResult;
 
 
 #[derive(Debug, SerBin, DeBin)]
@@ -25,7 +26,10 @@ pub enum ClientMessage {
     Ping(String),
     Pong(String),
     RawData(Vec<u8>),
-    ModMessage(EntityId, TypeId, Vec<u8>)
+    ModMessage(EntityId, TypeId, Vec<u8>),
+    /// answered with a `ServerMessage::ModReply` with the same conv_id
+    ModRequest(EntityId, TypeId, Vec<u8>),
+    ModReply(EntityId, NetResult<Vec<u8>, String>)
 }
 
 #[derive(Debug, SerBin, DeBin)]
@@ -0,0 +1,101 @@
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU16, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use crate::error::{Error, ErrorResult, Fatality};
+use crate::error::builtin::NetworkError;
+use crate::networking::SendMode;
+use crate::networking::transport::{ClientTransport, DetachedSender, ServerTransport};
+
+type Clients = Arc<Mutex<HashMap<SocketAddr, Sender<Vec<u8>>>>>;
+
+/// Returns the transport of a server and the connector its clients connect with, for running
+/// a server and clients in the same process without sockets. Both ends can live in different threads.
+/// Packets are passed as bytes in order and are never lost, regardless of their [`SendMode`].
+pub fn memory_transport() -> (MemoryServerTransport, MemoryConnector) {
+    let (sender, receiver) = channel();
+    let clients = Clients::default();
+    (
+        MemoryServerTransport { receiver, clients: clients.clone() },
+        MemoryConnector { sender, clients, next_port: Arc::new(AtomicU16::new(1)) }
+    )
+}
+
+pub struct MemoryServerTransport {
+    receiver: Receiver<(SocketAddr, Vec<u8>)>,
+    clients: Clients
+}
+
+impl ServerTransport for MemoryServerTransport {
+    fn receive(&mut self) -> Vec<(SocketAddr, Vec<u8>)> {
+        self.receiver.try_iter().collect()
+    }
+
+    fn send(&self, addr: SocketAddr, data: Vec<u8>, _mode: SendMode) -> ErrorResult<()> {
+        let mut clients = self.clients.lock().unwrap();
+        // the client was dropped
+        if let Some(client) = clients.get(&addr) && client.send(data).is_err() {
+            clients.remove(&addr);
+        }
+        Ok(())
+    }
+
+    fn disconnect(&self, addr: &SocketAddr) {
+        self.clients.lock().unwrap().remove(addr);
+    }
+}
+
+/// Connects clients to a [`MemoryServerTransport`].
+#[derive(Clone)]
+pub struct MemoryConnector {
+    sender: Sender<(SocketAddr, Vec<u8>)>,
+    clients: Clients,
+    next_port: Arc<AtomicU16>
+}
+
+impl MemoryConnector {
+    /// Every client gets its own address on localhost, the ports count up from 1.
+    pub fn connect(&self) -> MemoryClientTransport {
+        let addr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::LOCALHOST, self.next_port.fetch_add(1, Ordering::Relaxed)));
+        let (sender, receiver) = channel();
+        self.clients.lock().unwrap().insert(addr, sender);
+        MemoryClientTransport {
+            addr,
+            sender: self.sender.clone(),
+            receiver
+        }
+    }
+}
+
+pub struct MemoryClientTransport {
+    addr: SocketAddr,
+    sender: Sender<(SocketAddr, Vec<u8>)>,
+    receiver: Receiver<Vec<u8>>
+}
+
+impl MemoryClientTransport {
+    /// The address the server sees for this client.
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+}
+
+fn server_stopped() -> Box<Error> {
+    Error::new(NetworkError("the server stopped".to_string()), Fatality::WARN, false)
+}
+
+impl ClientTransport for MemoryClientTransport {
+    fn receive(&mut self) -> Vec<Vec<u8>> {
+        self.receiver.try_iter().collect()
+    }
+
+    fn send(&self, data: Vec<u8>, _mode: SendMode) -> ErrorResult<()> {
+        self.sender.send((self.addr, data)).map_err(|_| server_stopped())
+    }
+
+    fn detached_sender(&self) -> ErrorResult<DetachedSender> {
+        let (addr, sender) = (self.addr, self.sender.clone());
+        Ok(Box::new(move |data| sender.send((addr, data)).map_err(|_| server_stopped())))
+    }
+}
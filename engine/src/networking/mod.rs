@@ -4,6 +4,10 @@ use nanoserde::{DeBin, SerBin};
 pub mod client_packets;
 pub mod server_packets;
 pub mod messaging;
+pub mod replication;
+pub mod rpc;
+pub mod transport;
+pub mod memory;
 
 pub const MAX_PACKET_SIZE: usize = 25000;
 pub const MAX_RAW_DATA_SIZE: usize = MAX_PACKET_SIZE - 26;
@@ -0,0 +1,74 @@
+use nanoserde::{DeBin, DeBinErr, SerBin};
+use crate::Id;
+use crate::util::type_to_id;
+
+/// The replicated state of a server module, as seen by the client.
+///
+/// Implement it with [`crate::impl_snapshot`] to get per-field change detection:
+/// only the fields that changed since the last update are sent.
+pub trait Snapshot: SerBin + DeBin + 'static {
+    /// Serializes the fields that differ from `previous`, `None` if nothing changed.
+    fn delta(&self, previous: &Self) -> Option<Vec<u8>>;
+    fn apply_delta(&mut self, delta: &[u8]) -> Result<(), DeBinErr>;
+}
+
+#[derive(SerBin, DeBin)]
+pub enum SnapshotMessage {
+    Full(Vec<u8>),
+    Delta(Vec<u8>)
+}
+
+/// Receiver id under which snapshots of type `S` are sent to client handles.
+pub fn replication_id<S: Snapshot>() -> Id {
+    type_to_id::<(SnapshotMessage, S)>()
+}
+
+/// Implements [`Snapshot`] for a struct, comparing the listed fields (up to 64) one by one.
+/// All listed fields need to implement `PartialEq`, `SerBin` and `DeBin`.
+/// ```ignore
+/// #[derive(SerBin, DeBin)]
+/// pub(crate) struct WormState {
+///     segments: Vec<Vector2<f32>>,
+///     looking_dir: Vector2<f32>
+/// }
+///
+/// impl_snapshot!(WormState { segments, looking_dir });
+/// ```
+#[macro_export]
+macro_rules! impl_snapshot {
+    ($name: ty { $($field: ident),+ $(,)? }) => {
+        impl $crate::networking::replication::Snapshot for $name {
+            fn delta(&self, previous: &Self) -> Option<Vec<u8>> {
+                let mut mask = 0u64;
+                let mut fields = vec![];
+                let mut _bit = 0;
+                $(
+                    if self.$field != previous.$field {
+                        mask |= 1 << _bit;
+                        $crate::nanoserde::SerBin::ser_bin(&self.$field, &mut fields);
+                    }
+                    _bit += 1;
+                )+
+                if mask == 0 {
+                    return None
+                }
+                let mut delta = $crate::nanoserde::SerBin::serialize_bin(&mask);
+                delta.extend(fields);
+                Some(delta)
+            }
+
+            fn apply_delta(&mut self, delta: &[u8]) -> Result<(), $crate::nanoserde::DeBinErr> {
+                let mut offset = 0;
+                let mask: u64 = $crate::nanoserde::DeBin::de_bin(&mut offset, delta)?;
+                let mut _bit = 0;
+                $(
+                    if mask & (1 << _bit) != 0 {
+                        self.$field = $crate::nanoserde::DeBin::de_bin(&mut offset, delta)?;
+                    }
+                    _bit += 1;
+                )+
+                Ok(())
+            }
+        }
+    };
+}
@@ -0,0 +1,188 @@
+use std::any::type_name;
+use std::collections::HashMap;
+use std::ffi::c_void;
+use std::fmt::{Display, Formatter};
+#[allow(deprecated)]
+use std::hash::{Hasher, SipHasher};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicPtr, Ordering};
+use std::time::Duration;
+use nanoserde::{DeBin, SerBin};
+use crate::{Id, log};
+
+/// A remote procedure call, defined once in code shared by client and server with [`crate::rpc`].
+///
+/// Calls are identified by [`Rpc::NAME`] instead of the type of a function item,
+/// so the id stays the same when the receiving function changes.
+pub trait Rpc: 'static {
+    type Args: SerBin + DeBin;
+    const NAME: &'static str;
+
+    fn id() -> Id {
+        rpc_id(Self::NAME)
+    }
+}
+
+/// An [`Rpc`] the other side answers, defined with [`crate::request`].
+pub trait Request: Rpc {
+    type Reply: SerBin + DeBin;
+}
+
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RequestError {
+    /// no reply arrived in time
+    Timeout,
+    /// the other side has no handler for the request or could not decode it
+    Remote(String),
+    /// the reply could not be decoded
+    Decode(String)
+}
+
+impl Display for RequestError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RequestError::Timeout => write!(f, "request timed out"),
+            RequestError::Remote(e) => write!(f, "request failed on the other side: {e}"),
+            RequestError::Decode(e) => write!(f, "could not decode reply: {e}")
+        }
+    }
+}
+
+/// Decodes the reply to `R` as received from the other side.
+pub fn decode_reply<R: Request>(reply: Result<Vec<u8>, String>) -> Result<R::Reply, RequestError> {
+    let data = reply.map_err(RequestError::Remote)?;
+    R::Reply::deserialize_bin(&data).map_err(|e| RequestError::Decode(format!("{} ({e:?})", R::NAME)))
+}
+
+/// Runs a request handler on the received data, the result is sent back as the reply.
+pub fn handle_request<R: Request>(data: &[u8], handler: impl FnOnce(R::Args) -> R::Reply) -> Result<Vec<u8>, String> {
+    let args = decode_args::<R>(data).ok_or_else(|| format!("could not decode arguments of {}", R::NAME))?;
+    Ok(handler(args).serialize_bin())
+}
+
+pub fn rpc_id(name: &str) -> Id {
+    #[allow(deprecated)]
+    let mut s = SipHasher::new();
+    s.write(name.as_bytes());
+    Id::from_u64(s.finish())
+}
+
+/// Names and argument types of all rpcs that got a receiver, by id.
+///
+/// Every mod library links its own copy of the engine, so the host hands its registry to each mod
+/// through [`crate::abi::ModEntry`] when loading it. Names are owned since a mod may be unloaded.
+#[derive(Default)]
+pub struct RpcRegistry(Mutex<Option<HashMap<Id, (String, String)>>>);
+
+impl RpcRegistry {
+    pub const fn new() -> Self {
+        Self(Mutex::new(None))
+    }
+
+    /// Returns `false` and logs an error if a different rpc with the same id or different arguments was registered before.
+    pub fn register<R: Rpc>(&self) -> bool {
+        let mut registered = self.0.lock().unwrap();
+        let entry = registered.get_or_insert_with(Default::default)
+            .entry(R::id())
+            .or_insert_with(|| (R::NAME.to_string(), type_name::<R::Args>().to_string()));
+        if (entry.0.as_str(), entry.1.as_str()) != (R::NAME, type_name::<R::Args>()) {
+            log!(ERROR, "rpc {} ({}) clashes with {} ({})", R::NAME, type_name::<R::Args>(), entry.0, entry.1);
+            false
+        } else {
+            true
+        }
+    }
+
+    pub fn name(&self, id: &Id) -> Option<String> {
+        self.0.lock().unwrap().as_ref()?.get(id).map(|(name, _)| name.clone())
+    }
+}
+
+static REGISTERED: RpcRegistry = RpcRegistry::new();
+/// The registry of the host, set in mods when they are loaded.
+static HOST_REGISTERED: AtomicPtr<RpcRegistry> = AtomicPtr::new(std::ptr::null_mut());
+
+fn registry() -> &'static RpcRegistry {
+    let host = HOST_REGISTERED.load(Ordering::Acquire);
+    if host.is_null() {
+        &REGISTERED
+    } else {
+        unsafe { &*host }
+    }
+}
+
+/// The registry this copy of the engine uses, passed to [`use_rpc_registry`] of every loaded mod.
+pub fn rpc_registry() -> *const c_void {
+    registry() as *const RpcRegistry as *const c_void
+}
+
+/// Makes this copy of the engine record rpcs in `registry`, which has to outlive the library.
+/// Called by the host through [`crate::abi::ModEntry`] right after checking the mod's fingerprint.
+pub extern "C" fn use_rpc_registry(registry: *const c_void) {
+    HOST_REGISTERED.store(registry as *mut RpcRegistry, Ordering::Release);
+}
+
+/// Called whenever a receiver for `R` is registered, see [`RpcRegistry::register`].
+pub fn register_rpc<R: Rpc>() -> bool {
+    registry().register::<R>()
+}
+
+/// Name of the rpc, if a receiver for it was registered, for error messages.
+pub fn rpc_name(id: &Id) -> Option<String> {
+    registry().name(id)
+}
+
+/// Decodes the arguments of `R`, logging an error if that fails.
+pub fn decode_args<R: Rpc>(data: &[u8]) -> Option<R::Args> {
+    match R::Args::deserialize_bin(data) {
+        Ok(args) => Some(args),
+        Err(e) => {
+            log!(ERROR, "could not decode arguments of rpc {}: {e:?}", R::NAME);
+            None
+        }
+    }
+}
+
+/// Defines an [`Rpc`] and its arguments:
+/// ```ignore
+/// rpc!(pub ReceivePosition: (Vector2<f32>, bool));
+///
+/// // server
+/// messenger.send::<ReceivePosition>((position, true), SendMode::Safe);
+/// // client
+/// messenger.on::<ReceivePosition, _>(PlayerHandle::receive_position);
+/// ```
+/// The id is derived from the crate and rpc name.
+#[macro_export]
+macro_rules! rpc {
+    ($vis: vis $name: ident: $args: ty) => {
+        $vis struct $name;
+
+        impl $crate::networking::rpc::Rpc for $name {
+            type Args = $args;
+            const NAME: &'static str = concat!(env!("CARGO_PKG_NAME"), "::", stringify!($name));
+        }
+    };
+}
+
+/// Defines a [`Request`], its arguments and its reply:
+/// ```ignore
+/// request!(pub RequestChunk: Vector2<i32> => Chunk);
+///
+/// // server
+/// messenger.on_request::<RequestChunk>(|id, engine, client, pos| engine.chunk(pos));
+/// // client
+/// messenger.request::<RequestChunk, WorldHandle>(pos, DEFAULT_REQUEST_TIMEOUT, |handle, messenger, renderer, store, chunk| { .. });
+/// ```
+#[macro_export]
+macro_rules! request {
+    ($vis: vis $name: ident: $args: ty => $reply: ty) => {
+        $crate::rpc!($vis $name: $args);
+
+        impl $crate::networking::rpc::Request for $name {
+            type Reply = $reply;
+        }
+    };
+}
@@ -6,6 +6,7 @@ use crate::{ClientId, EntityId, Id, TypeId};
 use crate::nanoserde;
 use crate::nanoserde::{SerBin, DeBin};
 use crate::networking::NetResult;
+use crate::signing::ModSignature;
 
 
 #[derive(Debug, SerBin, DeBin)]
@@ -28,14 +29,30 @@ pub enum ServerMessage {
     RawData(Vec<u8>),
     AddClientHandle(EntityId, TypeId),
     RemoveClientHandle(EntityId),
-    ModMessage(EntityId, TypeId, Vec<u8>)
+    ModMessage(EntityId, TypeId, Vec<u8>),
+    /// answered with a `ClientMessage::ModReply` with the same conv_id
+    ModRequest(EntityId, TypeId, Vec<u8>),
+    ModReply(EntityId, NetResult<Vec<u8>, String>),
+    /// a mod whose client library changed
+    ReloadMod(ModInfo)
 }
 
-/// mods: Vec<(ModName, ModFlags, ZipHash, FileSize)>
+/// mods are in the order they have to be loaded
 #[derive(Debug, SerBin, DeBin)]
 pub struct ServerInfo {
     pub server_version: String,
     pub mod_profile: String,
     pub mod_version: String,
-    pub mods: Vec<(String, Vec<String>, String, u64)>
+    pub mods: Vec<ModInfo>
+}
+
+/// A client zip of a mod for the target of the client.
+#[derive(Debug, Clone, SerBin, DeBin)]
+pub struct ModInfo {
+    pub name_path: String,
+    pub flags: Vec<String>,
+    /// uppercase hex sha256 of the zip
+    pub hash: String,
+    pub size: u64,
+    pub signature: Option<ModSignature>
 }
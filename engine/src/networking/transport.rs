@@ -0,0 +1,29 @@
+use std::net::SocketAddr;
+use crate::error::ErrorResult;
+use crate::networking::SendMode;
+
+/// Carries serialized packets between the server and its clients. Serializing the packets and
+/// checking their size is left to the server and the client, a transport only delivers the bytes:
+/// over udp and tcp sockets in a normal game, over channels in singleplayer and in tests,
+/// see [`crate::networking::memory`].
+pub trait ServerTransport {
+    /// The packets received since the last call, with the address of the client that sent them.
+    fn receive(&mut self) -> Vec<(SocketAddr, Vec<u8>)>;
+    /// Packets to addresses without a connection are dropped.
+    fn send(&self, addr: SocketAddr, data: Vec<u8>, mode: SendMode) -> ErrorResult<()>;
+    /// Forgets the connection of a client that logged out.
+    #[allow(unused_variables)]
+    fn disconnect(&self, addr: &SocketAddr) {}
+}
+
+/// Sends quick packets to the server from another thread, see [`ClientTransport::detached_sender`].
+pub type DetachedSender = Box<dyn FnMut(Vec<u8>) -> ErrorResult<()> + Send>;
+
+pub trait ClientTransport {
+    /// The packets received from the server since the last call.
+    fn receive(&mut self) -> Vec<Vec<u8>>;
+    fn send(&self, data: Vec<u8>, mode: SendMode) -> ErrorResult<()>;
+    /// Used to keep the connection alive while the client is busy.
+    /// A fatal error means the server can not be reached anymore.
+    fn detached_sender(&self) -> ErrorResult<DetachedSender>;
+}
@@ -0,0 +1,98 @@
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use nanoserde::{DeBin, SerBin};
+use crate::error::{Error, ErrorResult, Fatality};
+use crate::error::builtin::ModError;
+
+/// The signature of a client zip belongs next to it as `<zip>.sig`.
+pub fn signature_file(zip: &str) -> String {
+    format!("{zip}.sig")
+}
+
+/// An ed25519 signature of a client zip by the mod's publisher.
+///
+/// What gets signed is the uppercase hex sha256 of the zip, the same hash the
+/// server advertises, so cached mods can be checked without their zip.
+/// On disk it is stored as two lines: the hex public key and the hex signature.
+#[derive(Debug, Clone, PartialEq, SerBin, DeBin)]
+pub struct ModSignature {
+    pub public_key: Vec<u8>,
+    pub signature: Vec<u8>
+}
+
+impl ModSignature {
+    pub fn sign(zip_hash: &str, key: &SigningKey) -> Self {
+        Self {
+            public_key: key.verifying_key().to_bytes().to_vec(),
+            signature: key.sign(zip_hash.to_ascii_uppercase().as_bytes()).to_bytes().to_vec()
+        }
+    }
+
+    pub fn verify(&self, zip_hash: &str) -> bool {
+        let Ok(public_key) = <[u8; 32]>::try_from(self.public_key.as_slice()) else { return false };
+        let Ok(signature) = <[u8; 64]>::try_from(self.signature.as_slice()) else { return false };
+        let Ok(key) = VerifyingKey::from_bytes(&public_key) else { return false };
+        key.verify(zip_hash.to_ascii_uppercase().as_bytes(), &Signature::from_bytes(&signature)).is_ok()
+    }
+
+    /// The public key of the publisher in hex, as used in trust stores.
+    pub fn publisher(&self) -> String {
+        to_hex(&self.public_key)
+    }
+
+    /// Reads the signature at `path`, `None` if there is none.
+    pub fn load<P: AsRef<Path>>(path: P) -> ErrorResult<Option<Self>> {
+        let mut data = String::new();
+        match File::open(path.as_ref()) {
+            Ok(mut f) => f.read_to_string(&mut data)?,
+            Err(_) => return Ok(None)
+        };
+        Self::parse(&data)
+            .map(Some)
+            .ok_or_else(|| Error::new(ModError(format!("invalid signature file {}", path.as_ref().display())), Fatality::FATAL, false))
+    }
+
+    /// Parses the contents of a signature file.
+    pub fn parse(data: &str) -> Option<Self> {
+        let mut lines = data.lines();
+        Some(Self {
+            public_key: from_hex(lines.next()?)?,
+            signature: from_hex(lines.next()?)?
+        })
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> ErrorResult<()> {
+        let mut f = File::create(path)?;
+        writeln!(f, "{}", to_hex(&self.public_key))?;
+        writeln!(f, "{}", to_hex(&self.signature))?;
+        Ok(())
+    }
+}
+
+/// Reads a signing key stored as 64 hex characters.
+pub fn load_signing_key<P: AsRef<Path>>(path: P) -> ErrorResult<SigningKey> {
+    let mut data = String::new();
+    File::open(path.as_ref())
+        .map_err(|e| Error::new(ModError(format!("could not open signing key {}: {e}", path.as_ref().display())), Fatality::FATAL, false))?
+        .read_to_string(&mut data)?;
+    from_hex(data.trim())
+        .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+        .map(|bytes| SigningKey::from_bytes(&bytes))
+        .ok_or_else(|| Error::new(ModError(format!("invalid signing key {}", path.as_ref().display())), Fatality::FATAL, false))
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02X}")).collect()
+}
+
+pub fn from_hex(s: &str) -> Option<Vec<u8>> {
+    let s = s.trim();
+    if s.len() % 2 != 0 {
+        return None
+    }
+    (0..s.len()).step_by(2)
+        .map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok())
+        .collect()
+}
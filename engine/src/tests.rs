@@ -2,6 +2,10 @@ use std::collections::{BTreeMap, HashMap};
 use test::Bencher;
 use crate::Id;
 use crate::util::id_map::{IdMap};
+use crate::manifest::{ModManifest, resolve_load_order};
+use crate::networking::replication::Snapshot;
+use crate::math::vector::Vector2;
+use nanoserde::{DeBin, SerBin};
 
 #[bench]
 fn bench_hashmap(b: &mut Bencher) {
@@ -55,4 +59,140 @@ fn bench_btree(b: &mut Bencher) {
             let _ = map.get(k);
         })
     });
-}
\ No newline at end of file
+}
+
+fn manifest(name: &str, version: &str, deps: &[(&str, &str)]) -> ModManifest {
+    ModManifest {
+        name: name.to_string(),
+        version: version.to_string(),
+        engine_version: format!("^{}", crate::ENGINE_VERSION),
+        dependencies: deps.iter().map(|(n, v)| (n.to_string(), v.to_string())).collect()
+    }
+}
+
+#[test]
+fn mod_load_order() {
+    let manifests = BTreeMap::from([
+        ("worms:worms", manifest("worms", "0.1.0", &[("player", "^0.1"), ("world", "^0.1")])),
+        ("player:player", manifest("player", "0.1.0", &[("world", "^0.1")])),
+        ("debug:debug", manifest("debug", "0.1.0", &[])),
+        ("world:world", manifest("world", "0.1.3", &[]))
+    ]);
+    let order = resolve_load_order(&manifests).unwrap();
+    assert_eq!(order, vec!["debug:debug", "world:world", "player:player", "worms:worms"]);
+}
+
+#[test]
+fn mod_missing_or_incompatible_dependency() {
+    let missing = BTreeMap::from([
+        ("worms:worms", manifest("worms", "0.1.0", &[("player", "^0.1")]))
+    ]);
+    assert!(resolve_load_order(&missing).is_err());
+    let incompatible = BTreeMap::from([
+        ("worms:worms", manifest("worms", "0.1.0", &[("player", "^0.2")])),
+        ("player:player", manifest("player", "0.1.0", &[]))
+    ]);
+    assert!(resolve_load_order(&incompatible).is_err());
+}
+
+#[test]
+fn mod_dependency_cycle() {
+    let manifests = BTreeMap::from([
+        ("a:a", manifest("a", "0.1.0", &[("b", "*")])),
+        ("b:b", manifest("b", "0.1.0", &[("a", "*")]))
+    ]);
+    assert!(resolve_load_order(&manifests).is_err());
+}
+
+#[derive(SerBin, DeBin, Clone, Debug, PartialEq)]
+struct TestSnapshot {
+    position: Vector2<f32>,
+    health: u32,
+    name: String
+}
+
+crate::impl_snapshot!(TestSnapshot { position, health, name });
+
+#[test]
+fn snapshot_delta_only_contains_changed_fields() {
+    let old = TestSnapshot { position: Vector2::new(1.0, 2.0), health: 10, name: "worm".to_string() };
+    let mut new = old.clone();
+    assert!(new.delta(&old).is_none());
+
+    new.health = 7;
+    let delta = new.delta(&old).unwrap();
+    // mask and a single u32
+    assert_eq!(delta.len(), 8 + 4);
+
+    new.position = Vector2::new(3.0, 4.0);
+    let mut client = old.clone();
+    client.apply_delta(&new.delta(&old).unwrap()).unwrap();
+    assert_eq!(client, new);
+    assert!(client.apply_delta(&[1]).is_err());
+}
+
+mod rpcs {
+    crate::rpc!(pub(super) Ping: u32);
+}
+
+mod other_rpcs {
+    crate::rpc!(pub(super) Ping: String);
+}
+
+#[test]
+fn rpc_ids_are_stable_and_checked() {
+    use crate::networking::rpc::{register_rpc, rpc_id, rpc_name, Rpc};
+    assert_eq!(rpcs::Ping::NAME, "engine::Ping");
+    assert_eq!(rpcs::Ping::id(), rpc_id("engine::Ping"));
+    assert!(register_rpc::<rpcs::Ping>());
+    assert!(register_rpc::<rpcs::Ping>());
+    // same name, different arguments
+    assert!(!register_rpc::<other_rpcs::Ping>());
+    assert_eq!(rpc_name(&rpcs::Ping::id()).as_deref(), Some("engine::Ping"));
+}
+
+#[test]
+fn rpc_registries_are_separate() {
+    use crate::networking::rpc::{RpcRegistry, Rpc};
+    // a mod loaded without the host's registry would check against an empty one
+    let host = RpcRegistry::new();
+    let unlinked = RpcRegistry::new();
+    assert!(host.register::<rpcs::Ping>());
+    assert!(unlinked.register::<other_rpcs::Ping>());
+    assert!(!host.register::<other_rpcs::Ping>());
+    assert_eq!(host.name(&rpcs::Ping::id()).as_deref(), Some("engine::Ping"));
+    assert_eq!(RpcRegistry::new().name(&rpcs::Ping::id()), None);
+}
+
+#[test]
+fn mod_signatures() {
+    use crate::signing::{from_hex, ModSignature, to_hex};
+    let key = crate::ed25519_dalek::SigningKey::from_bytes(&[7; 32]);
+    let signature = ModSignature::sign("ab12", &key);
+    assert!(signature.verify("AB12"));
+    assert!(!signature.verify("AB13"));
+    assert_eq!(from_hex(&signature.publisher()).unwrap(), signature.public_key);
+    assert_eq!(to_hex(&[0, 255]), "00FF");
+    assert!(from_hex("0").is_none());
+
+    let mut forged = signature.clone();
+    forged.public_key = crate::ed25519_dalek::SigningKey::from_bytes(&[8; 32]).verifying_key().to_bytes().to_vec();
+    assert!(!forged.verify("AB12"));
+}
+
+#[test]
+fn abi_fingerprint_depends_on_layouts() {
+    use crate::abi::{fingerprint, layout_of};
+    let a = fingerprint(&[layout_of::<u32>(), layout_of::<Id>()]);
+    assert_eq!(a, fingerprint(&[layout_of::<u32>(), layout_of::<Id>()]));
+    assert_ne!(a, fingerprint(&[layout_of::<u64>(), layout_of::<Id>()]));
+    assert_ne!(a, fingerprint(&[layout_of::<Id>(), layout_of::<u32>()]));
+}
+
+#[test]
+fn mod_entry_starts_with_abi_version() {
+    use crate::abi::ModEntry;
+    // the loader reads `abi_version` before knowing the rest of the layout is the same
+    assert_eq!(std::mem::offset_of!(ModEntry, abi_version), 0);
+    assert_eq!(std::mem::size_of::<Option<extern "C" fn() -> u64>>(), std::mem::size_of::<usize>());
+}
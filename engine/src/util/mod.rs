@@ -86,6 +86,8 @@ pub trait Typle {
     type NullableMutTuple<'a>;
     fn to_type_id_arr() -> [TypeId; Self::LEN];
     unsafe fn opt_boxed_arr_to_tuple_of_nullable_mut<'a, PseudoTy: ?Sized>(arr: [Option<&mut Box<PseudoTy>>; Self::LEN]) -> Self::NullableMutTuple<'a>;
+    /// Each pointer has to point to a valid value of the type at the same position in the tuple.
+    unsafe fn opt_ptr_arr_to_tuple_of_nullable_mut<'a>(arr: [Option<*mut u8>; Self::LEN]) -> Self::NullableMutTuple<'a>;
 }
 
 macro_rules! count {
@@ -106,6 +108,10 @@ macro_rules! typle_impls {
             unsafe fn opt_boxed_arr_to_tuple_of_nullable_mut<'a, PseudoTy: ?Sized>(mut arr: [Option<&mut Box<PseudoTy>>; Self::LEN]) -> Self::NullableMutTuple<'a> {
                 ($(arr[$index].take().map(|m| unsafe { &mut*std::mem::transmute::<&mut Box<_>, &(*mut $name, usize)>(m).0 }).into(), )+)
             }
+
+            unsafe fn opt_ptr_arr_to_tuple_of_nullable_mut<'a>(arr: [Option<*mut u8>; Self::LEN]) -> Self::NullableMutTuple<'a> {
+                ($(arr[$index].map(|m| unsafe { &mut *(m as *mut $name) }).into(), )+)
+            }
         }
     };
 }
@@ -0,0 +1,58 @@
+// The interface between the host and a WebAssembly mod, the sandboxed alternative to native mod
+// libraries. A wasm mod is one `.wasm` file per side that works on every mod target, see
+// `crate::mod_layout::WASM_MOD_TARGET`. Everything is passed as i32/i64/f32, ids as their `into_u64`,
+// rpcs by their `Rpc::NAME` (the guest gets the id from `rpc_id`) and rpc data as the `SerBin` bytes
+// of the arguments, so wasm and native mods can talk to each other.
+//
+// Exports of every guest:
+//   memory
+//   aeonetica_abi_version() -> i32                  has to return `WASM_ABI_VERSION`
+//   aeonetica_alloc(len: i32) -> i32                memory the host writes arguments to, owned by the guest afterwards
+//   aeonetica_init(flags: i32, len: i32)            `ServerMod::init`/`ClientMod::init`, the flags joined by '\n'
+//
+// Imports of every guest (module `IMPORT_MODULE`):
+//   log(ptr: i32, len: i32)
+//   rpc_id(name: i32, len: i32) -> i64
+//
+// Server guests additionally export:
+//   aeonetica_start()                                `ServerMod::start`
+//   aeonetica_tick(entity: i64, delta: f32)          `Module::tick` of entities the guest called `add_module` on
+//   aeonetica_receive(entity: i64, client: i64, rpc: i64, data: i32, len: i32)
+// and import:
+//   new_entity() -> i64
+//   remove_entity(entity: i64) -> i32              the entity is removed at the next sync point
+//   add_module(entity: i64) -> i32
+//   add_messenger(entity: i64, handle_type: i64) -> i32
+//   on(entity: i64, rpc: i64) -> i32                 rpcs sent by clients are passed to `aeonetica_receive`
+//   send(entity: i64, rpc: i64, data: i32, len: i32, mode: i32)
+//   send_to(entity: i64, client: i64, rpc: i64, data: i32, len: i32, mode: i32)
+//   add_client(entity: i64, client: i64) -> i32
+//   remove_client(entity: i64, client: i64) -> i32
+//
+// Client guests additionally export:
+//   aeonetica_handle_start(entity: i64, handle_type: i64)
+//   aeonetica_handle_receive(entity: i64, rpc: i64, data: i32, len: i32)
+//   aeonetica_handle_remove(entity: i64)
+// and import:
+//   register_handle(handle_type: i64)                only during `aeonetica_init`
+//   on(entity: i64, rpc: i64) -> i32                 rpcs sent by the server are passed to `aeonetica_handle_receive`
+//   send(entity: i64, rpc: i64, data: i32, len: i32, mode: i32)
+//
+// Server imports only accept entities the guest created with `new_entity`, others fail.
+// Functions returning i32 return 1 for success and 0 otherwise, `mode` is 0 for `SendMode::Quick`
+// and 1 for `SendMode::Safe`. Exports the guest does not need may be left out.
+// Client handles of wasm mods belong to no layer, rendering is not part of this version.
+
+use crate::networking::SendMode;
+
+/// Version of the interface above, raised whenever it changes.
+pub const WASM_ABI_VERSION: i32 = 1;
+pub const IMPORT_MODULE: &str = "aeonetica";
+
+/// Instructions a guest may execute per call into it before it is stopped,
+/// so a broken mod cannot hang the game.
+pub const FUEL_PER_CALL: u64 = 100_000_000;
+
+pub fn send_mode(mode: i32) -> SendMode {
+    if mode == 0 { SendMode::Quick } else { SendMode::Safe }
+}
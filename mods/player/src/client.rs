@@ -1,5 +1,6 @@
-use std::ops::{Add, Mul, Sub};
+use std::collections::VecDeque;
 use std::rc::Rc;
+use std::time::{Duration, Instant};
 use aeonetica_client::ClientMod;
 use aeonetica_client::data_store::DataStore;
 use aeonetica_client::networking::messaging::{ClientHandle, ClientMessenger};
@@ -22,11 +23,11 @@ use aeonetica_engine::util::nullable::Nullable::{Null, Value};
 use aeonetica_engine::util::type_to_id;
 use aeonetica_engine::math::vector::{Vector2, Vector3};
 use debug_mod::Debug;
-use world_mod::common::{GRAVITY, WorldView};
 use world_mod::client::{ClientWorld, WorldLayer};
 use world_mod::client::CameraData;
 use world_mod::client::{materials::{WithGlow, terrain_material, GlowTexture}, light::*};
-use crate::server::Player;
+use crate::movement::{MovementState, PLAYER_SIZE, PlayerInput};
+use crate::messages::{PlayerHandleType, PlayerState, ReceiveState, SendInputs, SetControlling};
 
 pub struct PlayerModClient {
 
@@ -34,7 +35,7 @@ pub struct PlayerModClient {
 
 impl ClientMod for PlayerModClient {
     fn register_handlers(&self, handlers: &mut IdMap<fn() -> Box<dyn ClientHandle>>, _store: &mut DataStore) {
-        handlers.insert(type_to_id::<PlayerHandle>(), || Box::new(PlayerHandle::new()));
+        handlers.insert(type_to_id::<PlayerHandleType>(), || Box::new(PlayerHandle::new()));
         log!("registered  client player mod stuffs");
     }
 
@@ -68,8 +69,7 @@ impl PlayerTexture {
 
 pub struct PlayerHandle {
     is_controlling: bool,
-    interpolation_delta: f32,
-    p_position: Vector2<f32>,
+    /// where the player is drawn
     position: Vector2<f32>,
 
     // rendering stuff
@@ -80,19 +80,25 @@ pub struct PlayerHandle {
     key_left: bool,
     key_right: bool,
     key_hover: bool,
-    speed: f32,
-    hover_force: f32,
-    hover_energy: f32,
-    is_grounded: bool,
-    velocity: Vector2<f32>
+    /// predicted state of the controlled player
+    state: MovementState,
+    sequence: u32,
+    /// inputs the server did not acknowledge yet
+    pending_inputs: VecDeque<PlayerInput>,
+    /// received positions of other players, drawn [`INTERPOLATION_DELAY`] in the past
+    snapshots: VecDeque<(Instant, Vector2<f32>)>
 }
 
+/// Inputs are sent quick, so every message repeats the latest unacknowledged ones.
+const RESENT_INPUTS: usize = 8;
+/// Stop predicting further ahead of the server than this.
+const MAX_PENDING_INPUTS: usize = 120;
+const INTERPOLATION_DELAY: Duration = Duration::from_millis(100);
+
 impl PlayerHandle {
     fn new() -> Self {
         Self {
             is_controlling: false,
-            interpolation_delta: 0.0,
-            p_position: Default::default(),
             position: Default::default(),
             quad: Null,
             light_id: 0,
@@ -100,11 +106,10 @@ impl PlayerHandle {
             key_left: false,
             key_right: false,
             key_hover: false,
-            speed: 10.0,
-            hover_force: 12.0,
-            hover_energy: 1.0,
-            is_grounded: false,
-            velocity: Default::default(),
+            state: MovementState::new(Default::default()),
+            sequence: 0,
+            pending_inputs: Default::default(),
+            snapshots: Default::default()
         }
     }
 
@@ -113,36 +118,64 @@ impl PlayerHandle {
         self.is_controlling = is_controlling
     }
 
-    pub(crate) fn receive_position(&mut self, _messenger: &mut ClientMessenger, _renderer: Nullable<&mut Renderer>, _store: &mut DataStore, (position, teleporting): (Vector2<f32>, bool)) { 
-		if teleporting {
+    pub(crate) fn receive_position(&mut self, _messenger: &mut ClientMessenger, _renderer: Nullable<&mut Renderer>, _store: &mut DataStore, state: &PlayerState, teleporting: bool) {
+        let position = state.position;
+        if teleporting {
             log!("got teleported");
-			self.p_position = position;
-			self.interpolation_delta = 1.0;
-			self.position = position;
-			let quad = &mut *self.quad;
-			quad.set_position(self.position);
-		} else {
-			if !self.is_controlling {
-				self.p_position = self.p_position + (self.position - self.p_position) * self.interpolation_delta;
-				self.interpolation_delta = 0.0;
-				self.position = position;
-			}
-		}
+            self.state = MovementState::new(position);
+            self.pending_inputs.clear();
+            self.snapshots.clear();
+            self.position = position;
+            let quad = &mut *self.quad;
+            quad.set_position(self.position);
+        } else if !self.is_controlling {
+            self.snapshots.push_back((Instant::now(), position));
+        }
+    }
+
+    /// Reconciliation: the server's state replaces the prediction,
+    /// then all inputs the server has not seen yet are replayed on top of it.
+    pub(crate) fn receive_state(&mut self, _messenger: &mut ClientMessenger, _renderer: Nullable<&mut Renderer>, store: &mut DataStore, (sequence, state): (u32, MovementState)) {
+        if !self.is_controlling {
+            return
+        }
+        self.pending_inputs.retain(|i| i.sequence > sequence);
+        self.state = state;
+        let world = store.get_store::<ClientWorld>();
+        for input in &self.pending_inputs {
+            self.state.step(input, *world);
+        }
+    }
+
+    /// Position of a remote player at `INTERPOLATION_DELAY` ago, between the two snapshots around that time.
+    fn interpolated_position(&mut self) -> Vector2<f32> {
+        let render_time = Instant::now() - INTERPOLATION_DELAY;
+        while self.snapshots.len() > 2 && self.snapshots[1].0 <= render_time {
+            self.snapshots.pop_front();
+        }
+        match (self.snapshots.front(), self.snapshots.get(1)) {
+            (Some((t0, p0)), Some((t1, p1))) if *t0 <= render_time => {
+                let span = (*t1 - *t0).as_secs_f32();
+                let alpha = if span > 0.0 { ((render_time - *t0).as_secs_f32() / span).min(1.0) } else { 1.0 };
+                *p0 + (*p1 - *p0) * alpha
+            }
+            (Some((_, p0)), _) => *p0,
+            _ => self.position
+        }
     }
 }
 
 impl ClientEntity for PlayerHandle {}
 
-const PLAYER_SIZE: f32 = 0.9;
-
 impl ClientHandle for PlayerHandle {
     fn owning_layer(&self) -> TypeId {
         type_to_id::<WorldLayer>()
     }
 
     fn start(&mut self, messenger: &mut ClientMessenger, _renderer: Nullable<&mut Renderer>, store: &mut DataStore) {
-        messenger.register_receiver(Self::set_controlling);
-        messenger.register_receiver(Self::receive_position);
+        messenger.on::<SetControlling, _>(Self::set_controlling);
+        messenger.register_replicated(Self::receive_position);
+        messenger.on::<ReceiveState, _>(Self::receive_state);
 
         let size = Vector2::new(PLAYER_SIZE, PLAYER_SIZE);
         self.quad = Value(
@@ -164,66 +197,42 @@ impl ClientHandle for PlayerHandle {
     }
 
     fn update(&mut self, messenger: &mut ClientMessenger, renderer: &mut Renderer, store: &mut DataStore, time: Time) {
-        let quad = &mut *self.quad;
         if self.is_controlling {
-            self.velocity.y -= GRAVITY * time.delta as f32;
-            if self.key_hover {
-                if self.hover_energy > 0.0 {
-                    self.velocity.y = (self.velocity.y * (1.0 - time.delta as f32 * 10.0)) - self.hover_force * time.delta as f32 * 10.0;
-                }
-                self.hover_energy = self.hover_energy.sub(0.75 * time.delta as f32).max(0.0);
-            } else {
-                self.hover_energy = self.hover_energy.add( if self.is_grounded { 1.0 } else { 0.125 } * time.delta as f32).min(1.0);
-            }
-            store.mut_store::<PlayerUIView>().hover_energy = self.hover_energy;
-
-            self.velocity.y -= self.velocity.y.abs().mul(0.25).max(0.025).mul(time.delta as f32).min(self.velocity.y.abs()).copysign(self.velocity.x);
-            self.velocity.x -= self.velocity.x.abs().mul(0.25).max(0.025).mul(time.delta as f32).min(self.velocity.x.abs()).copysign(self.velocity.x);
-            let v = if self.velocity.x.abs() < 0.05 {
-                self.velocity.x = 0.0;
-                self.velocity + Vector2::new(match (self.key_left, self.key_right) {
-                    (true, false) => -self.speed,
-                    (false, true) => self.speed,
-                    _ => 0.0
-                }, 0.0)
-            } else { self.velocity };
-
-            if v.mag_sq() > 0.0 {
+            if self.pending_inputs.len() < MAX_PENDING_INPUTS {
+                self.sequence += 1;
+                let input = PlayerInput {
+                    sequence: self.sequence,
+                    left: self.key_left,
+                    right: self.key_right,
+                    hover: self.key_hover,
+                    delta: time.delta
+                };
                 let world = store.get_store::<ClientWorld>();
-                let p = self.position;
-                let mov_delta = v * time.delta as f32;
-                world.calc_move(&mut self.position, Vector2::new(PLAYER_SIZE, PLAYER_SIZE), mov_delta);
-                let delta = self.position - p;
-                if delta.x.abs() < 0.01 * time.delta as f32 {
-                    self.velocity.x = 0.0;
-                }
-                if delta.y.abs() < 0.01 * time.delta as f32 {
-                    if self.velocity.y > 16.0 {
-                        store.mut_store::<CameraData>().add_trauma((self.velocity.y / 50.0) * (self.velocity.y / 50.0));
-                    }
-                    self.velocity.y = 0.0;
-                    let world = store.get_store::<ClientWorld>();
-                    self.is_grounded = world.overlap_aabb(self.position + Vector2::new(0.0, 0.02), Vector2::new(PLAYER_SIZE, PLAYER_SIZE));
-                } else {
-                    self.is_grounded = false;
+                let impact = self.state.step(&input, *world);
+                if let Some(velocity) = impact {
+                    store.mut_store::<CameraData>().add_trauma((velocity / 50.0) * (velocity / 50.0));
                 }
+                self.pending_inputs.push_back(input);
             }
-
-            if (self.position - self.p_position).mag_sq() > 0.05 {
-                messenger.call_server_fn(Player::client_position_update, (self.position, false), SendMode::Quick);
-                self.p_position = self.position;
+            let inputs = self.pending_inputs.iter().rev().take(RESENT_INPUTS).rev().copied().collect::<Vec<_>>();
+            if !inputs.is_empty() {
+                messenger.send::<SendInputs>(inputs, SendMode::Quick);
             }
-            quad.set_position(self.position);
+
+            store.mut_store::<PlayerUIView>().hover_energy = self.state.hover_energy;
+            self.position = self.state.position;
             store.mut_store::<CameraData>().position = self.position;
-        } else if self.interpolation_delta < 1.0 {
-            let delta = self.position - self.p_position;
-            let debug = store.get_store::<Debug<WorldLayer>>();
-            let mut debug = debug.renderer();
-            debug.rect(self.position, Vector2::new(0.9, 0.9), 0.1, [1.0, 0.0, 0.0, 1.0]);
-            quad.set_position(self.p_position + delta * self.interpolation_delta);
-            self.interpolation_delta = (time.delta as f32 * self.speed + self.interpolation_delta).min(1.0);
+        } else {
+            self.position = self.interpolated_position();
+            if let Some((_, target)) = self.snapshots.back() {
+                let debug = store.get_store::<Debug<WorldLayer>>();
+                let mut debug = debug.renderer();
+                debug.rect(*target, Vector2::new(PLAYER_SIZE, PLAYER_SIZE), 0.1, [1.0, 0.0, 0.0, 1.0]);
+            }
         }
-        
+
+        let quad = &mut *self.quad;
+        quad.set_position(self.position);
         (*store.mut_store::<LightStore>()).update(&self.light_id, Light::new(self.position + Vector2::new(PLAYER_SIZE / 2.0, PLAYER_SIZE / 2.0), 4.0, Vector3::new(0.3, 1.0, 0.3)));
         let _ = renderer.draw(quad);
     }
@@ -1,6 +1,10 @@
 use aeonetica_engine::register;
 
-mod client;
+#[cfg(feature = "window")]
+pub mod client;
+pub mod messages;
+pub mod movement;
+#[cfg(feature = "server-lib")]
 pub mod server;
 
 register!(client::PlayerModClient{}, server::PlayerModServer{});
\ No newline at end of file
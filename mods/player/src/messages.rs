@@ -0,0 +1,28 @@
+use aeonetica_engine::{impl_snapshot, rpc};
+use aeonetica_engine::math::vector::Vector2;
+use aeonetica_engine::nanoserde::{self, DeBin, SerBin};
+use aeonetica_engine::networking::messaging::ClientEntity;
+use crate::movement::{MovementState, PlayerInput};
+
+/// The client handle type of players. Clients without the client half of the mod,
+/// like bots, register their own handle for it.
+pub struct PlayerHandleType;
+
+impl ClientEntity for PlayerHandleType {}
+
+// server -> client
+rpc!(pub SetControlling: bool);
+// sequence number of the last applied input and the resulting state
+rpc!(pub ReceiveState: (u32, MovementState));
+
+// client -> server
+// the latest unacknowledged inputs
+rpc!(pub SendInputs: Vec<PlayerInput>);
+
+/// Replicated state of a player, a full snapshot places the player without interpolating.
+#[derive(SerBin, DeBin)]
+pub struct PlayerState {
+    pub position: Vector2<f32>
+}
+
+impl_snapshot!(PlayerState { position });
@@ -0,0 +1,87 @@
+use std::ops::Mul;
+use aeonetica_engine::nanoserde::{self, SerBin, DeBin};
+use aeonetica_engine::math::vector::Vector2;
+use world_mod::common::{GRAVITY, WorldView};
+
+pub const PLAYER_SIZE: f32 = 0.9;
+const SPEED: f32 = 10.0;
+const HOVER_FORCE: f32 = 12.0;
+/// Inputs cover at most one client frame, which is capped to 0.05s.
+pub const MAX_INPUT_DELTA: f32 = 0.05;
+
+/// The keys held during one client frame.
+#[derive(SerBin, DeBin, Clone, Copy, Debug, Default, PartialEq)]
+pub struct PlayerInput {
+    pub sequence: u32,
+    pub left: bool,
+    pub right: bool,
+    pub hover: bool,
+    pub delta: f32
+}
+
+/// Everything that is needed to simulate a player.
+/// Client and server run the same [`MovementState::step`],
+/// so the client can predict its own movement and the server stays authoritative.
+#[derive(SerBin, DeBin, Clone, Copy, Debug, PartialEq)]
+pub struct MovementState {
+    pub position: Vector2<f32>,
+    pub velocity: Vector2<f32>,
+    pub hover_energy: f32,
+    pub is_grounded: bool
+}
+
+impl MovementState {
+    pub fn new(position: Vector2<f32>) -> Self {
+        Self {
+            position,
+            velocity: Default::default(),
+            hover_energy: 1.0,
+            is_grounded: false
+        }
+    }
+
+    /// Returns the falling speed if the player hit the ground hard.
+    pub fn step<W: WorldView>(&mut self, input: &PlayerInput, world: &W) -> Option<f32> {
+        let delta = input.delta.clamp(0.0, MAX_INPUT_DELTA);
+        self.velocity.y -= GRAVITY * delta;
+        if input.hover {
+            if self.hover_energy > 0.0 {
+                self.velocity.y = (self.velocity.y * (1.0 - delta * 10.0)) - HOVER_FORCE * delta * 10.0;
+            }
+            self.hover_energy = (self.hover_energy - 0.75 * delta).max(0.0);
+        } else {
+            self.hover_energy = (self.hover_energy + if self.is_grounded { 1.0 } else { 0.125 } * delta).min(1.0);
+        }
+
+        self.velocity.y -= self.velocity.y.abs().mul(0.25).max(0.025).mul(delta).min(self.velocity.y.abs()).copysign(self.velocity.x);
+        self.velocity.x -= self.velocity.x.abs().mul(0.25).max(0.025).mul(delta).min(self.velocity.x.abs()).copysign(self.velocity.x);
+        let v = if self.velocity.x.abs() < 0.05 {
+            self.velocity.x = 0.0;
+            self.velocity + Vector2::new(match (input.left, input.right) {
+                (true, false) => -SPEED,
+                (false, true) => SPEED,
+                _ => 0.0
+            }, 0.0)
+        } else { self.velocity };
+
+        let mut impact = None;
+        if v.mag_sq() > 0.0 {
+            let p = self.position;
+            world.calc_move(&mut self.position, Vector2::new(PLAYER_SIZE, PLAYER_SIZE), v * delta);
+            let moved = self.position - p;
+            if moved.x.abs() < 0.01 * delta {
+                self.velocity.x = 0.0;
+            }
+            if moved.y.abs() < 0.01 * delta {
+                if self.velocity.y > 16.0 {
+                    impact = Some(self.velocity.y);
+                }
+                self.velocity.y = 0.0;
+                self.is_grounded = world.overlap_aabb(self.position + Vector2::new(0.0, 0.02), Vector2::new(PLAYER_SIZE, PLAYER_SIZE));
+            } else {
+                self.is_grounded = false;
+            }
+        }
+        impact
+    }
+}
@@ -1,5 +1,4 @@
-use std::cell::RefCell;
-use std::rc::Rc;
+use std::time::Instant;
 use aeonetica_engine::{ClientId, EntityId, log};
 use aeonetica_engine::networking::SendMode;
 use aeonetica_engine::util::id_map::IdMap;
@@ -7,11 +6,17 @@ use aeonetica_engine::math::vector::Vector2;
 use aeonetica_server::ecs::Engine;
 use aeonetica_server::ecs::events::ConnectionListener;
 use aeonetica_server::ecs::messaging::Messenger;
+use aeonetica_server::ecs::interest::{Interest, Viewer};
 use aeonetica_server::ecs::module::Module;
+use aeonetica_server::ecs::replication::{Replicated, Replication};
+use aeonetica_server::ecs::validation::{check_range, Validation, Violation, ViolationPolicies, ViolationPolicy};
 use aeonetica_server::ServerMod;
-use crate::client::PlayerHandle;
+use world_mod::server::world::{World, WorldEntity};
+use crate::messages::{PlayerHandleType, PlayerState, ReceiveState, SendInputs, SetControlling};
+use crate::movement::{MAX_INPUT_DELTA, MovementState, PLAYER_SIZE, PlayerInput};
 
-pub const PLAYER_HANDLER: &str = "PLAYER_HANDLER";
+/// Players further apart than this do not see each other.
+const INTEREST_RADIUS: f32 = 32.0;
 
 pub struct PlayerModServer {
 
@@ -20,63 +25,40 @@ pub struct PlayerModServer {
 impl ServerMod for PlayerModServer {
     fn start(&mut self, engine: &mut Engine) {
         log!("starting player mod server...");
+        engine.provide_service::<dyn Players>(Box::<PlayerHandler>::default());
         let eid = engine.new_entity();
-        engine.tag_entity(eid, PLAYER_HANDLER);
         let handler = engine.mut_entity(&eid).unwrap();
-        handler.add_module(PlayerHandler { players: Default::default() });
         handler.add_module(ConnectionListener::new(
-            |id, engine, client| {
+            |_id, engine, client| {
                 let pid = engine.new_entity();
+                // the player gets removed once the client leaves
+                engine.tie_to_client(&pid, Some(*client));
                 {
                     // creating self player
                     let mut player = engine.mut_entity(&pid);
-                    player.add_module(Messenger::new::<PlayerHandle>());
-                    player.add_module(Player { position: Vector2::new(3.0, 0.0) });
+                    player.add_module(Messenger::new::<PlayerHandleType>());
+                    player.add_module(Player::new(*client, Vector2::new(3.0, 0.0)));
+                    player.add_module(Replication::<Player>::new());
+                    player.add_module(Viewer::new(*client, Vector2::new(3.0, 0.0)));
+                    player.add_module(Interest::new(Vector2::new(3.0, 0.0), INTEREST_RADIUS));
                 }
-                let prcrc = engine.get_module_of::<PlayerHandler>(id).players.clone();
-                let mut players = prcrc.borrow_mut();
                 // adding player to list of players
-                players.insert(*client, pid);
-                let players_positions = players.iter().map(|(k, v)| {
-                    (k, v, engine.mut_module_of::<Player>(v).position)
-                }).collect::<Vec<_>>();
-                {
-                    let mut player = engine.mut_entity(&pid);
-                    let position = player.mut_module::<Player>().position;
-
-                    let mut messenger = player.mut_module::<Messenger>();
-                    messenger.register_receiver(Player::client_position_update);
-
-                    // register this player for all players
-                    for (pid, ..) in &players_positions {
-                        messenger.add_client(**pid);
-                    }
-                    // tell this player that they may control themselves
-                    messenger.call_client_fn_for(PlayerHandle::set_controlling, client, true, SendMode::Safe);
-                    messenger.call_client_fn(PlayerHandle::receive_position, (position, true), SendMode::Safe);
-                }
-                // register all other players for this player
-                for (pid, eid, position) in &players_positions {
-                    if *pid == client { continue }
-                    let mut messenger = engine.mut_module_of::<Messenger>(eid);
-                    messenger.add_client(*client);
-                    messenger.call_client_fn_for(PlayerHandle::receive_position, client, (*position, true), SendMode::Safe);
+                if let Ok(players) = engine.service_mut::<dyn Players>() {
+                    players.set_player(*client, Some(pid));
                 }
+                let mut messenger = engine.mut_module_of::<Messenger>(&pid);
+                messenger.on_validated::<SendInputs>(Player::client_input, Player::validate_inputs);
+                // the own client always sees this player, other players are added and removed
+                // by the interest system and get its position through replication
+                messenger.add_client(*client);
+                // tell this player that they may control themselves
+                messenger.send_to::<SetControlling>(client, true, SendMode::Safe);
                 log!("set up client ons server side");
-            }, |id, engine, client| {
-                // get list of players and entity id of client player
-                let prcrc = engine.get_module_of::<PlayerHandler>(id).players.clone();
-                let mut players = prcrc.borrow_mut();
-                let eid = players.remove(client).unwrap();
-				// unregister this player for all other players
-                let messenger: &mut Messenger = &mut engine.mut_module_of(&eid);
-				for (pid, _eid) in players.iter() {
-                    messenger.remove_client(pid);
-                }
-                // unregister all other players from this player
-                for (pid, eid) in players.iter() {
-                    if pid == client { continue }
-                    engine.mut_module_of::<Messenger>(eid).remove_client(client);
+            }, |_id, engine, client| {
+                // the player is removed together with the client,
+                // which the interest system then removes from the other players
+                if let Ok(players) = engine.service_mut::<dyn Players>() {
+                    players.set_player(*client, None);
                 }
                 log!("removed client ons server side");
         }));
@@ -84,24 +66,151 @@ impl ServerMod for PlayerModServer {
     }
 }
 
+/// Service of the player mod, other mods look players up through it
+/// instead of depending on how the player mod stores them:
+/// `engine.service::<dyn Players>()?.player_of(client)`
+pub trait Players {
+    /// The player entity of `client`, if it joined.
+    fn player_of(&self, client: &ClientId) -> Option<EntityId>;
+    /// The entities of all players.
+    fn players(&self) -> Vec<EntityId>;
+    /// Called by the player mod when `client` joins or leaves.
+    fn set_player(&mut self, client: ClientId, player: Option<EntityId>);
+}
+
+/// Provides [`Players`].
+#[derive(Default)]
 pub struct PlayerHandler {
     /// key: client_id, value: entity_id
-    pub players: Rc<RefCell<IdMap<EntityId>>>
+    pub players: IdMap<EntityId>
 }
 
-impl Module for PlayerHandler {
+impl Players for PlayerHandler {
+    fn player_of(&self, client: &ClientId) -> Option<EntityId> {
+        self.players.get(client).copied()
+    }
 
+    fn players(&self) -> Vec<EntityId> {
+        self.players.values().copied().collect()
+    }
+
+    fn set_player(&mut self, client: ClientId, player: Option<EntityId>) {
+        match player {
+            Some(player) => { self.players.insert(client, player); }
+            None => { self.players.remove(&client); }
+        }
+    }
 }
 
 pub struct Player {
-    pub position: Vector2<f32>
+    /// authoritative position, simulated from the inputs of the client
+    pub position: Vector2<f32>,
+    client: ClientId,
+    movement: MovementState,
+    /// sequence number of the last applied input
+    last_input: u32,
+    /// seconds of movement the client may still send, refilled with real time
+    input_budget: f32,
+    last_input_at: Instant
 }
 
+/// Most inputs a client sends at once, see `RESENT_INPUTS` on the client.
+const MAX_INPUTS_PER_MESSAGE: usize = 16;
+/// Allows inputs to arrive in bursts after lag, but not to be sent faster than real time.
+const MAX_INPUT_BUDGET: f32 = 0.5;
+const INPUT_BUDGET_TOLERANCE: f32 = 0.01;
+
 impl Player {
-    pub(crate) fn client_position_update(id: &EntityId, engine: &mut Engine, _client_id: &ClientId, (position, teleporting): (Vector2<f32>, bool)) {
-        let mut player = engine.mut_entity(id);
-        player.mut_module::<Player>().position = position;
-        player.mut_module::<Messenger>().call_client_fn(PlayerHandle::receive_position, (position, teleporting), SendMode::Safe);
+    fn new(client: ClientId, position: Vector2<f32>) -> Self {
+        Self {
+            position,
+            client,
+            movement: MovementState::new(position),
+            last_input: 0,
+            input_budget: MAX_INPUT_BUDGET,
+            last_input_at: Instant::now()
+        }
+    }
+
+    fn validate_inputs(id: &EntityId, engine: &Engine, client_id: &ClientId, inputs: &Vec<PlayerInput>) -> Validation {
+        let player = engine.get_module_of::<Player>(id);
+        if player.client != *client_id {
+            return Err(Violation::new("player_owner", format!("tried to move the player of {}", player.client)))
+        }
+        if inputs.len() > MAX_INPUTS_PER_MESSAGE {
+            return Err(Violation::new("player_inputs", format!("sent {} inputs at once", inputs.len())))
+        }
+        for input in inputs {
+            check_range("player_input_delta", input.delta, 0.0, MAX_INPUT_DELTA)?;
+        }
+        Ok(())
+    }
+
+    /// The client resends its unacknowledged inputs with every message, since they are sent quick.
+    ///
+    /// Inputs that fail validation are acknowledged without being applied,
+    /// so the client snaps back to the server's state (rubber-banding).
+    pub(crate) fn client_input(id: &EntityId, engine: &mut Engine, client_id: &ClientId, inputs: Vec<PlayerInput>) {
+        let policy = |engine: &Engine, check: &str| engine.get_resource::<ViolationPolicies>().map_or(ViolationPolicy::RubberBand, |p| p.get(check));
+        let rate_policy = policy(engine, "player_input_rate");
+        let collision_policy = policy(engine, "player_collision");
+
+        let Some(&WorldEntity(wid)) = engine.get_resource::<WorldEntity>().option() else { return };
+        let Some((player, world)) = engine.query_two::<&mut Player, &World>(id, &wid) else { return };
+        let now = Instant::now();
+        player.input_budget = (player.input_budget + (now - player.last_input_at).as_secs_f32()).min(MAX_INPUT_BUDGET);
+        player.last_input_at = now;
+
+        let mut violations = vec![];
+        let mut acknowledged = false;
+        for input in inputs.iter().filter(|i| i.sequence > player.last_input) {
+            player.last_input = input.sequence;
+            acknowledged = true;
+            if input.delta > player.input_budget + INPUT_BUDGET_TOLERANCE {
+                violations.push(Violation::new("player_input_rate", format!("sent {}s of movement with {}s left", input.delta, player.input_budget)));
+                if rate_policy != ViolationPolicy::Log {
+                    continue
+                }
+            }
+            player.input_budget = (player.input_budget - input.delta).max(0.0);
+
+            let previous = player.movement;
+            player.movement.step(input, world);
+            if world.overlap_aabb(player.movement.position, Vector2::new(PLAYER_SIZE, PLAYER_SIZE)) {
+                violations.push(Violation::new("player_collision", format!("moved into a solid tile at {:?}", player.movement.position)));
+                if collision_policy != ViolationPolicy::Log {
+                    player.movement = previous;
+                }
+            }
+        }
+        if !acknowledged {
+            return
+        }
+        player.position = player.movement.position;
+        let (position, state, sequence, client) = (player.position, player.movement, player.last_input, player.client);
+
+        for violation in violations {
+            if engine.report_violation(client_id, violation) == ViolationPolicy::Kick {
+                // the client and its player get removed at the next sync point
+                return
+            }
+        }
+
+        let mut entity = engine.mut_entity(id);
+        entity.mut_module::<Viewer>().position = position;
+        entity.mut_module::<Interest>().position = position;
+        // the other clients get the new position through replication
+        entity.mut_module::<Messenger>().send_to::<ReceiveState>(&client, (sequence, state), SendMode::Quick);
+    }
+}
+
+impl Replicated for Player {
+    type Snapshot = PlayerState;
+
+    fn snapshot(&self) -> PlayerState {
+        PlayerState {
+            position: self.position
+        }
     }
 }
 
@@ -1,3 +1,4 @@
+use std::time::Duration;
 use aeonetica_engine::{EntityId, Id, log};
 use aeonetica_engine::networking::SendMode;
 use aeonetica_server::ecs::module::Module;
@@ -41,9 +42,9 @@ impl Module for MyModule {
                 messenger.call_client_fn(MyClientHandle::receive_server_msg, format!("user left: {user}"), SendMode::Safe);
             }));
         log!("registered client loginout listener");
-        engine.queue_task( |mut e: &mut Engine| {
+        engine.queue_entity_task(id, |mut e: &mut Engine| {
             for i in 1..11 {
-                yield_task!(e, WaitFor::ticks(20));
+                yield_task!(e, WaitFor::duration(Duration::from_secs(1)));
                 log!("waited {i} seconds...")
             }
         });
@@ -11,7 +11,7 @@ use aeonetica_engine::{log, TypeId};
 use aeonetica_engine::math::camera::Camera;
 use aeonetica_engine::math::vector::*;
 use aeonetica_engine::networking::messaging::ClientEntity;
-use aeonetica_engine::networking::SendMode;
+use aeonetica_engine::networking::rpc::DEFAULT_REQUEST_TIMEOUT;
 use aeonetica_engine::util::id_map::IdMap;
 use aeonetica_engine::util::nullable::Nullable;
 use aeonetica_engine::util::type_to_id;
@@ -22,7 +22,7 @@ use crate::client::pipeline::WorldRenderPipeline;
 use crate::client::materials::{WithGlow, WithTerrain};
 
 use crate::common::{Chunk, CHUNK_SIZE, WorldView};
-use crate::server::world::World;
+use crate::messages::{RequestChunk, WorldHandleType};
 use crate::tiles::{Tile, FgTile};
 
 use debug_mod::Debug;
@@ -67,7 +67,7 @@ impl ClientMod for WorldModClient {
 
     fn register_handlers(&self, handlers: &mut IdMap<fn() -> Box<dyn ClientHandle>>, _store: &mut DataStore) {
         log!("handles registered");
-        handlers.insert(type_to_id::<WorldHandle>(), || Box::new(WorldHandle::new()));
+        handlers.insert(type_to_id::<WorldHandleType>(), || Box::new(WorldHandle::new()));
     }
 
     fn start<'a>(&self, store: &mut DataStore, provider: OpenGlRenderContextProvider<'a>) -> &'a mut RenderContext {
@@ -268,10 +268,6 @@ impl Block {
 }
 
 impl ClientHandle for WorldHandle {
-    fn start(&mut self, messenger: &mut ClientMessenger, _renderer: Nullable<&mut Renderer>, _store: &mut DataStore) {
-        messenger.register_receiver(Self::receive_chunk_data);
-    }
-
     fn owning_layer(&self) -> TypeId {
         type_to_id::<WorldLayer>()
     }
@@ -286,7 +282,14 @@ impl ClientHandle for WorldHandle {
             for y in (center_chunk.y-1)..=(center_chunk.y+1) {
                 let k = Vector2::from((x, y));
                 chunks.entry(k).or_insert_with(|| {
-                    messenger.call_server_fn(World::request_world_chunk, k, SendMode::Safe);
+                    messenger.request::<RequestChunk, Self>(k, DEFAULT_REQUEST_TIMEOUT, move |handle, messenger, renderer, store, chunk| match chunk {
+                        Ok(chunk) => handle.receive_chunk_data(messenger, renderer, store, chunk),
+                        Err(e) => {
+                            log!(WARN, "could not load chunk {k}: {e}");
+                            // requested again on the next update
+                            store.mut_store::<ClientWorld>().chunks.remove(&k);
+                        }
+                    });
                     ClientChunk::Requested
                 });
             }
@@ -1,8 +1,11 @@
 use aeonetica_engine::register;
 
+#[cfg(feature = "window")]
 pub mod client;
+#[cfg(feature = "server-lib")]
 pub mod server;
 pub mod common;
+pub mod messages;
 pub mod tiles;
 
 register!(client::WorldModClient{}, server::WorldModServer::new());
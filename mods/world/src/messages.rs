@@ -0,0 +1,13 @@
+use aeonetica_engine::math::vector::Vector2;
+use aeonetica_engine::request;
+use aeonetica_engine::networking::messaging::ClientEntity;
+use crate::common::Chunk;
+
+/// The client handle type of the world, so the server half does not need the client half.
+pub struct WorldHandleType;
+
+impl ClientEntity for WorldHandleType {}
+
+// client -> server
+// the chunk at the given chunk position, generated if needed
+request!(pub RequestChunk: Vector2<i32> => Chunk);
@@ -12,7 +12,7 @@ pub struct WorldModServer {
 }
 
 impl WorldModServer {
-    pub(crate) fn new() -> Self {
+    pub fn new() -> Self {
         Self {
             seed: 0
         }
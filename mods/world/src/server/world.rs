@@ -2,7 +2,6 @@
 
 use std::rc::Rc;
 use aeonetica_engine::{ClientId, EntityId, log};
-use aeonetica_engine::networking::SendMode;
 use aeonetica_engine::math::vector::Vector2;
 use aeonetica_engine::util::id_map::{IdSet};
 use aeonetica_engine::util::nullable::Nullable;
@@ -11,13 +10,17 @@ use aeonetica_server::ecs::entity::Entity;
 use aeonetica_server::ecs::events::ConnectionListener;
 use aeonetica_server::ecs::messaging::Messenger;
 use aeonetica_server::ecs::module::Module;
-use crate::client::WorldHandle;
 use crate::common::{Chunk, Population, WorldView};
+use crate::messages::{RequestChunk, WorldHandleType};
 use crate::server::gen::GenProvider;
 use crate::tiles::{Tile, FgTile};
 
 pub const WORLD: &str = "WORLD";
 
+/// Resource of the world mod, the entity holding the [`World`] module.
+#[derive(Copy, Clone)]
+pub struct WorldEntity(pub EntityId);
+
 pub(crate) struct ChunkHolder {
     further_x: Option<Box<ChunkHolder>>,
     further_y: Option<Box<ChunkHolder>>,
@@ -50,9 +53,10 @@ impl World {
     pub(crate) fn new_wold_entity(engine: &mut Engine, seed: u64) -> EntityId {
         let eid = engine.new_entity();
         engine.tag_entity(eid, WORLD);
+        engine.add_resource(WorldEntity(eid));
         let entity: &mut Entity = &mut engine.mut_entity(&eid);
-        entity.add_module(Messenger::new::<WorldHandle>());
-        entity.mut_module::<Messenger>().register_receiver(World::request_world_chunk);
+        entity.add_module(Messenger::new::<WorldHandleType>());
+        entity.mut_module::<Messenger>().on_request::<RequestChunk>(World::request_world_chunk);
 
         entity.add_module(ConnectionListener::new(
             |id, engine, client| {
@@ -151,9 +155,8 @@ impl World {
         self.mut_chunk_at(chunk_pos)
     }
 
-    pub(crate) fn request_world_chunk(id: &EntityId, engine: &mut Engine, client: &ClientId, chunk_pos: Vector2<i32>) {
-        let chunk = engine.mut_module_of::<Self>(id).get_chunk_at(chunk_pos).clone();
-        engine.mut_module_of::<Messenger>(id).call_client_fn_for(WorldHandle::receive_chunk_data, client, chunk, SendMode::Safe);
+    pub(crate) fn request_world_chunk(id: &EntityId, engine: &mut Engine, _client: &ClientId, chunk_pos: Vector2<i32>) -> Chunk {
+        engine.mut_module_of::<Self>(id).get_chunk_at(chunk_pos).clone()
     }
 
     pub fn try_get_tile_no_gen(&self, pos: Vector2<i32>) -> Nullable<Tile> {
@@ -6,7 +6,7 @@ use debug_mod::Debug;
 use world_mod::client::{WorldLayer, materials::terrain_material};
 use world_mod::client::materials::WithTerrain;
 
-use crate::server::{WORM_SPEED};
+use crate::server::{WORM_SPEED, WormState};
 
 
 pub struct WormsModClient {
@@ -55,7 +55,9 @@ impl WormHandle {
         })
     }
 
-    pub(crate) fn receive_position(&mut self, _messenger: &mut ClientMessenger, mut renderer: Nullable<&mut Renderer>, store: &mut DataStore, (segments, looking_dir, teleporting): (Vec<Vector2<f32>>, Vector2<f32>, bool)) {
+    pub(crate) fn receive_state(&mut self, _messenger: &mut ClientMessenger, mut renderer: Nullable<&mut Renderer>, store: &mut DataStore, state: &WormState, teleporting: bool) {
+        let segments = state.segments.clone();
+        let looking_dir = state.looking_dir;
         if self.segments.is_empty() {
             let material = terrain_material(store);
             let sheet = store.get_or_create(WormSheet::load);
@@ -103,7 +105,7 @@ impl ClientHandle for WormHandle {
     }
 
     fn start(&mut self, messenger: &mut ClientMessenger, mut renderer: Nullable<&mut Renderer>, _store: &mut DataStore) {
-        messenger.register_receiver(WormHandle::receive_position);
+        messenger.register_replicated(WormHandle::receive_state);
         let pos = Vector2::new(2.0, 2.0);
         let size = Vector2::new(2.0, 2.0);
         //renderer.add(&mut Line::new(pos, pos + (size.x, 0.0).into(), 0.2,  255, [1.0, 0.0, 0.0, 1.0]));
@@ -4,5 +4,7 @@ use aeonetica_engine::register;
 
 pub(crate) mod client;
 pub(crate) mod server;
+#[cfg(test)]
+mod tests;
 
 register!(client::WormsModClient::new(), server::WormsModServer::new());
\ No newline at end of file
@@ -1,7 +1,8 @@
-use aeonetica_engine::{time::Time, math::vector::Vector2, EntityId, networking::SendMode, util::nullable::Nullable};
-use aeonetica_server::{ServerMod, ecs::{module::Module, Engine, messaging::Messenger}};
-use player_mod::server::{PLAYER_HANDLER, PlayerHandler, Player};
-use world_mod::{server::world::{WORLD, World}, common::WorldView};
+use aeonetica_engine::{time::Time, math::vector::Vector2, EntityId, impl_snapshot};
+use aeonetica_engine::nanoserde::{self, SerBin, DeBin};
+use aeonetica_server::{ServerMod, ecs::{module::Module, Engine, messaging::Messenger, replication::{Replicated, Replication}, interest::Interest}};
+use player_mod::server::{Player, Players};
+use world_mod::{server::world::{World, WorldEntity}, common::WorldView};
 use crate::client::WormHandle;
 
 
@@ -25,7 +26,6 @@ const SEG_LEN: f32 = 0.8;
 pub(crate) const WORM_SPEED: f32 = 5.0;
 
 pub(crate) struct Worm {
-    ppos: Vector2<f32>,
     looking_dir: Vector2<f32>,
     segments: Vec<Vector2<f32>>,
     attack_cooldown: f32
@@ -38,12 +38,13 @@ impl Worm {
         let mut entity = engine.mut_entity(&eid);
         entity.add_module(Worm::new(Vector2::new(-15.0, 0.0), Vector2::new(1.0,  0.0), 10));
         entity.add_module(Messenger::new::<WormHandle>());
+        entity.add_module(Interest::new(Vector2::new(-15.0, 0.0), 24.0));
+        entity.add_module(Replication::<Worm>::new());
         eid
     }
 
     fn new(pos: Vector2<f32>, dir: Vector2<f32>, segs: usize) -> Self {
         Self {
-            ppos: Default::default(),
             looking_dir: Vector2::new(1.0, 0.0),
             segments: {
                 let dir = dir.normalized();
@@ -60,38 +61,26 @@ impl Worm {
 
 impl Module for Worm {
     fn tick(id: &EntityId, engine: &mut Engine, time: Time) {
-        let prcrc = engine.mut_module_by_tag::<PlayerHandler>(PLAYER_HANDLER).players.clone();
-        let players = prcrc.borrow_mut();
+        let players = engine.service::<dyn Players>().map(|p| p.players()).unwrap_or_default();
         let mut worm = engine.mut_module_of::<Worm>(id);
         let mut self_pos = worm.segments[0];
-        let ppos = worm.ppos;
         let mut target = None;
         let mut pdsq = f32::MAX;
         if worm.attack_cooldown > 0.0 {
             worm.attack_cooldown -= time.delta;
         } else {
-            for (pid, epid) in players.iter() {
+            for epid in players.iter() {
                 let pos = engine.get_module_of::<Player>(epid).position;
                 let dsq = (pos - self_pos).mag_sq();
-                if dsq > 24.0*24.0 {
-                    engine.mut_module_of::<Messenger>(id).remove_client(pid);
-
-                } else {
-                    if dsq < pdsq {
-                        target = Some(pos);
-                        pdsq = dsq;
-                    }
-                    if engine.mut_module_of::<Messenger>(id).add_client(*pid) {
-                        println!("ADDDD");
-                        let (mut messenger, worm) = engine.two_mut_modules_of::<Messenger, Worm>(id);
-                        messenger.call_client_fn(WormHandle::receive_position, (worm.segments.clone(), worm.looking_dir, true), SendMode::Safe);
-                    }
+                if dsq <= 24.0*24.0 && dsq < pdsq {
+                    target = Some(pos);
+                    pdsq = dsq;
                 }
             }
         }
 
-        let wid = **engine.get_entity_id_by_tag(WORLD);
-        let (mut worm, world): (Nullable<&mut Worm>, Nullable<&mut World>) = engine.many_mut_modules_of_entities::<2, (Worm, World)>([*id, wid]);
+        let Some(&WorldEntity(wid)) = engine.get_resource::<WorldEntity>().option() else { return };
+        let (worm, world) = engine.query_two::<&mut Worm, &mut World>(id, &wid).unwrap();
 
         if pdsq < 0.5 {
             worm.attack_cooldown = 0.3;
@@ -123,11 +112,25 @@ impl Module for Worm {
                 worm.attack_cooldown = 0.4;
             }
         }
+        engine.mut_module_of::<Interest>(id).position = self_pos;
+    }
+}
+
+#[derive(SerBin, DeBin)]
+pub(crate) struct WormState {
+    pub(crate) segments: Vec<Vector2<f32>>,
+    pub(crate) looking_dir: Vector2<f32>
+}
+
+impl_snapshot!(WormState { segments, looking_dir });
+
+impl Replicated for Worm {
+    type Snapshot = WormState;
 
-        if (ppos - self_pos).mag_sq() > 0.05 {
-            let (mut messenger, mut worm) = engine.two_mut_modules_of::<Messenger, Worm>(id);
-            worm.ppos = self_pos;
-            messenger.call_client_fn(WormHandle::receive_position, (worm.segments.clone(), worm.looking_dir, false), SendMode::Safe);
+    fn snapshot(&self) -> WormState {
+        WormState {
+            segments: self.segments.clone(),
+            looking_dir: self.looking_dir
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,75 @@
+use aeonetica_engine::ClientId;
+use aeonetica_engine::networking::client_packets::ClientMessage;
+use aeonetica_engine::networking::memory::{memory_transport, MemoryConnector};
+use aeonetica_engine::networking::server_packets::ServerMessage;
+use aeonetica_engine::time::Time;
+use aeonetica_engine::util::type_to_id;
+use aeonetica_server::{ServerMod, ServerModBox};
+use aeonetica_server::ecs::Engine;
+use aeonetica_server::ecs::messaging::Messenger;
+use aeonetica_server::server::start_linked;
+use aeonetica_server::test_util::SimulatedClient;
+use player_mod::server::PlayerModServer;
+use world_mod::server::WorldModServer;
+use crate::client::WormHandle;
+use crate::server::{Worm, WormsModServer};
+
+const TICK: Time = Time { time: 0.0, delta: 0.05, raw_delta: 0.05 };
+
+/// The worm mod and the mods it depends on, with a fixed world seed.
+fn start_server() -> (Box<Engine>, MemoryConnector) {
+    let mut world: Box<dyn ServerMod> = Box::new(WorldModServer::new());
+    world.init(&vec!["42".to_string()]);
+    let mods = vec![
+        ServerModBox::without_library("world:world", world),
+        ServerModBox::without_library("player:player", Box::new(PlayerModServer {})),
+        ServerModBox::without_library("worms:worms", Box::new(WormsModServer::new()))
+    ];
+    let (transport, connector) = memory_transport();
+    (start_linked(mods, transport), connector)
+}
+
+fn worm_receivers(engine: &mut Engine) -> Vec<ClientId> {
+    engine.query::<(&Worm, &Messenger)>()
+        .flat_map(|(_, messenger)| messenger.clients().copied().collect::<Vec<_>>())
+        .collect()
+}
+
+#[test]
+fn worm_adds_and_removes_clients() {
+    let (mut engine, connector) = start_server();
+    let mut clients = (0..3).map(|_| SimulatedClient::join(&connector)).collect::<Vec<_>>();
+    engine.run_tick(TICK);
+    engine.run_tick(TICK);
+
+    // players spawn within the interest radius of the worm
+    let receivers = worm_receivers(&mut engine);
+    assert_eq!(receivers.len(), clients.len());
+    for client in &mut clients {
+        assert!(receivers.contains(&client.id));
+        assert!(client.received().iter().any(|m| matches!(m, ServerMessage::AddClientHandle(_, handle_type) if *handle_type == type_to_id::<WormHandle>())));
+    }
+
+    clients[0].send(ClientMessage::Logout);
+    engine.run_tick(TICK);
+    engine.run_tick(TICK);
+
+    let receivers = worm_receivers(&mut engine);
+    assert!(!receivers.contains(&clients[0].id));
+    assert!(receivers.contains(&clients[1].id) && receivers.contains(&clients[2].id));
+}
+
+/// World, player and debug are linked into this test as well. If they exported
+/// entries of their own, linking would fail with duplicate symbols.
+/// Run with `--features server` or `--features client` to build the entry of this mod.
+#[test]
+fn only_the_mod_itself_exports_an_entry() {
+    let _ = (WorldModServer::new(), PlayerModServer {});
+    #[cfg(any(feature = "client", feature = "server"))]
+    {
+        let entry = unsafe { &*crate::_aeonetica_mod_entry() };
+        assert_eq!(entry.abi_version, aeonetica_engine::abi::MOD_ABI_VERSION);
+        assert_eq!(entry.create_client.is_some(), cfg!(feature = "client"));
+        assert_eq!(entry.create_server.is_some(), cfg!(feature = "server"));
+    }
+}
@@ -0,0 +1,64 @@
+use std::path::PathBuf;
+use aeonetica_engine::error::{Error, ErrorResult, Fatality};
+use aeonetica_engine::error::builtin::ValueError;
+use aeonetica_engine::log;
+use crate::pack::{keygen, PackOptions};
+
+mod pack;
+#[cfg(test)]
+mod tests;
+
+const HELP: &str = "Usage:
+    aeonetica-pack build <mod dir> [options]    builds and packages a mod
+    aeonetica-pack keygen <key file>            generates a key to sign mods with
+    aeonetica-pack --help
+
+Options:
+    -r, --release           build in release mode
+    -t, --targets <t1,t2>   mod targets the package has to contain (default: the host target)
+    -p, --profile <file>    take the mod targets from the `mod_targets` of a mods.ron
+    -s, --sign <key file>   sign the client zips with the key
+    -d, --deploy <dir>      copy the package to <dir>/<mod name>.zip
+    -o, --output <file>     copy the package to <file>
+    -z, --only-zip          do not recompile, only package
+    -w, --wasm              build a wasm mod that runs on every target, ignores -t and -p
+
+Client zips for targets other than the host have to be built beforehand
+and placed in target/<mode>/ of the workspace as <name>_client-<target>.zip";
+
+fn main() {
+    aeonetica_engine::enable_ansi_support::enable_ansi_support().unwrap_or_else(|_| eprintln!("ansi not supported in this console"));
+    let args: Vec<_> = std::env::args().skip(1).collect();
+    let result = match args.as_slice() {
+        [cmd, key_file] if cmd == "keygen" => keygen(key_file),
+        [cmd, mod_dir, options @ ..] if cmd == "build" => parse_options(mod_dir, options).and_then(|o| o.pack()),
+        _ => {
+            log!("{HELP}");
+            return
+        }
+    };
+    if let Err(e) = result {
+        e.log_exit()
+    }
+}
+
+fn parse_options(mod_dir: &str, args: &[String]) -> ErrorResult<PackOptions> {
+    let mut options = PackOptions::new(PathBuf::from(mod_dir));
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().cloned()
+            .ok_or_else(|| Error::new(ValueError(format!("missing value for {arg}")), Fatality::FATAL, false));
+        match arg.as_str() {
+            "-r" | "--release" => options.release = true,
+            "-t" | "--targets" => options.targets = Some(value()?.split(',').map(|t| t.trim().to_string()).collect()),
+            "-p" | "--profile" => options.profile = Some(PathBuf::from(value()?)),
+            "-s" | "--sign" => options.signing_key = Some(PathBuf::from(value()?)),
+            "-d" | "--deploy" => options.deploy = Some(PathBuf::from(value()?)),
+            "-o" | "--output" => options.output = Some(PathBuf::from(value()?)),
+            "-z" | "--only-zip" => options.build = false,
+            "-w" | "--wasm" => options.wasm = true,
+            _ => return Err(Error::new(ValueError(format!("unknown argument {arg}; use `--help` for help")), Fatality::FATAL, false))
+        }
+    }
+    Ok(options)
+}
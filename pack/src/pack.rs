@@ -0,0 +1,283 @@
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{Cursor, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use aeonetica_engine::error::{Error, ErrorResult, Fatality};
+use aeonetica_engine::error::builtin::{IOError, ModError};
+use aeonetica_engine::ed25519_dalek::SigningKey;
+use aeonetica_engine::manifest::{MANIFEST_FILE, ModManifest};
+use aeonetica_engine::mod_layout::{client_lib, client_zip, server_lib, server_wasm, server_zip, WASM_MOD_TARGET};
+use aeonetica_engine::nanoserde::{self, DeRon};
+use aeonetica_engine::sha2::{Digest, Sha256};
+use aeonetica_engine::signing::{load_signing_key, ModSignature, signature_file, to_hex};
+use aeonetica_engine::{log, MOD_TARGET};
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+/// The parts of a server's `mods.ron` the packer cares about.
+#[derive(DeRon)]
+struct ModProfile {
+    #[allow(unused)]
+    profile: String,
+    #[allow(unused)]
+    version: String,
+    mod_targets: Option<Vec<String>>,
+    #[allow(unused)]
+    modstack: HashMap<String, Vec<String>>
+}
+
+pub(crate) struct PackOptions {
+    pub(crate) mod_dir: PathBuf,
+    pub(crate) release: bool,
+    pub(crate) build: bool,
+    pub(crate) targets: Option<Vec<String>>,
+    pub(crate) profile: Option<PathBuf>,
+    pub(crate) signing_key: Option<PathBuf>,
+    pub(crate) deploy: Option<PathBuf>,
+    pub(crate) output: Option<PathBuf>,
+    pub(crate) wasm: bool
+}
+
+/// The rust target wasm mods are built for.
+const WASM_RUST_TARGET: &str = "wasm32-unknown-unknown";
+
+impl PackOptions {
+    pub(crate) fn new(mod_dir: PathBuf) -> Self {
+        Self {
+            mod_dir,
+            release: false,
+            build: true,
+            targets: None,
+            profile: None,
+            signing_key: None,
+            deploy: None,
+            output: None,
+            wasm: false
+        }
+    }
+
+    fn mode(&self) -> &'static str {
+        if self.release { "release" } else { "debug" }
+    }
+
+    /// target/<mode> of the workspace, where cargo puts the library and the packer its zips
+    fn out_dir(&self) -> ErrorResult<PathBuf> {
+        if let Ok(dir) = std::env::var("CARGO_TARGET_DIR") {
+            return Ok(PathBuf::from(dir).join(self.mode()))
+        }
+        let output = Command::new("cargo")
+            .current_dir(&self.mod_dir)
+            .args(["locate-project", "--workspace", "--message-format", "plain"])
+            .output()?;
+        let manifest = PathBuf::from(String::from_utf8_lossy(&output.stdout).trim());
+        match manifest.parent() {
+            Some(root) if output.status.success() => Ok(root.join("target").join(self.mode())),
+            _ => Err(Error::new(IOError(format!("{} is not part of a cargo workspace", self.mod_dir.display())), Fatality::FATAL, false))
+        }
+    }
+
+    /// The mod target built on this machine.
+    fn build_target(&self) -> &'static str {
+        if self.wasm { WASM_MOD_TARGET } else { MOD_TARGET }
+    }
+
+    fn resolve_targets(&self) -> ErrorResult<Vec<String>> {
+        // one wasm client runs on every target
+        if self.wasm {
+            return Ok(vec![WASM_MOD_TARGET.to_string()])
+        }
+        if let Some(targets) = &self.targets {
+            return Ok(targets.clone())
+        }
+        if let Some(profile) = &self.profile {
+            let mut data = String::new();
+            File::open(profile)
+                .map_err(|e| Error::new(IOError(format!("could not open profile {}: {e}", profile.display())), Fatality::FATAL, false))?
+                .read_to_string(&mut data)?;
+            let profile: ModProfile = DeRon::deserialize_ron(&data)?;
+            if let Some(targets) = profile.mod_targets {
+                return Ok(targets)
+            }
+        }
+        Ok(vec![MOD_TARGET.to_string()])
+    }
+
+    /// Builds the mod and writes `target/<mode>/<name>.zip`.
+    pub(crate) fn pack(&self) -> ErrorResult<()> {
+        let name = self.mod_dir.canonicalize()?.file_name()
+            .and_then(|n| n.to_str()).map(str::to_string)
+            .ok_or_else(|| Error::new(IOError(format!("invalid mod directory {}", self.mod_dir.display())), Fatality::FATAL, false))?;
+        let manifest = ModManifest::load(self.mod_dir.join(MANIFEST_FILE))?;
+        if manifest.name != name {
+            return Err(Error::new(ModError(format!("mod directory {name} has manifest for mod {}", manifest.name)), Fatality::FATAL, false))
+        }
+        let signing_key = self.signing_key.as_ref().map(load_signing_key).transpose()?;
+
+        let targets = self.resolve_targets()?;
+        let out_dir = self.out_dir()?;
+        // fail before compiling anything
+        let missing: Vec<_> = targets.iter()
+            .filter(|t| *t != self.build_target() && !out_dir.join(client_zip(&name, t)).exists())
+            .collect();
+        if !missing.is_empty() {
+            return Err(Error::new(ModError(format!("missing client zips for targets {missing:?}, build them on those targets and place them in {}", out_dir.display())), Fatality::FATAL, false))
+        }
+
+        log!("packaging mod {name} v{} in {} mode for targets {targets:?}", manifest.version, self.mode());
+        let build_target = self.build_target();
+        if targets.iter().any(|t| t == build_target) {
+            let lib = self.build_feature(&out_dir, &name, "client", &client_lib(&name, build_target))?;
+            zip_files(&out_dir.join(client_zip(&name, build_target)), &[(client_lib(&name, build_target), lib)])?;
+        }
+        let server_file = if self.wasm { server_wasm(&name) } else { server_lib(&name) };
+        let lib = self.build_feature(&out_dir, &name, "server", &server_file)?;
+        zip_files(&out_dir.join(server_zip(&name)), &[(server_file.clone(), lib)])?;
+
+        let mut files = vec![
+            (MANIFEST_FILE.to_string(), std::fs::read(self.mod_dir.join(MANIFEST_FILE))?),
+            (server_zip(&name), std::fs::read(out_dir.join(server_zip(&name)))?)
+        ];
+        for target in &targets {
+            let zip = std::fs::read(out_dir.join(client_zip(&name, target)))?;
+            if let Some(key) = &signing_key {
+                let hash = format!("{:X}", Sha256::digest(&zip));
+                let signature = ModSignature::sign(&hash, key);
+                let file = out_dir.join(signature_file(&client_zip(&name, target)));
+                signature.save(&file)?;
+                files.push((signature_file(&client_zip(&name, target)), std::fs::read(file)?));
+            }
+            files.push((client_zip(&name, target), zip));
+        }
+        let package = out_dir.join(format!("{name}.zip"));
+        zip_files(&package, &files)?;
+        validate(&package, &name, &server_file, &targets)?;
+        log!("packaged {}", package.display());
+
+        if let Some(output) = &self.output {
+            std::fs::copy(&package, output)?;
+        }
+        if let Some(deploy) = &self.deploy {
+            if !deploy.is_dir() {
+                return Err(Error::new(IOError(format!("could not deploy mod {name} to {}: no such directory", deploy.display())), Fatality::FATAL, false))
+            }
+            log!("deploying {name} to {}", deploy.display());
+            std::fs::copy(&package, deploy.join(format!("{name}.zip")))?;
+        }
+        Ok(())
+    }
+
+    /// Builds the library with only `feature` enabled and keeps a copy of it as `out/<lib_name>`,
+    /// both features are built to the same file.
+    fn build_feature(&self, out_dir: &Path, name: &str, feature: &str, lib_name: &str) -> ErrorResult<Vec<u8>> {
+        let copy = out_dir.join("out").join(lib_name);
+        if !self.build {
+            log!("skipping {feature} build: --only-zip");
+            return std::fs::read(&copy)
+                .map_err(|e| Error::new(IOError(format!("no previous {feature} build at {}: {e}", copy.display())), Fatality::FATAL, false))
+        }
+        log!("building {feature}...");
+        let mut cmd = Command::new("cargo");
+        cmd.current_dir(&self.mod_dir)
+            .args(["build", "--lib", "-p", name, "--no-default-features", "--features", feature]);
+        if self.release {
+            cmd.arg("--release");
+        }
+        if self.wasm {
+            cmd.args(["--target", WASM_RUST_TARGET]);
+        }
+        let status = cmd.status()?;
+        if !status.success() {
+            return Err(Error::new(ModError(format!("building the {feature} of {} failed: {status}", self.mod_dir.display())), Fatality::FATAL, false))
+        }
+        std::fs::create_dir_all(out_dir.join("out"))?;
+        std::fs::copy(self.built_lib(out_dir, name), &copy)?;
+        Ok(std::fs::read(copy)?)
+    }
+
+    /// The cdylib cargo built, wasm builds are in target/<rust target>/<mode>.
+    fn built_lib(&self, out_dir: &Path, name: &str) -> PathBuf {
+        let crate_name = name.replace('-', "_");
+        if self.wasm {
+            let target_dir = out_dir.parent().unwrap_or(out_dir);
+            return target_dir.join(WASM_RUST_TARGET).join(self.mode()).join(format!("{crate_name}.wasm"))
+        }
+        #[cfg(target_os = "windows")]
+        let file = format!("{crate_name}.dll");
+        #[cfg(not(target_os = "windows"))]
+        let file = format!("lib{crate_name}.so");
+        out_dir.join(file)
+    }
+}
+
+pub(crate) fn zip_files(path: &Path, files: &[(String, Vec<u8>)]) -> ErrorResult<()> {
+    let map_err = |e: zip::result::ZipError| Error::new(IOError(format!("could not write {}: {e}", path.display())), Fatality::FATAL, false);
+    let mut zip = ZipWriter::new(File::create(path)?);
+    for (name, data) in files {
+        zip.start_file(name, FileOptions::default().compression_method(CompressionMethod::Deflated)).map_err(map_err)?;
+        zip.write_all(data)?;
+    }
+    zip.finish().map_err(map_err)?;
+    Ok(())
+}
+
+/// Reads a file from a zip, `None` if the zip has no such file.
+fn read_entry<R: Read + std::io::Seek>(zip: &mut ZipArchive<R>, name: &str) -> Option<Vec<u8>> {
+    let mut file = zip.by_name(name).ok()?;
+    let mut data = vec![];
+    file.read_to_end(&mut data).ok()?;
+    Some(data)
+}
+
+/// Checks that the package has the layout the server unpacks in `unpack_mod`.
+pub(crate) fn validate(package: &Path, name: &str, server_file: &str, targets: &[String]) -> ErrorResult<()> {
+    let invalid = |what: String| Error::new(ModError(format!("invalid package {}: {what}", package.display())), Fatality::FATAL, false);
+    let mut zip = ZipArchive::new(File::open(package)?).map_err(|e| invalid(e.to_string()))?;
+    let manifest = read_entry(&mut zip, MANIFEST_FILE).ok_or_else(|| invalid(format!("no {MANIFEST_FILE}")))?;
+    DeRon::deserialize_ron(&String::from_utf8_lossy(&manifest)).map(|_: ModManifest| ()).map_err(|e| invalid(format!("{e:?}")))?;
+
+    let mut expect_lib = |zip_name: String, lib_name: String| -> ErrorResult<Vec<u8>> {
+        let data = read_entry(&mut zip, &zip_name).ok_or_else(|| invalid(format!("no {zip_name}")))?;
+        let mut inner = ZipArchive::new(Cursor::new(&data)).map_err(|e| invalid(format!("{zip_name}: {e}")))?;
+        read_entry(&mut inner, &lib_name).ok_or_else(|| invalid(format!("{zip_name} has no {lib_name}")))?;
+        Ok(data)
+    };
+    expect_lib(server_zip(name), server_file.to_string())?;
+    let mut client_zips = vec![];
+    for target in targets {
+        client_zips.push((target, expect_lib(client_zip(name, target), client_lib(name, target))?));
+    }
+    for (target, data) in client_zips {
+        let Some(signature) = read_entry(&mut zip, &signature_file(&client_zip(name, target))) else { continue };
+        let hash = format!("{:X}", Sha256::digest(&data));
+        let valid = ModSignature::parse(&String::from_utf8_lossy(&signature)).is_some_and(|s| s.verify(&hash));
+        if !valid {
+            return Err(invalid(format!("signature of the client zip for {target} does not match")))
+        }
+    }
+    Ok(())
+}
+
+/// Writes a new signing key to `path` and logs its public key,
+/// which clients add to their trust store.
+/// On unix, only the owner may read the key file.
+pub(crate) fn keygen(path: &str) -> ErrorResult<()> {
+    if Path::new(path).exists() {
+        return Err(Error::new(IOError(format!("{path} already exists")), Fatality::FATAL, false))
+    }
+    let mut bytes = [0; 32];
+    getrandom::getrandom(&mut bytes)
+        .map_err(|e| Error::new(IOError(format!("could not generate key: {e}")), Fatality::FATAL, false))?;
+    let key = SigningKey::from_bytes(&bytes);
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    writeln!(options.open(path)?, "{}", to_hex(&bytes))?;
+    log!("wrote signing key to {path}, keep it secret");
+    log!("public key: {}", to_hex(key.verifying_key().as_bytes()));
+    Ok(())
+}
@@ -0,0 +1,112 @@
+use std::io::{Cursor, Write};
+use std::path::{Path, PathBuf};
+use aeonetica_engine::Id;
+use aeonetica_engine::manifest::MANIFEST_FILE;
+use aeonetica_engine::mod_layout::{client_lib, client_zip, server_zip};
+use aeonetica_engine::sha2::{Digest, Sha256};
+use aeonetica_engine::signing::{load_signing_key, ModSignature, signature_file, to_hex};
+use zip::ZipWriter;
+use zip::write::FileOptions;
+use crate::pack::{keygen, validate, zip_files};
+
+const NAME: &str = "test";
+const SERVER_FILE: &str = "libtest_server.so";
+const MANIFEST: &str = r#"(name: "test", version: "0.1.0", engine_version: "*", dependencies: {})"#;
+
+/// An empty directory in the temp dir, unique per call.
+fn temp_dir() -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("aeonetica-pack-test-{}", Id::new()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn zip_bytes(files: &[(String, Vec<u8>)]) -> Vec<u8> {
+    let mut zip = ZipWriter::new(Cursor::new(vec![]));
+    for (name, data) in files {
+        zip.start_file(name, FileOptions::default()).unwrap();
+        zip.write_all(data).unwrap();
+    }
+    zip.finish().unwrap().into_inner()
+}
+
+fn targets() -> Vec<String> {
+    vec!["x86_64-unix".to_string(), "x86_64-windows".to_string()]
+}
+
+/// The files of a valid package for [`targets`].
+fn package_files() -> Vec<(String, Vec<u8>)> {
+    let mut files = vec![
+        (MANIFEST_FILE.to_string(), MANIFEST.as_bytes().to_vec()),
+        (server_zip(NAME), zip_bytes(&[(SERVER_FILE.to_string(), vec![1])]))
+    ];
+    for target in targets() {
+        files.push((client_zip(NAME, &target), zip_bytes(&[(client_lib(NAME, &target), vec![2])])));
+    }
+    files
+}
+
+fn signature(zip: &[u8], key: &[u8; 32]) -> Vec<u8> {
+    let signature = ModSignature::sign(&format!("{:X}", Sha256::digest(zip)), &aeonetica_engine::ed25519_dalek::SigningKey::from_bytes(key));
+    format!("{}\n{}\n", to_hex(&signature.public_key), to_hex(&signature.signature)).into_bytes()
+}
+
+fn check(dir: &Path, files: &[(String, Vec<u8>)]) -> bool {
+    let package = dir.join(format!("{}.zip", Id::new()));
+    zip_files(&package, files).unwrap();
+    validate(&package, NAME, SERVER_FILE, &targets()).is_ok()
+}
+
+fn without(files: &[(String, Vec<u8>)], name: &str) -> Vec<(String, Vec<u8>)> {
+    files.iter().filter(|(n, _)| n != name).cloned().collect()
+}
+
+fn replaced(files: &[(String, Vec<u8>)], name: &str, data: Vec<u8>) -> Vec<(String, Vec<u8>)> {
+    files.iter().map(|(n, d)| (n.clone(), if n == name { data.clone() } else { d.clone() })).collect()
+}
+
+#[test]
+fn packages_are_validated() {
+    let dir = temp_dir();
+    let files = package_files();
+    let unix_zip = client_zip(NAME, &targets()[0]);
+    assert!(check(&dir, &files));
+
+    assert!(!check(&dir, &without(&files, MANIFEST_FILE)));
+    assert!(!check(&dir, &replaced(&files, MANIFEST_FILE, b"(name: \"test\")".to_vec())));
+    assert!(!check(&dir, &without(&files, &server_zip(NAME))));
+    assert!(!check(&dir, &replaced(&files, &server_zip(NAME), zip_bytes(&[("other.so".to_string(), vec![1])]))));
+    // every target needs a client zip with its library
+    assert!(!check(&dir, &without(&files, &unix_zip)));
+    assert!(!check(&dir, &replaced(&files, &unix_zip, zip_bytes(&[(client_lib(NAME, &targets()[1]), vec![2])]))));
+    assert!(!check(&dir, &replaced(&files, &unix_zip, b"not a zip".to_vec())));
+
+    let client = files.iter().find(|(n, _)| *n == unix_zip).unwrap().1.clone();
+    let signed = [files.clone(), vec![(signature_file(&unix_zip), signature(&client, &[7; 32]))]].concat();
+    assert!(check(&dir, &signed));
+    let forged = [files.clone(), vec![(signature_file(&unix_zip), signature(b"another zip", &[7; 32]))]].concat();
+    assert!(!check(&dir, &forged));
+    let garbage = [files, vec![(signature_file(&unix_zip), b"not a signature".to_vec())]].concat();
+    assert!(!check(&dir, &garbage));
+
+    std::fs::write(dir.join("broken.zip"), b"not a zip").unwrap();
+    assert!(validate(&dir.join("broken.zip"), NAME, SERVER_FILE, &targets()).is_err());
+    let _ = std::fs::remove_dir_all(dir);
+}
+
+#[test]
+fn keygen_writes_a_private_key_once() {
+    let dir = temp_dir();
+    let path = dir.join("key");
+    let path = path.to_str().unwrap();
+    keygen(path).unwrap();
+    assert!(load_signing_key(path).is_ok());
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        assert_eq!(std::fs::metadata(path).unwrap().permissions().mode() & 0o777, 0o600);
+    }
+    let key = std::fs::read(path).unwrap();
+    assert!(keygen(path).is_err());
+    assert_eq!(std::fs::read(path).unwrap(), key);
+    let _ = std::fs::remove_dir_all(dir);
+}
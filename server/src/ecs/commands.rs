@@ -0,0 +1,111 @@
+use std::rc::Rc;
+use aeonetica_engine::{EntityId, Id};
+use aeonetica_engine::util::nullable::Nullable::Value;
+use crate::ecs::Engine;
+use crate::ecs::module::Module;
+
+pub(crate) type Command = Box<dyn FnOnce(&mut Engine)>;
+
+/// Structural changes that are applied at the next sync point of the tick
+/// instead of while modules or tasks are being run:
+/// after handling network packets, after ticking all modules and after running tasks.
+///
+/// Commands are applied in the order they were queued.
+/// Entities spawned through commands get their id right away,
+/// so further commands can already refer to them.
+/// ```ignore
+/// let bullet = engine.commands().spawn()
+///     .insert_module(Bullet::new(dir))
+///     .insert_module(Messenger::new::<BulletHandle>())
+///     .id();
+/// engine.commands().despawn(*id);
+/// ```
+pub struct Commands<'a> {
+    queue: &'a mut Vec<(Option<Rc<str>>, Command)>,
+    owner: Option<Rc<str>>
+}
+
+impl<'a> Commands<'a> {
+    /// Queues an arbitrary change.
+    pub fn add<F: FnOnce(&mut Engine) + 'static>(&mut self, command: F) -> &mut Self {
+        self.queue.push((self.owner.clone(), Box::new(command)));
+        self
+    }
+
+    pub fn spawn(&mut self) -> EntityCommands<'_, 'a> {
+        let id = Id::new();
+        self.add(move |engine| engine.new_entity_with_id(id));
+        EntityCommands { commands: self, id }
+    }
+
+    pub fn entity(&mut self, id: EntityId) -> EntityCommands<'_, 'a> {
+        EntityCommands { commands: self, id }
+    }
+
+    pub fn despawn(&mut self, id: EntityId) -> &mut Self {
+        self.add(move |engine| { engine.remove_entity(&id); })
+    }
+
+    /// Does nothing if the entity does not exist anymore or already has a module of that type.
+    pub fn insert_module<T: Module + Sized + 'static>(&mut self, id: EntityId, module: T) -> &mut Self {
+        self.add(move |engine| if let Value(e) = engine.mut_entity(&id) {
+            e.add_module(module);
+        })
+    }
+
+    pub fn remove_module<T: Module + Sized + 'static>(&mut self, id: EntityId) -> &mut Self {
+        self.add(move |engine| if let Value(e) = engine.mut_entity(&id) {
+            e.remove_module::<T>();
+        })
+    }
+}
+
+pub struct EntityCommands<'c, 'a> {
+    commands: &'c mut Commands<'a>,
+    id: EntityId
+}
+
+impl EntityCommands<'_, '_> {
+    pub fn id(&self) -> EntityId {
+        self.id
+    }
+
+    pub fn insert_module<T: Module + Sized + 'static>(self, module: T) -> Self {
+        self.commands.insert_module(self.id, module);
+        self
+    }
+
+    pub fn remove_module<T: Module + Sized + 'static>(self) -> Self {
+        self.commands.remove_module::<T>(self.id);
+        self
+    }
+
+    pub fn tag<S: Into<String>>(self, tag: S) -> Self {
+        let id = self.id;
+        let tag = tag.into();
+        self.commands.add(move |engine| { engine.tag_entity(id, tag); });
+        self
+    }
+
+    pub fn despawn(self) {
+        self.commands.despawn(self.id);
+    }
+}
+
+impl Engine {
+    pub fn commands(&mut self) -> Commands<'_> {
+        Commands {
+            owner: self.current_mod.clone(),
+            queue: &mut self.commands
+        }
+    }
+
+    /// Sync point: applies all queued commands, including the ones queued by commands.
+    pub(crate) fn apply_commands(&mut self) {
+        while !self.commands.is_empty() {
+            for (owner, command) in std::mem::take(&mut self.commands) {
+                self.as_mod(owner, command);
+            }
+        }
+    }
+}
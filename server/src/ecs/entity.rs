@@ -1,5 +1,6 @@
 
-use aeonetica_engine::{EntityId, Id, TypeId};
+use std::rc::Rc;
+use aeonetica_engine::{ClientId, EntityId, TypeId};
 use aeonetica_engine::util::id_map::IdMap;
 use aeonetica_engine::util::nullable::Nullable;
 use aeonetica_engine::util::type_to_id;
@@ -7,27 +8,45 @@ use crate::ecs::Engine;
 use crate::ecs::module::{Module, ModuleDyn};
 
 pub struct Entity {
-    engine: *mut Engine, // Only use for add/removal events!!! DO NOT use for any other purpose!!!
+    engine: *mut Engine, // Only use for add/removal events and the module storage!!! DO NOT use for any other purpose!!!
     pub(crate) entity_id: EntityId,
-    pub(crate) modules: IdMap<Box<dyn ModuleDyn>>
+    /// types of the modules of this entity, the modules themselves live in the engine's module storage
+    pub(crate) modules: Vec<TypeId>,
+    /// name_path of the mod that created this entity
+    pub(crate) owner: Option<Rc<str>>,
+    /// modules that were added by a different mod than the owner of this entity
+    pub(crate) module_owners: IdMap<Rc<str>>,
+    pub(crate) parent: Option<EntityId>,
+    pub(crate) children: Vec<EntityId>,
+    /// the entity is removed when this client leaves
+    pub(crate) client: Option<ClientId>
 }
 
 impl Entity {
-    pub(crate) fn new(engine: &Engine) -> Self {
+    pub(crate) fn new(engine: &Engine, entity_id: EntityId) -> Self {
         Self {
             engine: engine as *const Engine as *mut Engine,
-            entity_id: Id::new(),
-            modules: Default::default()
+            entity_id,
+            modules: vec![],
+            owner: engine.current_mod.clone(),
+            module_owners: Default::default(),
+            parent: None,
+            children: vec![],
+            client: None
         }
     }
 
     pub fn add_module<T: Module + Sized + 'static>(&mut self, mut module: T) -> bool {
         module.init();
-        if let std::collections::hash_map::Entry::Vacant(e) = self.modules.entry(type_to_id::<T>()) {
-            e.insert(Box::new(module));
-            if let Some(m) = self.modules.get(&type_to_id::<T>()) {
-                m.start_dyn(&self.entity_id, unsafe {&mut *self.engine})
+        let engine = unsafe { &mut *self.engine };
+        if engine.storage.insert(self.entity_id, module, &engine.current_mod) {
+            self.modules.push(type_to_id::<T>());
+            if let Some(owner) = &engine.current_mod {
+                if engine.current_mod != self.owner {
+                    self.module_owners.insert(type_to_id::<T>(), owner.clone());
+                }
             }
+            T::start(&self.entity_id, engine);
             true
         } else {
             false
@@ -35,46 +54,71 @@ impl Entity {
     }
 
     pub fn modules(&self) -> Vec<TypeId> {
-        self.modules.keys().copied().collect()
+        self.modules.clone()
     }
 
     pub fn remove_module<T: Module + Sized + 'static>(&mut self) -> bool{
-        if let Some(m) = self.modules.get(&type_to_id::<T>()) { m.remove_dyn(&self.entity_id, unsafe {&mut *self.engine}) }
-        self.modules.remove(&type_to_id::<T>()).is_some()
+        self.remove_module_type(&type_to_id::<T>())
+    }
+
+    pub(crate) fn remove_module_type(&mut self, ty: &TypeId) -> bool {
+        if !self.has_module_type(ty) {
+            return false
+        }
+        let engine = unsafe { &mut *self.engine };
+        if let Some(m) = engine.storage.storage(ty).and_then(|s| s.get_dyn(&self.entity_id)) {
+            m.remove_dyn(&self.entity_id, unsafe { &mut *self.engine })
+        }
+        self.modules.retain(|t| t != ty);
+        self.module_owners.remove(ty);
+        engine.storage.remove(ty, &self.entity_id)
     }
 
     pub fn get_module<T: Module + Sized + 'static>(&self) -> Nullable<&T> {
-        self.modules.get(&type_to_id::<T>()).map(|m| unsafe { &*std::mem::transmute::<&Box<_>, &(*const T, usize)>(m).0 } ).into()
+        unsafe { &*self.engine }.storage.get::<T>(&self.entity_id).into()
     }
 
     pub fn mut_module<T: Module + Sized + 'static>(&mut self) -> Nullable<&mut T> {
-        self.modules.get_mut(&type_to_id::<T>()).map(|m| unsafe { &mut*std::mem::transmute::<&Box<_>, &(*mut T, usize)>(m).0 }).into()
+        unsafe { &mut *self.engine }.storage.get_mut::<T>(&self.entity_id).into()
     }
 
     pub fn get_or_create<T: Module + Sized + 'static, F: FnOnce() -> T>(&mut self, creator: F) -> &T {
-        self.modules.get(&type_to_id::<T>()).map(|m| unsafe { &*std::mem::transmute::<&Box<_>, &(*const T, usize)>(m).0 } ).unwrap_or_else(||{
+        if !self.has_module::<T>() {
             self.add_module(creator());
-            self.mut_module::<T>().unwrap()
-        })
+        }
+        self.get_module::<T>().unwrap()
     }
 
     pub fn mut_or_create<T: Module + Sized + 'static, F: FnOnce() -> T>(&mut self, creator: F) -> &T {
-        self.modules.get_mut(&type_to_id::<T>()).map(|m| unsafe { &*std::mem::transmute::<&Box<_>, &(*mut T, usize)>(m).0 } ).unwrap_or_else(||{
+        if !self.has_module::<T>() {
             self.add_module(creator());
-            self.mut_module::<T>().unwrap()
-        })
+        }
+        self.mut_module::<T>().unwrap()
     }
 
 
     pub fn has_module<T: Module + Sized + 'static>(&self) -> bool {
-        self.modules.contains_key(&type_to_id::<T>())
+        self.has_module_type(&type_to_id::<T>())
     }
 
     pub fn has_module_type(&self, ty: &TypeId) -> bool {
-        self.modules.contains_key(ty)
+        self.modules.contains(ty)
     }
 
     pub fn id(&self) -> EntityId {
         self.entity_id
     }
-}
\ No newline at end of file
+
+    pub fn parent(&self) -> Option<EntityId> {
+        self.parent
+    }
+
+    pub fn children(&self) -> &[EntityId] {
+        &self.children
+    }
+
+    /// The client this entity's lifetime is tied to, see [`Engine::tie_to_client`].
+    pub fn client(&self) -> Option<ClientId> {
+        self.client
+    }
+}
@@ -1,6 +1,11 @@
-use aeonetica_engine::{ClientId, EntityId};
+use std::any::type_name;
+use std::ops::{Deref, DerefMut};
+use aeonetica_engine::{ClientId, EntityId, log};
+use aeonetica_engine::util::nullable::Nullable;
+use aeonetica_engine::util::type_to_id;
 use crate::ecs::Engine;
 use crate::ecs::module::Module;
+use crate::ecs::scheduling::Event;
 
 pub struct ConnectionListener {
     pub(crate) on_join: fn(id: &EntityId, engine: &mut Engine, user: &ClientId),
@@ -16,4 +21,118 @@ impl ConnectionListener {
     }
 }
 
-impl Module for ConnectionListener {}
\ No newline at end of file
+impl Module for ConnectionListener {}
+
+/// Calls `callback` whenever an event of type `E` is fired.
+///
+/// Listeners run ordered by `order` (lowest first), listeners with the same order
+/// run in an unspecified but stable order.
+pub struct EventListener<E: Event> {
+    pub(crate) order: i32,
+    pub(crate) callback: fn(id: &EntityId, engine: &mut Engine, event: &mut Dispatch<E>)
+}
+
+impl<E: Event> EventListener<E> {
+    pub fn new(callback: fn(id: &EntityId, engine: &mut Engine, event: &mut Dispatch<E>)) -> Self {
+        Self::with_order(0, callback)
+    }
+
+    pub fn with_order(order: i32, callback: fn(id: &EntityId, engine: &mut Engine, event: &mut Dispatch<E>)) -> Self {
+        Self {
+            order,
+            callback
+        }
+    }
+}
+
+impl<E: Event> Module for EventListener<E> {}
+
+/// An event on its way through the listeners.
+/// Listeners may modify the event and, if `E::CANCELLABLE`, cancel it.
+pub struct Dispatch<E: Event> {
+    event: E,
+    cancelled: bool
+}
+
+impl<E: Event> Dispatch<E> {
+    /// Stops the event from reaching any further listeners or waiting tasks.
+    /// Cancelling an event that is not `CANCELLABLE` is ignored.
+    pub fn cancel(&mut self) {
+        if E::CANCELLABLE {
+            self.cancelled = true
+        } else {
+            log!(WARN, "tried to cancel event {} which is not cancellable", type_name::<E>())
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled
+    }
+}
+
+impl<E: Event> Deref for Dispatch<E> {
+    type Target = E;
+    fn deref(&self) -> &E { &self.event }
+}
+
+impl<E: Event> DerefMut for Dispatch<E> {
+    fn deref_mut(&mut self) -> &mut E { &mut self.event }
+}
+
+impl Engine {
+    /// Queues the event, it is delivered at the end of the tick.
+    pub fn fire<E: Event>(&mut self, event: E) {
+        self.queued_events.push((self.current_mod.clone(), Box::new(move |engine| { engine.fire_now(event); })));
+    }
+
+    /// Delivers the event right away, first to all [`EventListener`]s,
+    /// then to all tasks waiting for `WaitFor::event::<E>()`.
+    ///
+    /// Returns the event as modified by the listeners, or `None` if it got cancelled.
+    pub fn fire_now<E: Event>(&mut self, event: E) -> Option<E> {
+        let mut listeners = self.storage.column::<EventListener<E>>()
+            .map(|c| c.iter().map(|(id, l)| (l.order, *id, l.callback)).collect::<Vec<_>>())
+            .unwrap_or_default();
+        listeners.sort_by_key(|(order, id, _)| (*order, id.into_u64()));
+
+        let mut dispatch = Dispatch { event, cancelled: false };
+        for (_, id, callback) in listeners {
+            // an earlier listener might have removed this one
+            if self.storage.get::<EventListener<E>>(&id).is_none() {
+                continue
+            }
+            let owner = self.module_owner(&id, &type_to_id::<EventListener<E>>());
+            self.as_mod(owner, |engine| callback(&id, engine, &mut dispatch));
+            if dispatch.cancelled {
+                return None
+            }
+        }
+
+        let previous = self.current_event.replace(Box::new(dispatch.event));
+        self.fire_event::<E>();
+        let event = std::mem::replace(&mut self.current_event, previous).unwrap();
+        Some(*event.downcast::<E>().unwrap())
+    }
+
+    /// The event that resumed the currently running task:
+    /// ```ignore
+    /// engine.queue_task(|mut e: &mut Engine| {
+    ///     yield_task!(e, WaitFor::event::<PlayerDied>());
+    ///     let died = e.received_event::<PlayerDied>();
+    ///     // ...
+    /// });
+    /// ```
+    /// Null if the task was not resumed by an event of type `E`, e.g. through [`Engine::fire_event`].
+    pub fn received_event<E: Event>(&self) -> Nullable<&E> {
+        self.current_event.as_ref()?.downcast_ref::<E>().into()
+    }
+
+    /// Sync point at the end of the tick: delivers all queued events, including the ones fired while delivering.
+    pub(crate) fn dispatch_events(&mut self) {
+        while !self.queued_events.is_empty() {
+            for (owner, dispatch) in std::mem::take(&mut self.queued_events) {
+                self.as_mod(owner, dispatch);
+            }
+        }
+    }
+}
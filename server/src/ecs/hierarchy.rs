@@ -0,0 +1,105 @@
+use aeonetica_engine::{ClientId, EntityId};
+use crate::ecs::Engine;
+
+/// Entities can be arranged in a tree.
+/// Removing an entity also removes all of its children, running their [`crate::ecs::module::Module::remove`] hooks.
+///
+/// An entity can also be tied to a client, it is removed once the client leaves or gets kicked,
+/// right after all `ConnectionListener::on_leave` callbacks ran:
+/// ```ignore
+/// let player = engine.new_entity();
+/// engine.tie_to_client(&player, client);
+/// let weapon = engine.new_child_entity(&player).unwrap();
+/// ```
+impl Engine {
+    /// Returns `None` if the parent does not exist.
+    pub fn new_child_entity(&mut self, parent: &EntityId) -> Option<EntityId> {
+        if !self.entity_exists(parent) {
+            return None
+        }
+        let id = self.new_entity();
+        self.set_parent(&id, Some(*parent));
+        Some(id)
+    }
+
+    /// Moves the entity to a new parent, or makes it a root entity if `parent` is `None`.
+    /// Fails if either entity does not exist or if `parent` is a descendant of the entity.
+    pub fn set_parent(&mut self, id: &EntityId, parent: Option<EntityId>) -> bool {
+        if !self.entity_exists(id) {
+            return false
+        }
+        if let Some(parent) = &parent {
+            if !self.entity_exists(parent) || self.is_ancestor_of(id, parent) {
+                return false
+            }
+        }
+        if let Some(old) = self.entites.get_mut(id).unwrap().parent.take() {
+            if let Some(old) = self.entites.get_mut(&old) {
+                old.children.retain(|c| c != id);
+            }
+        }
+        if let Some(parent) = parent {
+            self.entites.get_mut(&parent).unwrap().children.push(*id);
+        }
+        self.entites.get_mut(id).unwrap().parent = parent;
+        true
+    }
+
+    #[inline]
+    pub fn parent_of(&self, id: &EntityId) -> Option<EntityId> {
+        self.entites.get(id)?.parent
+    }
+
+    #[inline]
+    pub fn children_of(&self, id: &EntityId) -> &[EntityId] {
+        self.entites.get(id).map(|e| &e.children[..]).unwrap_or(&[])
+    }
+
+    /// All children, children of children and so on, parents before their children.
+    pub fn descendants_of(&self, id: &EntityId) -> Vec<EntityId> {
+        let mut descendants = self.children_of(id).to_vec();
+        let mut i = 0;
+        while i < descendants.len() {
+            descendants.extend_from_slice(self.children_of(&descendants[i]));
+            i += 1;
+        }
+        descendants
+    }
+
+    /// `true` if `ancestor` is `id` itself or one of its (grand)parents.
+    pub fn is_ancestor_of(&self, ancestor: &EntityId, id: &EntityId) -> bool {
+        let mut current = Some(*id);
+        while let Some(c) = current {
+            if c == *ancestor {
+                return true
+            }
+            current = self.parent_of(&c);
+        }
+        false
+    }
+
+    /// Removes the entity once the client leaves or gets kicked.
+    /// Pass `None` to untie it again.
+    pub fn tie_to_client(&mut self, id: &EntityId, client: Option<ClientId>) -> bool {
+        match self.entites.get_mut(id) {
+            Some(e) => {
+                e.client = client;
+                true
+            }
+            None => false
+        }
+    }
+
+    pub fn client_entities(&self, client: &ClientId) -> Vec<EntityId> {
+        self.entites.iter()
+            .filter(|(_, e)| e.client.as_ref() == Some(client))
+            .map(|(id, _)| *id)
+            .collect()
+    }
+
+    pub(crate) fn remove_client_entities(&mut self, client: &ClientId) {
+        for id in self.client_entities(client) {
+            self.remove_entity(&id);
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use std::collections::{HashMap, HashSet};
+use aeonetica_engine::ClientId;
+use aeonetica_engine::math::vector::Vector2;
+use crate::ecs::Engine;
+use crate::ecs::messaging::Messenger;
+use crate::ecs::module::Module;
+
+/// Side length of the spatial hash cells, in tiles.
+const CELL_SIZE: f32 = 16.0;
+pub const DEFAULT_HYSTERESIS: f32 = 2.0;
+
+/// The point of view of a client, usually added to the entity the client controls.
+pub struct Viewer {
+    pub client: ClientId,
+    pub position: Vector2<f32>
+}
+
+impl Viewer {
+    pub fn new(client: ClientId, position: Vector2<f32>) -> Self {
+        Self { client, position }
+    }
+}
+
+impl Module for Viewer {}
+
+/// Makes the engine manage the receivers of the entity's [`Messenger`]:
+/// every tick, clients whose [`Viewer`] is within `radius` of `position` are added,
+/// clients that moved further away than `radius + hysteresis` are removed again.
+///
+/// Clients added to the messenger by hand are never removed.
+/// `position` has to be kept up to date by the mod.
+pub struct Interest {
+    pub position: Vector2<f32>,
+    pub radius: f32,
+    pub hysteresis: f32,
+    /// clients added by the interest system
+    managed: HashSet<ClientId>
+}
+
+impl Interest {
+    pub fn new(position: Vector2<f32>, radius: f32) -> Self {
+        Self::with_hysteresis(position, radius, DEFAULT_HYSTERESIS)
+    }
+
+    pub fn with_hysteresis(position: Vector2<f32>, radius: f32, hysteresis: f32) -> Self {
+        Self {
+            position,
+            radius,
+            hysteresis,
+            managed: Default::default()
+        }
+    }
+}
+
+impl Module for Interest {}
+
+fn cell(position: f32) -> i32 {
+    (position / CELL_SIZE).floor() as i32
+}
+
+impl Engine {
+    /// Sync point at the end of the tick, before replication:
+    /// updates the receivers of all entities with an [`Interest`].
+    pub(crate) fn update_interest(&mut self) {
+        let mut grid = HashMap::<(i32, i32), Vec<(ClientId, Vector2<f32>)>>::new();
+        for viewer in self.query::<&Viewer>() {
+            grid.entry((cell(viewer.position.x), cell(viewer.position.y)))
+                .or_default()
+                .push((viewer.client, viewer.position));
+        }
+
+        for (interest, messenger) in self.query::<(&mut Interest, &mut Messenger)>() {
+            let range = interest.radius + interest.hysteresis;
+            let mut in_range = HashSet::new();
+            for x in cell(interest.position.x - range)..=cell(interest.position.x + range) {
+                for y in cell(interest.position.y - range)..=cell(interest.position.y + range) {
+                    for (client, position) in grid.get(&(x, y)).into_iter().flatten() {
+                        let dsq = (*position - interest.position).mag_sq();
+                        if dsq <= interest.radius * interest.radius
+                            || (dsq <= range * range && interest.managed.contains(client)) {
+                            in_range.insert(*client);
+                        }
+                    }
+                }
+            }
+
+            for client in interest.managed.iter().filter(|c| !in_range.contains(*c)).copied().collect::<Vec<_>>() {
+                messenger.remove_client(&client);
+                interest.managed.remove(&client);
+            }
+            for client in in_range {
+                if !messenger.has_client(&client) && messenger.add_client(client) {
+                    interest.managed.insert(client);
+                }
+            }
+        }
+    }
+}
@@ -6,6 +6,7 @@ use std::fmt::Debug;
 use std::rc::Rc;
 use aeonetica_engine::{ClientId, EntityId, Id, TypeId};
 use aeonetica_engine::nanoserde::{DeBin, SerBin};
+use aeonetica_engine::networking::rpc::{decode_args, handle_request, register_rpc, Request, Rpc};
 use aeonetica_engine::networking::server_packets::{ServerMessage, ServerPacket};
 use aeonetica_engine::util::type_to_id;
 use crate::ecs::{Module, Engine};
@@ -32,40 +33,127 @@ pub unsafe trait DataStore {}
 
 pub struct Messenger {
     ns: Option<Rc<RefCell<NetworkServer>>>,
+    engine: *const Engine, // Only use to look up the mod registering a function!!!
     handle_type: TypeId,
     entity_id: EntityId,
     pub(crate) receivers: HashSet<ClientId>,
-    pub(crate) receiver_functions: IdMap<Box<dyn Fn(&EntityId, &mut Engine, &ClientId, &Vec<u8>)>>
+    pub(crate) receiver_functions: IdMap<Box<dyn Fn(&EntityId, &mut Engine, &ClientId, &Vec<u8>)>>,
+    pub(crate) request_functions: IdMap<Box<dyn Fn(&EntityId, &mut Engine, &ClientId, &Vec<u8>) -> Result<Vec<u8>, String>>>,
+    /// name_path of the mod that registered each receiver and request function,
+    /// they are removed when that mod gets reloaded since their code lives in its library
+    pub(crate) function_owners: IdMap<Rc<str>>
 }
 
 impl Module for Messenger {
     fn start(id: &Id, engine: &mut Engine) where Self: Sized {
         let ns = engine.runtime.ns.clone();
+        let engine_ptr = engine as *const Engine;
         let module = engine.mut_module_of::<Self>(id).unwrap();
         module.entity_id = *id;
-        module.ns = Some(ns)
+        module.ns = Some(ns);
+        module.engine = engine_ptr
+    }
+
+    fn remove(id: &Id, engine: &mut Engine) where Self: Sized {
+        let module = engine.mut_module_of::<Self>(id).unwrap();
+        for client in module.receivers.iter().copied().collect::<Vec<_>>() {
+            module.remove_client(&client);
+        }
     }
 }
 
 impl Messenger {
     pub fn new<H: ClientEntity + Sized + 'static>() -> Self {
+        Self::with_handle_type(type_to_id::<H>())
+    }
+
+    /// For client handles that are not a rust type, like the ones of wasm mods.
+    pub(crate) fn with_handle_type(handle_type: TypeId) -> Self {
         Self {
             ns: None,
+            engine: std::ptr::null(),
             receivers: Default::default(),
-            handle_type: type_to_id::<H>(),
+            handle_type,
             entity_id: Id::new(),
-            receiver_functions: Default::default()
+            receiver_functions: Default::default(),
+            request_functions: Default::default(),
+            function_owners: Default::default()
+        }
+    }
+
+    /// Registers `f` as the receiver with id `rid`, owned by the mod that is running.
+    pub(crate) fn insert_receiver(&mut self, rid: Id, f: Box<dyn Fn(&EntityId, &mut Engine, &ClientId, &Vec<u8>)>) {
+        self.record_owner(rid);
+        self.receiver_functions.insert(rid, f);
+    }
+
+    fn insert_request_function(&mut self, rid: Id, f: Box<dyn Fn(&EntityId, &mut Engine, &ClientId, &Vec<u8>) -> Result<Vec<u8>, String>>) {
+        self.record_owner(rid);
+        self.request_functions.insert(rid, f);
+    }
+
+    fn record_owner(&mut self, rid: Id) {
+        // messengers that were not added to an entity yet belong to whoever adds them
+        match unsafe { self.engine.as_ref() }.and_then(|engine| engine.current_mod.clone()) {
+            Some(owner) => self.function_owners.insert(rid, owner),
+            None => self.function_owners.remove(&rid)
+        };
+    }
+
+    /// Removes all receivers and request functions registered by the mod `owner`, returns how many.
+    pub(crate) fn remove_functions_of(&mut self, owner: &Rc<str>) -> usize {
+        let owned = self.function_owners.iter()
+            .filter(|(_, o)| *o == owner)
+            .map(|(rid, _)| *rid)
+            .collect::<Vec<_>>();
+        for rid in &owned {
+            self.function_owners.remove(rid);
+            self.receiver_functions.remove(rid);
+            self.request_functions.remove(rid);
         }
+        owned.len()
     }
 
     pub fn register_receiver<F: Fn(&EntityId, &mut Engine, &ClientId, M) + 'static, M: SerBin + DeBin>(&mut self, f: F) {
         let m = move |id: &Id, engine: &mut Engine, sender: &ClientId, data: &Vec<u8>|
             f(id, engine, sender, M::deserialize_bin(data).unwrap());
-        self.receiver_functions.insert(type_to_id::<F>(), Box::new(m));
+        self.insert_receiver(type_to_id::<F>(), Box::new(m));
     }
 
     pub fn unregister_receiver<F: Fn(&EntityId, &mut Engine, &ClientId, M) + 'static, M: SerBin + DeBin>(&mut self, _: F) {
         self.receiver_functions.remove(&type_to_id::<F>());
+        self.function_owners.remove(&type_to_id::<F>());
+    }
+
+    /// Registers `f` as the receiver of the rpc `R` sent by clients.
+    pub fn on<R: Rpc>(&mut self, f: impl Fn(&EntityId, &mut Engine, &ClientId, R::Args) + 'static) {
+        register_rpc::<R>();
+        let m = move |id: &EntityId, engine: &mut Engine, sender: &ClientId, data: &Vec<u8>| {
+            if let Some(args) = decode_args::<R>(data) {
+                f(id, engine, sender, args)
+            }
+        };
+        self.insert_receiver(R::id(), Box::new(m));
+    }
+
+    /// Registers `f` to answer the request `R` sent by clients.
+    pub fn on_request<R: Request>(&mut self, f: impl Fn(&EntityId, &mut Engine, &ClientId, R::Args) -> R::Reply + 'static) {
+        register_rpc::<R>();
+        let m = move |id: &EntityId, engine: &mut Engine, sender: &ClientId, data: &Vec<u8>|
+            handle_request::<R>(data, |args| f(id, engine, sender, args));
+        self.insert_request_function(R::id(), Box::new(m));
+    }
+
+    /// Calls the rpc `R` on the client handles of all receivers.
+    pub fn send<R: Rpc>(&mut self, args: R::Args, mode: SendMode) {
+        let data = args.serialize_bin();
+        for client in &self.receivers {
+            self.send_raw(client, R::id(), data.clone(), mode);
+        }
+    }
+
+    pub fn send_to<R: Rpc>(&mut self, client: &ClientId, args: R::Args, mode: SendMode) {
+        self.send_raw(client, R::id(), args.serialize_bin(), mode);
     }
 
     pub fn call_client_fn<F: Fn(&mut T, &mut TClientMessenger, Nullable<&mut TRenderer>, &mut TDataStore, M), T: ClientEntity, TClientMessenger: ClientMessenger, TRenderer: Renderer, TDataStore: DataStore, M: SerBin + DeBin>(&mut self, _: F, message: M, mode: SendMode) {
@@ -86,6 +174,14 @@ impl Messenger {
         }, mode);
     }
 
+    /// Sends already serialized data to the receiver `rid` of the client handle.
+    pub(crate) fn send_raw(&self, client: &ClientId, rid: Id, data: Vec<u8>, mode: SendMode) {
+        let _ = self.ns.as_ref().unwrap().borrow().send(client, &ServerPacket {
+            conv_id: Id::new(),
+            message: ServerMessage::ModMessage(self.entity_id, rid, data),
+        }, mode);
+    }
+
     pub fn clients(&self) -> Iter<ClientId> {
         self.receivers.iter()
     }
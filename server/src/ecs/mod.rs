@@ -1,21 +1,25 @@
 
-use std::any::type_name;
+use std::any::{Any, type_name};
 use std::collections::{HashMap, HashSet};
 use std::collections::hash_set;
 use std::collections::hash_map::{Iter, IterMut, Keys};
+use std::rc::Rc;
 
 
 use crate::ecs::entity::Entity;
 use aeonetica_engine::util::{type_to_id, Typle};
-use aeonetica_engine::{ClientId, EntityId, Id, log};
+use aeonetica_engine::{ClientId, EntityId, Id, log, TypeId};
 use aeonetica_engine::networking::SendMode;
 use aeonetica_engine::networking::server_packets::{ServerMessage, ServerPacket};
 use aeonetica_engine::util::id_map::IdMap;
 use aeonetica_engine::util::nullable::Nullable;
-use aeonetica_engine::util::nullable::Nullable::Value;
+use crate::ecs::commands::Command;
 use crate::ecs::events::ConnectionListener;
 
 use crate::ecs::module::{Module, ModuleDyn};
+use crate::ecs::storage::{Column, ModuleStorage, Storage};
+use crate::ecs::resources::Resource;
+use crate::ecs::requests::PendingRequest;
 use crate::ecs::scheduling::TaskQueue;
 use crate::server_runtime::ServerRuntime;
 
@@ -24,25 +28,56 @@ pub mod entity;
 pub mod events;
 pub mod messaging;
 pub mod scheduling;
+pub mod resources;
+pub mod query;
+pub mod commands;
+pub mod hierarchy;
+pub mod replication;
+pub mod interest;
+pub mod validation;
+pub mod requests;
+pub(crate) mod storage;
 
 pub struct Engine {
     entites: IdMap<Entity>,
+    pub(crate) storage: ModuleStorage,
     tagged: HashMap<String, EntityId>,
-    tasks: TaskQueue,
+    pub(crate) resources: IdMap<Resource>,
+    pub(crate) tasks: TaskQueue,
+    /// queued structural changes and the mods that queued them
+    pub(crate) commands: Vec<(Option<Rc<str>>, Command)>,
+    /// events fired with `fire`, delivered at the end of the tick
+    pub(crate) queued_events: Vec<(Option<Rc<str>>, Command)>,
+    /// the event currently being delivered to waiting tasks
+    pub(crate) current_event: Option<Box<dyn Any>>,
+    /// one replication system per replicated module type and the mod that registered it
+    pub(crate) replicators: IdMap<(fn(&mut Engine), Option<Rc<str>>)>,
+    /// requests sent to clients by their conv_id
+    pub(crate) pending_replies: IdMap<PendingRequest>,
     pub(crate) clients: HashSet<ClientId>,
     pub(crate) runtime: ServerRuntime,
-    pub(crate) tick: usize
+    pub(crate) tick: usize,
+    /// name_path of the mod on whose behalf code is currently running
+    pub(crate) current_mod: Option<Rc<str>>
 }
 
 impl Engine {
     pub fn new(runtime: ServerRuntime) -> Self {
         Self {
             entites: Default::default(),
+            storage: Default::default(),
             tagged: Default::default(),
+            resources: Default::default(),
             clients: Default::default(),
             tasks: TaskQueue::default(),
+            commands: vec![],
+            queued_events: vec![],
+            current_event: None,
+            replicators: Default::default(),
+            pending_replies: Default::default(),
             runtime,
-            tick: 0
+            tick: 0,
+            current_mod: None
         }
     }
 
@@ -53,14 +88,27 @@ impl Engine {
         unsafe { &mut *(self as *const Engine as usize as *mut Engine) }
     }
 
+    /// Runs `f` on behalf of the given mod.
+    /// Entities, modules and tasks created in `f` are owned by that mod.
+    pub(crate) fn as_mod<R>(&mut self, owner: Option<Rc<str>>, f: impl FnOnce(&mut Self) -> R) -> R {
+        let previous = std::mem::replace(&mut self.current_mod, owner);
+        let r = f(self);
+        self.current_mod = previous;
+        r
+    }
+
     #[inline]
     pub fn new_entity(&mut self) -> EntityId {
-        let e = Entity::new(self);
-        let id = e.entity_id;
-        self.entites.insert(id, e);
+        let id = Id::new();
+        self.new_entity_with_id(id);
         id
     }
 
+    pub(crate) fn new_entity_with_id(&mut self, id: EntityId) {
+        let e = Entity::new(self, id);
+        self.entites.insert(id, e);
+    }
+
     /// Retuns true if user got successfully kicked.
     /// Kick fails if the client is not joined.
     /// Kicking will also unregister the client.
@@ -76,6 +124,8 @@ impl Engine {
             self.for_each_module_of_type::<ConnectionListener, _>(|engine, eid,  m| {
                 (m.on_leave)(eid, engine, id);
             });
+            self.remove_client_entities(id);
+            self.drop_client_replies(id);
             let _ = self.runtime.ns.borrow().send(id, &ServerPacket {
                 conv_id: Id::new(),
                 message: ServerMessage::Kick(reason.to_string()),
@@ -95,39 +145,86 @@ impl Engine {
         self.clients.iter()
     }
 
-    pub(crate) fn for_each_module<F: Fn(&mut Self, &EntityId, &mut Box<dyn ModuleDyn>)>(&mut self, runner: F) {
+    /// name_path of the mod that added the module to the entity
+    pub(crate) fn module_owner(&self, id: &EntityId, ty: &TypeId) -> Option<Rc<str>> {
+        let e = self.entites.get(id)?;
+        e.module_owners.get(ty).or(e.owner.as_ref()).cloned()
+    }
+
+    /// Runs over all modules, one module type after the other.
+    /// Modules added during iteration are first visited in the next call.
+    pub(crate) fn for_each_module<F: Fn(&mut Self, &EntityId, &mut dyn ModuleDyn)>(&mut self, runner: F) {
         let mut_self_ref_ptr = self as *mut Self;
-        for id in self.entites.keys().cloned().collect::<Vec<_>>() {
-            if let Some(e) = self.entites.get_mut(&id) {
-                for mid in e.modules.keys().cloned().collect::<Vec<_>>() {
-                    if let Some(m) = e.modules.get_mut(&mid) {
-                        runner(unsafe{ &mut *mut_self_ref_ptr }, &id,  m)
+        for ty in self.storage.types() {
+            let storage = match self.storage.storage_mut(&ty) {
+                Some(s) => s as *mut dyn Storage,
+                None => continue
+            };
+            // the column is boxed, so it stays in place even if new columns get added by the runner
+            unsafe {
+                (*storage).lock();
+                for i in 0..(*storage).dense_len() {
+                    if let Some((id, m)) = (*storage).at_dyn(i) {
+                        let engine = &mut *mut_self_ref_ptr;
+                        let owner = engine.module_owner(&id, &ty);
+                        let previous = std::mem::replace(&mut engine.current_mod, owner);
+                        runner(engine, &id, m);
+                        engine.current_mod = previous;
                     }
                 }
-            };
+                (*storage).unlock();
+            }
         }
     }
 
     pub fn for_each_module_of_type<T: Module + Sized + 'static, F: Fn(&mut Self, &EntityId, &mut T)>(&mut self, runner: F) {
         let mut_self_ref_ptr = self as *mut Self;
-        for id in self.entites.keys().cloned().collect::<Vec<_>>() {
-            if let Some(e) = self.entites.get_mut(&id) {
-                if let Value(m) = e.mut_module::<T>() {
-                    runner(unsafe{ &mut *mut_self_ref_ptr }, &id, m)
+        let column = match self.storage.column_mut::<T>() {
+            Some(c) => c as *mut Column<T>,
+            None => return
+        };
+        unsafe {
+            (*column).lock();
+            for i in 0..(*column).dense_len() {
+                if let Some((id, m)) = (*column).at_mut(i) {
+                    let engine = &mut *mut_self_ref_ptr;
+                    let owner = engine.module_owner(&id, &type_to_id::<T>());
+                    let previous = std::mem::replace(&mut engine.current_mod, owner);
+                    runner(engine, &id, m);
+                    engine.current_mod = previous;
                 }
             }
+            (*column).unlock();
         }
     }
 
+    /// Also removes all children of the entity, after the entity's own remove hooks ran.
     pub fn remove_entity(&mut self, id: &EntityId) -> bool {
         let mut_self_ref_ptr = self as *mut Self;
-        if let Some(e) = self.entites.get_mut(id) {
-            for mid in e.modules.keys().cloned().collect::<Vec<_>>() {
-                if let Some(m) = e.modules.get(&mid) {
-                    m.remove_dyn(id, unsafe { &mut *mut_self_ref_ptr})
-                }
-            }
+        let types = match self.entites.get(id) {
+            Some(e) => e.modules.clone(),
+            None => return false
         };
+        // all remove hooks run before any module is dropped
+        for ty in &types {
+            if let Some(m) = self.storage.storage(ty).and_then(|s| s.get_dyn(id)) {
+                m.remove_dyn(id, unsafe { &mut *mut_self_ref_ptr })
+            }
+        }
+        // the hooks might have already removed the entity
+        let Some(e) = self.entites.get_mut(id) else { return false };
+        for child in std::mem::take(&mut e.children) {
+            self.remove_entity(&child);
+        }
+        if let Some(parent) = self.entites.get(id).and_then(|e| e.parent) {
+            if let Some(parent) = self.entites.get_mut(&parent) {
+                parent.children.retain(|c| c != id);
+            }
+        }
+        for ty in &types {
+            self.storage.remove(ty, id);
+        }
+        self.cancel_entity_tasks(id);
         self.entites.remove(id).is_some()
     }
 
@@ -230,20 +327,14 @@ impl Engine {
                 }
             }
         }
-        unsafe {
-            #[allow(deprecated)]
-            let mut arr: [Option<&mut Box<_>>; TT::LEN] = std::mem::uninitialized();
-            for i in 0..ids.len() {
-                let mut_engine = self.mut_handle();
-                if let Some(entity) = mut_engine.entites.get_mut(&ids[i]) {
-                    arr[i] = entity.modules.get_mut(&tids[i])
-                }
-                else {
-                    arr[i] = None;
-                }
-            }
-            TT::opt_boxed_arr_to_tuple_of_nullable_mut(arr)
+        let mut arr: [Option<*mut u8>; TT::LEN] = [None; TT::LEN];
+        for i in 0..ids.len() {
+            let storage = &mut self.storage as *mut ModuleStorage;
+            arr[i] = unsafe { (*storage).storage_mut(&tids[i]) }
+                .and_then(|s| s.get_dyn_mut(&ids[i]))
+                .map(|m| m as *mut _ as *mut u8);
         }
+        unsafe { TT::opt_ptr_arr_to_tuple_of_nullable_mut(arr) }
     }
 
     #[inline]
@@ -0,0 +1,204 @@
+use std::any::type_name;
+use std::marker::PhantomData;
+use aeonetica_engine::{EntityId, TypeId};
+use aeonetica_engine::util::type_to_id;
+use crate::ecs::Engine;
+use crate::ecs::module::Module;
+use crate::ecs::storage::ModuleStorage;
+
+/// A module type accessed by a [`Query`].
+pub struct Access {
+    ty: TypeId,
+    name: &'static str,
+    mutable: bool,
+    /// entities without this module are skipped
+    required: bool
+}
+
+/// The modules of a single entity, matched to the accesses of a query.
+pub struct Fetch<'a> {
+    id: EntityId,
+    slots: Vec<Option<*mut u8>>,
+    marker: PhantomData<&'a mut ModuleStorage>
+}
+
+/// Something that can be fetched from an entity:
+/// - `&T` and `&mut T` for a module `T`
+/// - `Option<Q>` to also match entities that do not have `Q`
+/// - `EntityId` for the id of the entity
+/// - tuples of queries
+///
+/// Fetching the same module type twice in one query panics,
+/// since it would hand out aliasing references.
+pub trait Query {
+    type Item<'a>;
+    /// Number of modules this query accesses.
+    #[doc(hidden)]
+    fn len() -> usize;
+    #[doc(hidden)]
+    fn accesses(accesses: &mut Vec<Access>);
+    /// `index` is the position of the first access of this query in the fetch.
+    #[doc(hidden)]
+    fn fetch<'a>(fetch: &mut Fetch<'a>, index: usize) -> Option<Self::Item<'a>>;
+}
+
+impl<T: Module + 'static> Query for &T {
+    type Item<'a> = &'a T;
+
+    fn len() -> usize { 1 }
+
+    fn accesses(accesses: &mut Vec<Access>) {
+        accesses.push(Access { ty: type_to_id::<T>(), name: type_name::<T>(), mutable: false, required: true })
+    }
+
+    fn fetch<'a>(fetch: &mut Fetch<'a>, index: usize) -> Option<Self::Item<'a>> {
+        fetch.slots[index].take().map(|m| unsafe { &*(m as *const T) })
+    }
+}
+
+impl<T: Module + 'static> Query for &mut T {
+    type Item<'a> = &'a mut T;
+
+    fn len() -> usize { 1 }
+
+    fn accesses(accesses: &mut Vec<Access>) {
+        accesses.push(Access { ty: type_to_id::<T>(), name: type_name::<T>(), mutable: true, required: true })
+    }
+
+    fn fetch<'a>(fetch: &mut Fetch<'a>, index: usize) -> Option<Self::Item<'a>> {
+        fetch.slots[index].take().map(|m| unsafe { &mut *(m as *mut T) })
+    }
+}
+
+impl<Q: Query> Query for Option<Q> {
+    type Item<'a> = Option<Q::Item<'a>>;
+
+    fn len() -> usize { Q::len() }
+
+    fn accesses(accesses: &mut Vec<Access>) {
+        let start = accesses.len();
+        Q::accesses(accesses);
+        accesses[start..].iter_mut().for_each(|a| a.required = false)
+    }
+
+    fn fetch<'a>(fetch: &mut Fetch<'a>, index: usize) -> Option<Self::Item<'a>> {
+        Some(Q::fetch(fetch, index))
+    }
+}
+
+impl Query for EntityId {
+    type Item<'a> = EntityId;
+
+    fn len() -> usize { 0 }
+
+    fn accesses(_accesses: &mut Vec<Access>) {}
+
+    fn fetch<'a>(fetch: &mut Fetch<'a>, _index: usize) -> Option<Self::Item<'a>> {
+        Some(fetch.id)
+    }
+}
+
+macro_rules! query_impls {
+    ($($name: ident)+) => {
+        impl<$($name: Query,)+> Query for ($($name,)+) {
+            type Item<'a> = ($($name::Item<'a>,)+);
+
+            fn len() -> usize { 0 $(+ $name::len())+ }
+
+            fn accesses(accesses: &mut Vec<Access>) {
+                $($name::accesses(accesses);)+
+            }
+
+            #[allow(unused_assignments)]
+            fn fetch<'a>(fetch: &mut Fetch<'a>, index: usize) -> Option<Self::Item<'a>> {
+                let mut index = index;
+                Some(($({
+                    let item = $name::fetch(fetch, index);
+                    index += $name::len();
+                    item?
+                },)+))
+            }
+        }
+    };
+}
+
+query_impls! { A }
+query_impls! { A B }
+query_impls! { A B C }
+query_impls! { A B C D }
+query_impls! { A B C D E }
+query_impls! { A B C D E F }
+query_impls! { A B C D E F G }
+query_impls! { A B C D E F G H }
+
+fn checked_accesses<Q: Query>() -> Vec<Access> {
+    let mut accesses = Vec::with_capacity(Q::len());
+    Q::accesses(&mut accesses);
+    for (i, a) in accesses.iter().enumerate() {
+        if let Some(b) = accesses[i+1..].iter().find(|b| b.ty == a.ty) {
+            panic!("cannot borrow the same type twice in one query: {} ({}) and {} ({})",
+                   a.name, if a.mutable { "mut" } else { "ref" }, b.name, if b.mutable { "mut" } else { "ref" })
+        }
+    }
+    accesses
+}
+
+/// Safety: no other reference to the modules of the entity may exist for `'a`.
+unsafe fn fetch_entity<'a, Q: Query>(accesses: &[Access], storage: *mut ModuleStorage, id: EntityId) -> Option<Q::Item<'a>> {
+    let mut slots = Vec::with_capacity(accesses.len());
+    for a in accesses {
+        let m = unsafe { (*storage).storage_mut(&a.ty) }
+            .and_then(|s| s.get_dyn_mut(&id))
+            .map(|m| m as *mut _ as *mut u8);
+        if a.required && m.is_none() {
+            return None
+        }
+        slots.push(m);
+    }
+    Q::fetch(&mut Fetch { id, slots, marker: PhantomData }, 0)
+}
+
+impl Engine {
+    /// Iterates over all entities that have the modules of the query.
+    /// ```ignore
+    /// for (id, worm, messenger, player) in engine.query::<(EntityId, &mut Worm, &Messenger, Option<&Player>)>() {
+    ///     // ...
+    /// }
+    /// ```
+    pub fn query<Q: Query>(&mut self) -> impl Iterator<Item = Q::Item<'_>> {
+        let accesses = checked_accesses::<Q>();
+        // only entities that have the first required module can match
+        let ids = match accesses.iter().find(|a| a.required) {
+            Some(a) => self.storage.storage(&a.ty).map(|s| s.ids()).unwrap_or_default(),
+            None => self.entites.keys().copied().collect()
+        };
+        let storage = &mut self.storage as *mut ModuleStorage;
+        // every entity is visited once and no module type is fetched twice, so items never alias
+        ids.into_iter().filter_map(move |id| unsafe { fetch_entity::<Q>(&accesses, storage, id) })
+    }
+
+    /// Returns `None` if the entity does not exist or lacks a module of the query.
+    pub fn query_entity<Q: Query>(&mut self, id: &EntityId) -> Option<Q::Item<'_>> {
+        let accesses = checked_accesses::<Q>();
+        if !self.entity_exists(id) {
+            return None
+        }
+        unsafe { fetch_entity::<Q>(&accesses, &mut self.storage, *id) }
+    }
+
+    /// Queries two different entities at once.
+    /// Panics if both ids are the same, use a single query for that instead.
+    pub fn query_two<Q1: Query, Q2: Query>(&mut self, id1: &EntityId, id2: &EntityId) -> Option<(Q1::Item<'_>, Q2::Item<'_>)> {
+        if id1 == id2 {
+            panic!("cannot query the same entity twice: {id1}")
+        }
+        let accesses1 = checked_accesses::<Q1>();
+        let accesses2 = checked_accesses::<Q2>();
+        if !self.entity_exists(id1) || !self.entity_exists(id2) {
+            return None
+        }
+        let storage = &mut self.storage as *mut ModuleStorage;
+        // the entities are distinct, so the modules of both queries do not alias
+        unsafe { Some((fetch_entity::<Q1>(&accesses1, storage, *id1)?, fetch_entity::<Q2>(&accesses2, storage, *id2)?)) }
+    }
+}
@@ -0,0 +1,93 @@
+use std::collections::HashSet;
+use std::marker::PhantomData;
+use aeonetica_engine::{ClientId, EntityId};
+use aeonetica_engine::nanoserde::SerBin;
+use aeonetica_engine::networking::replication::{replication_id, Snapshot, SnapshotMessage};
+use aeonetica_engine::networking::SendMode;
+use aeonetica_engine::util::type_to_id;
+use crate::ecs::Engine;
+use crate::ecs::messaging::Messenger;
+use crate::ecs::module::Module;
+
+/// A module whose state is sent to the clients of the entity's [`Messenger`] automatically.
+///
+/// Add a [`Replication<T>`] module next to it to start replicating.
+/// At the end of every tick the snapshot is compared to the one of the last tick
+/// and the changed fields are sent to all receivers of the messenger.
+/// Clients that were added to the messenger since the last tick receive the full state instead, always reliably.
+///
+/// On the client, register a receiver with `ClientMessenger::register_replicated`.
+pub trait Replicated: Module + Sized + 'static {
+    type Snapshot: Snapshot;
+
+    /// How changes are sent. With [`SendMode::Quick`] a lost change is only corrected
+    /// once that field changes again, so rarely changing state should use [`SendMode::Safe`].
+    const MODE: SendMode = SendMode::Quick;
+
+    fn snapshot(&self) -> Self::Snapshot;
+}
+
+pub struct Replication<T: Replicated> {
+    last: Option<T::Snapshot>,
+    /// receivers that already got the full state
+    synced: HashSet<ClientId>,
+    marker: PhantomData<T>
+}
+
+impl<T: Replicated> Replication<T> {
+    pub fn new() -> Self {
+        Self {
+            last: None,
+            synced: Default::default(),
+            marker: PhantomData
+        }
+    }
+}
+
+impl<T: Replicated> Default for Replication<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Replicated> Module for Replication<T> {
+    fn start(_id: &EntityId, engine: &mut Engine) where Self: Sized {
+        let owner = engine.current_mod.clone();
+        engine.replicators.entry(type_to_id::<T>()).or_insert((replicate::<T>, owner));
+    }
+}
+
+fn replicate<T: Replicated>(engine: &mut Engine) {
+    for (module, replication, messenger) in engine.query::<(&T, &mut Replication<T>, &Messenger)>() {
+        let snapshot = module.snapshot();
+        replication.synced.retain(|c| messenger.has_client(c));
+
+        let delta = replication.last.as_ref().and_then(|last| snapshot.delta(last));
+        if let Some(delta) = delta {
+            let message = SnapshotMessage::Delta(delta).serialize_bin();
+            for client in &replication.synced {
+                messenger.send_raw(client, replication_id::<T::Snapshot>(), message.clone(), T::MODE);
+            }
+        }
+
+        let new = messenger.clients().filter(|c| !replication.synced.contains(c)).copied().collect::<Vec<_>>();
+        if !new.is_empty() {
+            let message = SnapshotMessage::Full(snapshot.serialize_bin()).serialize_bin();
+            for client in new {
+                messenger.send_raw(&client, replication_id::<T::Snapshot>(), message.clone(), SendMode::Safe);
+                replication.synced.insert(client);
+            }
+        }
+        replication.last = Some(snapshot);
+    }
+}
+
+impl Engine {
+    /// Sync point at the end of the tick: sends the changes of all [`Replicated`] modules.
+    pub(crate) fn replicate(&mut self) {
+        let replicators = self.replicators.values().cloned().collect::<Vec<_>>();
+        for (replicate, owner) in replicators {
+            self.as_mod(owner, replicate);
+        }
+    }
+}
@@ -0,0 +1,89 @@
+use std::marker::PhantomData;
+use std::time::Duration;
+use aeonetica_engine::{ClientId, EntityId, Id};
+use aeonetica_engine::nanoserde::SerBin;
+use aeonetica_engine::networking::rpc::{decode_reply, Request, RequestError};
+use aeonetica_engine::networking::SendMode;
+use aeonetica_engine::networking::server_packets::{ServerMessage, ServerPacket};
+use crate::ecs::Engine;
+use crate::ecs::scheduling::{PrivateWaiter, TaskId, WaitFor};
+
+/// A request waiting for its reply.
+/// Dropped once the reply is taken, the waiting task timed out, finished or got cancelled,
+/// or the client left.
+pub(crate) struct PendingRequest {
+    client: ClientId,
+    /// the task that sent the request
+    task: Option<TaskId>,
+    reply: Option<Result<Vec<u8>, String>>
+}
+
+/// A request sent to a client, see [`Engine::request_client`].
+pub struct PendingReply<R: Request> {
+    id: Id,
+    marker: PhantomData<R>
+}
+
+impl<R: Request> PendingReply<R> {
+    /// Resumes the task once the reply arrived or `timeout` passed.
+    pub fn wait(&self, timeout: Duration) -> WaitFor {
+        WaitFor::EventOrTimeout(self.id, timeout, PrivateWaiter)
+    }
+}
+
+impl Engine {
+    /// Sends the request `R` to the client handle of the entity on `client`.
+    /// Inside a task, wait for the reply and then take it:
+    /// ```ignore
+    /// engine.queue_task(move |mut e: &mut Engine| {
+    ///     let reply = e.request_client::<AskName>(&id, &client, ());
+    ///     yield_task!(e, reply.wait(DEFAULT_REQUEST_TIMEOUT));
+    ///     match e.take_reply(&reply) {
+    ///         Ok(name) => log!("client is called {name}"),
+    ///         Err(e) => log!(ERROR, "{e}")
+    ///     }
+    /// });
+    /// ```
+    pub fn request_client<R: Request>(&mut self, entity: &EntityId, client: &ClientId, args: R::Args) -> PendingReply<R> {
+        let id = Id::new();
+        self.pending_replies.insert(id, PendingRequest { client: *client, task: self.tasks.running, reply: None });
+        let _ = self.runtime.ns.borrow().send(client, &ServerPacket {
+            conv_id: id,
+            message: ServerMessage::ModRequest(*entity, R::id(), args.serialize_bin()),
+        }, SendMode::Safe);
+        PendingReply { id, marker: PhantomData }
+    }
+
+    /// The reply to the request, or [`RequestError::Timeout`] if it did not arrive (yet).
+    /// The reply can only be taken once.
+    pub fn take_reply<R: Request>(&mut self, pending: &PendingReply<R>) -> Result<R::Reply, RequestError> {
+        match self.pending_replies.remove(&pending.id) {
+            Some(PendingRequest { reply: Some(reply), .. }) => decode_reply::<R>(reply),
+            _ => Err(RequestError::Timeout)
+        }
+    }
+
+    /// Stores the reply and resumes the task waiting for it.
+    pub(crate) fn receive_reply(&mut self, sender: &ClientId, id: &Id, reply: Result<Vec<u8>, String>) {
+        match self.pending_replies.get_mut(id) {
+            Some(pending) if pending.client == *sender && pending.reply.is_none() => pending.reply = Some(reply),
+            _ => return
+        }
+        self.fire_raw_event(id);
+    }
+
+    /// Drops the request if its reply has not arrived, a late reply is then ignored.
+    pub(crate) fn drop_unanswered_reply(&mut self, id: &Id) {
+        if self.pending_replies.get(id).is_some_and(|p| p.reply.is_none()) {
+            self.pending_replies.remove(id);
+        }
+    }
+
+    pub(crate) fn drop_task_replies(&mut self, task: &TaskId) {
+        self.pending_replies.retain(|_, p| p.task.as_ref() != Some(task));
+    }
+
+    pub(crate) fn drop_client_replies(&mut self, client: &ClientId) {
+        self.pending_replies.retain(|_, p| p.client != *client);
+    }
+}
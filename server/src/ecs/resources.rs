@@ -0,0 +1,108 @@
+use std::any::{Any, type_name};
+use std::rc::Rc;
+use aeonetica_engine::error::{Error, ErrorResult, Fatality};
+use aeonetica_engine::error::builtin::ModError;
+use aeonetica_engine::util::nullable::Nullable;
+use aeonetica_engine::util::type_to_id;
+use crate::ecs::Engine;
+
+pub(crate) struct Resource {
+    value: Box<dyn Any>,
+    /// name_path of the mod that added this resource
+    pub(crate) owner: Option<Rc<str>>
+}
+
+/// Resources are singletons stored on the [`Engine`], keyed by their type.
+/// They replace entities that only exist to hold a single module under a well known tag.
+///
+/// Services are resources that are looked up through a trait,
+/// so that mods do not need to know the concrete type of another mod:
+/// ```ignore
+/// pub trait Players {
+///     fn player_of(&self, client: &ClientId) -> Option<EntityId>;
+/// }
+///
+/// // player mod, in ServerMod::start
+/// engine.provide_service::<dyn Players>(Box::new(PlayerRegistry::default()));
+///
+/// // any other mod
+/// let player = engine.service::<dyn Players>()?.player_of(client);
+/// ```
+impl Engine {
+    /// Fails if a resource of that type already exists.
+    pub fn add_resource<T: 'static>(&mut self, resource: T) -> bool {
+        if let std::collections::hash_map::Entry::Vacant(e) = self.resources.entry(type_to_id::<T>()) {
+            e.insert(Resource {
+                value: Box::new(resource),
+                owner: self.current_mod.clone()
+            });
+            true
+        } else {
+            false
+        }
+    }
+
+    #[inline]
+    pub fn add_default_resource<T: Default + 'static>(&mut self) -> bool {
+        self.add_resource(T::default())
+    }
+
+    #[inline]
+    pub fn has_resource<T: 'static>(&self) -> bool {
+        self.resources.contains_key(&type_to_id::<T>())
+    }
+
+    #[inline]
+    pub fn get_resource<T: 'static>(&self) -> Nullable<&T> {
+        self.resources.get(&type_to_id::<T>())?.value.downcast_ref().into()
+    }
+
+    #[inline]
+    pub fn mut_resource<T: 'static>(&mut self) -> Nullable<&mut T> {
+        self.resources.get_mut(&type_to_id::<T>())?.value.downcast_mut().into()
+    }
+
+    /// Like [`Engine::get_resource`], but with an error that names the missing type.
+    pub fn resource<T: 'static>(&self) -> ErrorResult<&T> {
+        self.get_resource::<T>().option().ok_or_else(|| missing::<T>("resource"))
+    }
+
+    /// Like [`Engine::mut_resource`], but with an error that names the missing type.
+    pub fn resource_mut<T: 'static>(&mut self) -> ErrorResult<&mut T> {
+        self.mut_resource::<T>().option().ok_or_else(|| missing::<T>("resource"))
+    }
+
+    pub fn remove_resource<T: 'static>(&mut self) -> Option<T> {
+        let resource = self.resources.remove(&type_to_id::<T>())?;
+        resource.value.downcast().ok().map(|r| *r)
+    }
+
+    /// Registers `service` as the implementation of `S`, usually a trait object type.
+    /// Fails if the service is already provided.
+    #[inline]
+    pub fn provide_service<S: ?Sized + 'static>(&mut self, service: Box<S>) -> bool {
+        self.add_resource(service)
+    }
+
+    #[inline]
+    pub fn has_service<S: ?Sized + 'static>(&self) -> bool {
+        self.has_resource::<Box<S>>()
+    }
+
+    pub fn service<S: ?Sized + 'static>(&self) -> ErrorResult<&S> {
+        self.get_resource::<Box<S>>().option().map(|s| &**s).ok_or_else(|| missing::<S>("service"))
+    }
+
+    pub fn service_mut<S: ?Sized + 'static>(&mut self) -> ErrorResult<&mut S> {
+        self.mut_resource::<Box<S>>().option().map(|s| &mut **s).ok_or_else(|| missing::<S>("service"))
+    }
+
+    #[inline]
+    pub fn remove_service<S: ?Sized + 'static>(&mut self) -> Option<Box<S>> {
+        self.remove_resource::<Box<S>>()
+    }
+}
+
+fn missing<T: ?Sized>(kind: &str) -> Box<Error> {
+    Error::new(ModError(format!("no {kind} {} registered, is the mod providing it loaded and started before this one?", type_name::<T>())), Fatality::DEFAULT, false)
+}
@@ -1,18 +1,26 @@
 use std::cmp::Ordering;
 use std::collections::{BinaryHeap};
-use std::collections::hash_map::Entry;
 use std::marker::PhantomData;
 use std::ops::{Coroutine, CoroutineState};
-use aeonetica_engine::TypeId;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+use aeonetica_engine::{EntityId, Id, TypeId};
 use aeonetica_engine::util::id_map::IdMap;
 use aeonetica_engine::util::type_to_id;
 use crate::ecs::Engine;
 
 pub trait TaskFunc = for<'a> Coroutine<&'a mut Engine, Yield = Yielder<'a>, Return = ()>;
 
+pub type TaskId = Id;
+
 pub(crate) struct Task {
+    id: TaskId,
     timestamp: usize,
+    /// only set while waiting for an event with a timeout
+    deadline: Option<Instant>,
     func: Box<dyn TaskFunc>,
+    /// name_path of the mod that queued this task
+    owner: Option<Rc<str>>
 }
 
 impl PartialOrd for Task {
@@ -27,14 +35,81 @@ impl PartialEq for Task {
     fn eq(&self, other: &Self) -> bool { self.timestamp == other.timestamp }
 }
 
-pub trait Event {}
+/// Events carry data and are fired with [`Engine::fire`] or [`Engine::fire_now`].
+/// See [`crate::ecs::events::EventListener`] for how to listen to them.
+pub trait Event: 'static {
+    /// Whether listeners may cancel the event.
+    const CANCELLABLE: bool = false;
+}
 
 impl Eq for Task {}
 
+/// Conditions that are checked every tick.
+enum Poll {
+    Time(Instant),
+    Until(Box<dyn Fn(&Engine) -> bool>)
+}
+
 #[derive(Default)]
 pub(crate) struct TaskQueue {
     pub(crate) heap: BinaryHeap<Task>,
-    pub(crate) event_queue: IdMap<Vec<Box<dyn TaskFunc>>>
+    pub(crate) event_queue: IdMap<Vec<Task>>,
+    polled: Vec<(Poll, Task)>,
+    /// all tasks that are neither finished nor cancelled, and the entities owning them
+    alive: IdMap<Option<EntityId>>,
+    /// the task currently being resumed
+    pub(crate) running: Option<TaskId>
+}
+
+impl TaskQueue {
+    /// Drops all tasks queued by the given mod.
+    pub(crate) fn remove_owned(&mut self, owner: &Rc<str>) {
+        self.remove_where(|t| t.owner.as_ref() == Some(owner));
+    }
+
+    fn remove_where(&mut self, f: impl Fn(&Task) -> bool) {
+        let alive = &mut self.alive;
+        let mut keep = |t: &Task| if f(t) {
+            alive.remove(&t.id);
+            false
+        } else {
+            true
+        };
+        self.heap.retain(&mut keep);
+        for tasks in self.event_queue.values_mut() {
+            tasks.retain(&mut keep);
+        }
+        self.polled.retain(|(_, t)| keep(t));
+    }
+
+    fn push_event(&mut self, event: EventId, task: Task) {
+        self.event_queue.entry(event).or_default().push(task);
+    }
+}
+
+/// Refers to a queued task.
+/// The task itself is owned by the [`Engine`], dropping the handle does not cancel it.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TaskHandle {
+    id: TaskId
+}
+
+impl TaskHandle {
+    pub fn id(&self) -> TaskId {
+        self.id
+    }
+
+    /// Drops the task without resuming it again.
+    /// Returns `false` if it already finished or got cancelled.
+    /// A task may cancel itself, it is dropped at its next yield.
+    pub fn cancel(&self, engine: &mut Engine) -> bool {
+        engine.cancel_task(&self.id)
+    }
+
+    /// `true` once the task completed or got cancelled.
+    pub fn is_finished(&self, engine: &Engine) -> bool {
+        !engine.tasks.alive.contains_key(&self.id)
+    }
 }
 
 pub type EventId = TypeId;
@@ -43,17 +118,40 @@ pub struct PrivateWaiter;
 
 pub enum WaitFor {
     Ticks(usize, PrivateWaiter),
-    Event(EventId, PrivateWaiter)
+    Event(EventId, PrivateWaiter),
+    Duration(Duration, PrivateWaiter),
+    Until(Box<dyn Fn(&Engine) -> bool>, PrivateWaiter),
+    EventOrTimeout(EventId, Duration, PrivateWaiter)
 }
 
 impl WaitFor {
     pub fn ticks(ticks: usize) -> Self {
         WaitFor::Ticks(ticks, PrivateWaiter)
     }
+
+    pub fn next_tick() -> Self {
+        WaitFor::Ticks(1, PrivateWaiter)
+    }
     
     pub fn event<T: Event>() -> Self {
         WaitFor::Event(type_to_id::<T>(), PrivateWaiter)
     }
+
+    /// Wall-clock time, checked once per tick.
+    pub fn duration(duration: Duration) -> Self {
+        WaitFor::Duration(duration, PrivateWaiter)
+    }
+
+    /// Resumes the task in the first tick in which `predicate` returns `true`, checked once per tick.
+    pub fn until<F: Fn(&Engine) -> bool + 'static>(predicate: F) -> Self {
+        WaitFor::Until(Box::new(predicate), PrivateWaiter)
+    }
+
+    /// Resumes the task on the next event of type `T` or once `timeout` has passed.
+    /// On timeout, [`Engine::received_event`] is null.
+    pub fn event_or_timeout<T: Event>(timeout: Duration) -> Self {
+        WaitFor::EventOrTimeout(type_to_id::<T>(), timeout, PrivateWaiter)
+    }
 }
 
 pub struct Yielder<'a>(PrivateYielder, PhantomData<&'a ()>, WaitFor);
@@ -72,26 +170,65 @@ impl Engine {
         Yielder(PrivateYielder, PhantomData, waiter)
     }
 
-    pub fn queue_task<'a>(&mut self, task: impl Coroutine<&'a mut Engine, Yield = Yielder<'a>, Return = ()> + 'static) {
+    /// Queues a task to be run this tick.
+    pub fn queue_task<'a>(&mut self, task: impl Coroutine<&'a mut Engine, Yield = Yielder<'a>, Return = ()> + 'static) -> TaskHandle {
+        self.queue_task_with_entity(None, task)
+    }
+
+    /// Like [`Engine::queue_task`], but the task is cancelled when the entity gets removed.
+    pub fn queue_entity_task<'a>(&mut self, entity: &EntityId, task: impl Coroutine<&'a mut Engine, Yield = Yielder<'a>, Return = ()> + 'static) -> TaskHandle {
+        self.queue_task_with_entity(Some(*entity), task)
+    }
+
+    fn queue_task_with_entity<'a>(&mut self, entity: Option<EntityId>, task: impl Coroutine<&'a mut Engine, Yield = Yielder<'a>, Return = ()> + 'static) -> TaskHandle {
         let taskfn: Box<dyn Coroutine<&'a mut Engine, Yield = Yielder<'a>, Return = ()>> = Box::new(*Box::new(task));
+        let id = Id::new();
+        self.tasks.alive.insert(id, entity);
         self.tasks.heap.push(Task {
+            id,
             timestamp: self.tick,
-            func: unsafe { std::mem::transmute::<_, _>(taskfn) }
+            deadline: None,
+            func: unsafe { std::mem::transmute::<_, _>(taskfn) },
+            owner: self.current_mod.clone()
         });
+        TaskHandle { id }
     }
 
-    pub fn fire_event<E: Event>(&mut self) {
-        if let Some(q) = self.tasks.event_queue.remove(&type_to_id::<E>()) {
-            for task in q {
-                self.run_task(task);
-            }
+    /// See [`TaskHandle::cancel`].
+    pub fn cancel_task(&mut self, id: &TaskId) -> bool {
+        if self.tasks.alive.remove(id).is_none() {
+            return false
         }
+        // a running task is not in the queue, it is dropped once it yields
+        self.tasks.remove_where(|t| t.id == *id);
+        self.drop_task_replies(id);
+        true
+    }
+
+    /// Cancels all tasks owned by the entity.
+    pub(crate) fn cancel_entity_tasks(&mut self, entity: &EntityId) {
+        let tasks = self.tasks.alive.iter()
+            .filter(|(_, e)| e.as_ref() == Some(entity))
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>();
+        for id in tasks {
+            self.cancel_task(&id);
+        }
+    }
+
+    /// Resumes all tasks waiting for `E` without an event value.
+    /// Use [`Engine::fire`] to fire an event that carries data.
+    pub fn fire_event<E: Event>(&mut self) {
+        self.fire_raw_event(&type_to_id::<E>())
     }
 
     pub fn fire_raw_event(&mut self, id: &EventId) {
         if let Some(q) = self.tasks.event_queue.remove(id) {
             for task in q {
-                self.run_task(task);
+                // an earlier task might have cancelled it
+                if self.tasks.alive.contains_key(&task.id) {
+                    self.run_task(task);
+                }
             }
         }
     }
@@ -99,27 +236,77 @@ impl Engine {
     pub(crate) fn run_tasks(&mut self) {
         while self.tasks.heap.peek().map(|t| t.timestamp <= self.tick).unwrap_or(false) {
             let task = self.tasks.heap.pop().unwrap();
-            self.run_task(task.func);
+            self.run_task(task);
+        }
+
+        let now = Instant::now();
+        for (poll, task) in std::mem::take(&mut self.tasks.polled) {
+            let ready = match &poll {
+                Poll::Time(at) => *at <= now,
+                Poll::Until(predicate) => predicate(self)
+            };
+            if ready {
+                // the predicate might have cancelled the task
+                if self.tasks.alive.contains_key(&task.id) {
+                    self.run_task(task);
+                }
+            } else {
+                self.tasks.polled.push((poll, task));
+            }
+        }
+
+        let mut timed_out = vec![];
+        for (event, tasks) in self.tasks.event_queue.iter_mut() {
+            let (expired, waiting): (Vec<_>, Vec<_>) = std::mem::take(tasks).into_iter()
+                .partition(|t| t.deadline.map(|d| d <= now).unwrap_or(false));
+            *tasks = waiting;
+            timed_out.extend(expired.into_iter().map(|t| (*event, t)));
+        }
+        for (event, task) in timed_out {
+            // the event might be the reply to a request
+            self.drop_unanswered_reply(&event);
+            if self.tasks.alive.contains_key(&task.id) {
+                self.run_task(task);
+            }
         }
     }
 
-    pub(crate) fn run_task(&mut self, f: Box<dyn TaskFunc>) {
-        let mut fnpin = Box::into_pin(f);
-        match fnpin.as_mut().resume(self) {
+    pub(crate) fn run_task(&mut self, task: Task) {
+        let mut fnpin = Box::into_pin(task.func);
+        let previous = std::mem::replace(&mut self.current_mod, task.owner.clone());
+        let previous_task = self.tasks.running.replace(task.id);
+        let state = fnpin.as_mut().resume(self);
+        self.tasks.running = previous_task;
+        self.current_mod = previous;
+        if !self.tasks.alive.contains_key(&task.id) {
+            // cancelled while running
+            return
+        }
+        let mut requeued = Task {
+            id: task.id,
+            timestamp: self.tick,
+            deadline: None,
+            func: Box::from(fnpin),
+            owner: task.owner
+        };
+        match state {
             CoroutineState::Yielded(yielder) => match yielder.2 {
-                WaitFor::Ticks(t, _) => self.tasks.heap.push(Task {
-                    timestamp: { self.tick + t },
-                    func: Box::from(fnpin),
-                }),
-                WaitFor::Event(event, _) => {
-                    if let Entry::Vacant(e) = self.tasks.event_queue.entry(event) {
-                        e.insert(vec![Box::from(fnpin)]);
-                    } else {
-                        self.tasks.event_queue.get_mut(&event).unwrap().push(Box::from(fnpin));
-                    }
+                WaitFor::Ticks(t, _) => {
+                    requeued.timestamp += t;
+                    self.tasks.heap.push(requeued)
+                }
+                WaitFor::Event(event, _) => self.tasks.push_event(event, requeued),
+                WaitFor::Duration(duration, _) => self.tasks.polled.push((Poll::Time(Instant::now() + duration), requeued)),
+                WaitFor::Until(predicate, _) => self.tasks.polled.push((Poll::Until(predicate), requeued)),
+                WaitFor::EventOrTimeout(event, timeout, _) => {
+                    requeued.deadline = Some(Instant::now() + timeout);
+                    self.tasks.push_event(event, requeued)
                 }
             }
-            CoroutineState::Complete(_) => (),
+            CoroutineState::Complete(_) => {
+                self.tasks.alive.remove(&requeued.id);
+                self.drop_task_replies(&requeued.id);
+            }
         }
     }
 }
@@ -0,0 +1,266 @@
+use std::any::Any;
+use std::rc::Rc;
+use aeonetica_engine::{EntityId, TypeId};
+use aeonetica_engine::util::id_map::IdMap;
+use aeonetica_engine::util::type_to_id;
+use crate::ecs::module::{Module, ModuleDyn};
+
+#[derive(Copy, Clone)]
+enum Slot {
+    Dense(usize),
+    /// inserted while the column was locked
+    Pending(usize)
+}
+
+/// Sparse set holding all modules of one type.
+///
+/// Modules are boxed, so inserting never moves the existing ones: handles and entities
+/// point into the column while mods keep adding modules.
+/// While a column is locked (it is being iterated), modules are never moved:
+/// insertions are kept aside and removals only leave a tombstone.
+/// Both are applied once the last lock is released.
+pub(crate) struct Column<T> {
+    ids: Vec<Option<EntityId>>,
+    modules: Vec<Box<T>>,
+    pending: Vec<(Option<EntityId>, Box<T>)>,
+    slots: IdMap<Slot>,
+    locks: usize,
+    tombstones: usize
+}
+
+impl<T> Column<T> {
+    pub(crate) fn new() -> Self {
+        Self {
+            ids: vec![],
+            modules: vec![],
+            pending: vec![],
+            slots: Default::default(),
+            locks: 0,
+            tombstones: 0
+        }
+    }
+
+    pub(crate) fn insert(&mut self, id: EntityId, module: T) -> bool {
+        if self.slots.contains_key(&id) {
+            return false
+        }
+        if self.locks > 0 {
+            self.pending.push((Some(id), Box::new(module)));
+            self.slots.insert(id, Slot::Pending(self.pending.len() - 1));
+        } else {
+            self.ids.push(Some(id));
+            self.modules.push(Box::new(module));
+            self.slots.insert(id, Slot::Dense(self.modules.len() - 1));
+        }
+        true
+    }
+
+    pub(crate) fn get(&self, id: &EntityId) -> Option<&T> {
+        match self.slots.get(id)? {
+            Slot::Dense(i) => Some(&*self.modules[*i]),
+            Slot::Pending(i) => Some(&self.pending[*i].1)
+        }
+    }
+
+    pub(crate) fn get_mut(&mut self, id: &EntityId) -> Option<&mut T> {
+        match *self.slots.get(id)? {
+            // as_mut_ptr does not reborrow the whole vec,
+            // so references to other modules of this column stay valid
+            Slot::Dense(i) => Some(unsafe { &mut **self.modules.as_mut_ptr().add(i) }),
+            Slot::Pending(i) => Some(&mut self.pending[i].1)
+        }
+    }
+
+    /// Number of dense slots, including tombstones.
+    /// Does not change while the column is locked.
+    #[inline]
+    pub(crate) fn dense_len(&self) -> usize {
+        self.ids.len()
+    }
+
+    /// Returns `None` if the module at `index` got removed.
+    #[inline]
+    pub(crate) fn at_mut(&mut self, index: usize) -> Option<(EntityId, &mut T)> {
+        let id = self.ids[index]?;
+        Some((id, unsafe { &mut **self.modules.as_mut_ptr().add(index) }))
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (&EntityId, &T)> {
+        self.ids.iter().zip(&self.modules).filter_map(|(id, m)| Some((id.as_ref()?, &**m)))
+            .chain(self.pending.iter().filter_map(|(id, m)| Some((id.as_ref()?, &**m))))
+    }
+
+    #[inline]
+    pub(crate) fn lock(&mut self) {
+        self.locks += 1;
+    }
+
+    pub(crate) fn unlock(&mut self) {
+        self.locks -= 1;
+        if self.locks > 0 {
+            return
+        }
+        if self.tombstones > 0 {
+            for i in (0..self.ids.len()).rev() {
+                if self.ids[i].is_none() {
+                    self.swap_remove(i);
+                }
+            }
+            self.tombstones = 0;
+        }
+        for (id, module) in std::mem::take(&mut self.pending) {
+            if let Some(id) = id {
+                self.ids.push(Some(id));
+                self.modules.push(module);
+                self.slots.insert(id, Slot::Dense(self.modules.len() - 1));
+            }
+        }
+    }
+
+    /// Drops the module. If the column is locked, the module is only dropped once it gets unlocked.
+    pub(crate) fn remove(&mut self, id: &EntityId) -> bool {
+        let Some(slot) = self.slots.remove(id) else { return false };
+        match slot {
+            Slot::Dense(i) if self.locks > 0 => {
+                self.ids[i] = None;
+                self.tombstones += 1;
+            }
+            Slot::Dense(i) => self.swap_remove(i),
+            Slot::Pending(i) => self.pending[i].0 = None
+        }
+        true
+    }
+
+    fn swap_remove(&mut self, index: usize) {
+        self.ids.swap_remove(index);
+        self.modules.swap_remove(index);
+        if let Some(Some(moved)) = self.ids.get(index) {
+            self.slots.insert(*moved, Slot::Dense(index));
+        }
+    }
+}
+
+/// Type erased access to a [`Column`].
+pub(crate) trait Storage {
+    fn contains(&self, id: &EntityId) -> bool;
+    fn get_dyn(&self, id: &EntityId) -> Option<&dyn ModuleDyn>;
+    fn get_dyn_mut(&mut self, id: &EntityId) -> Option<&mut dyn ModuleDyn>;
+    fn remove(&mut self, id: &EntityId) -> bool;
+    fn ids(&self) -> Vec<EntityId>;
+    fn dense_len(&self) -> usize;
+    fn at_dyn(&mut self, index: usize) -> Option<(EntityId, &mut dyn ModuleDyn)>;
+    fn lock(&mut self);
+    fn unlock(&mut self);
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<T: Module + 'static> Storage for Column<T> {
+    fn contains(&self, id: &EntityId) -> bool {
+        self.slots.contains_key(id)
+    }
+
+    fn get_dyn(&self, id: &EntityId) -> Option<&dyn ModuleDyn> {
+        self.get(id).map(|m| m as &dyn ModuleDyn)
+    }
+
+    fn get_dyn_mut(&mut self, id: &EntityId) -> Option<&mut dyn ModuleDyn> {
+        self.get_mut(id).map(|m| m as &mut dyn ModuleDyn)
+    }
+
+    fn remove(&mut self, id: &EntityId) -> bool {
+        Column::remove(self, id)
+    }
+
+    fn ids(&self) -> Vec<EntityId> {
+        self.iter().map(|(id, _)| *id).collect()
+    }
+
+    fn dense_len(&self) -> usize {
+        Column::dense_len(self)
+    }
+
+    fn at_dyn(&mut self, index: usize) -> Option<(EntityId, &mut dyn ModuleDyn)> {
+        self.at_mut(index).map(|(id, m)| (id, m as &mut dyn ModuleDyn))
+    }
+
+    fn lock(&mut self) {
+        Column::lock(self)
+    }
+
+    fn unlock(&mut self) {
+        Column::unlock(self)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+pub(crate) struct StorageBox {
+    pub(crate) storage: Box<dyn Storage>,
+    /// name_path of the mod that created the column.
+    /// The column's vtable lives in that mod's library, so it has to go when the mod gets unloaded.
+    pub(crate) owner: Option<Rc<str>>
+}
+
+/// All modules of all entities, one [`Column`] per module type.
+#[derive(Default)]
+pub(crate) struct ModuleStorage {
+    pub(crate) columns: IdMap<StorageBox>
+}
+
+impl ModuleStorage {
+    pub(crate) fn insert<T: Module + 'static>(&mut self, id: EntityId, module: T, owner: &Option<Rc<str>>) -> bool {
+        self.columns.entry(type_to_id::<T>())
+            .or_insert_with(|| StorageBox {
+                storage: Box::new(Column::<T>::new()),
+                owner: owner.clone()
+            })
+            .storage.as_any_mut().downcast_mut::<Column<T>>().unwrap()
+            .insert(id, module)
+    }
+
+    #[inline]
+    pub(crate) fn column<T: Module + 'static>(&self) -> Option<&Column<T>> {
+        self.columns.get(&type_to_id::<T>())?.storage.as_any().downcast_ref()
+    }
+
+    #[inline]
+    pub(crate) fn column_mut<T: Module + 'static>(&mut self) -> Option<&mut Column<T>> {
+        self.columns.get_mut(&type_to_id::<T>())?.storage.as_any_mut().downcast_mut()
+    }
+
+    #[inline]
+    pub(crate) fn get<T: Module + 'static>(&self, id: &EntityId) -> Option<&T> {
+        self.column::<T>()?.get(id)
+    }
+
+    #[inline]
+    pub(crate) fn get_mut<T: Module + 'static>(&mut self, id: &EntityId) -> Option<&mut T> {
+        self.column_mut::<T>()?.get_mut(id)
+    }
+
+    #[inline]
+    pub(crate) fn storage(&self, ty: &TypeId) -> Option<&dyn Storage> {
+        self.columns.get(ty).map(|s| &*s.storage)
+    }
+
+    #[inline]
+    pub(crate) fn storage_mut(&mut self, ty: &TypeId) -> Option<&mut (dyn Storage + 'static)> {
+        self.columns.get_mut(ty).map(|s| &mut *s.storage)
+    }
+
+    #[inline]
+    pub(crate) fn remove(&mut self, ty: &TypeId, id: &EntityId) -> bool {
+        self.storage_mut(ty).map(|s| s.remove(id)).unwrap_or(false)
+    }
+
+    pub(crate) fn types(&self) -> Vec<TypeId> {
+        self.columns.keys().copied().collect()
+    }
+}
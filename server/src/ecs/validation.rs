@@ -0,0 +1,132 @@
+use std::collections::HashMap;
+use aeonetica_engine::{ClientId, EntityId, log};
+use aeonetica_engine::nanoserde::{DeBin, SerBin};
+use aeonetica_engine::networking::rpc::{decode_args, register_rpc, Rpc};
+use aeonetica_engine::util::type_to_id;
+use crate::ecs::Engine;
+use crate::ecs::messaging::Messenger;
+
+/// What happens when a client sends something that fails validation.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ViolationPolicy {
+    /// Only log the violation, the message is handled as usual.
+    Log,
+    /// Drop the message, the receiver keeps or restores the server's state.
+    RubberBand,
+    /// Drop the message and kick the client.
+    Kick
+}
+
+#[derive(Debug)]
+pub struct Violation {
+    /// name of the failed check, used to look up its policy
+    pub check: &'static str,
+    pub reason: String
+}
+
+impl Violation {
+    pub fn new<S: Into<String>>(check: &'static str, reason: S) -> Self {
+        Self { check, reason: reason.into() }
+    }
+}
+
+pub type Validation = Result<(), Violation>;
+
+/// Resource configuring the [`ViolationPolicy`] per check.
+/// Without it, every violation is rubber-banded.
+pub struct ViolationPolicies {
+    pub default: ViolationPolicy,
+    checks: HashMap<String, ViolationPolicy>
+}
+
+impl Default for ViolationPolicies {
+    fn default() -> Self {
+        Self {
+            default: ViolationPolicy::RubberBand,
+            checks: Default::default()
+        }
+    }
+}
+
+impl ViolationPolicies {
+    pub fn set(&mut self, check: &str, policy: ViolationPolicy) {
+        self.checks.insert(check.to_string(), policy);
+    }
+
+    pub fn get(&self, check: &str) -> ViolationPolicy {
+        self.checks.get(check).copied().unwrap_or(self.default)
+    }
+}
+
+/// Fails if `value` is not within `min..=max`.
+pub fn check_range(check: &'static str, value: f32, min: f32, max: f32) -> Validation {
+    if (min..=max).contains(&value) {
+        Ok(())
+    } else {
+        Err(Violation::new(check, format!("{value} is not within {min}..={max}")))
+    }
+}
+
+impl Engine {
+    /// Logs the violation and applies its policy.
+    /// Returns the policy, so the caller knows whether to drop the message.
+    ///
+    /// Kicks are applied at the next sync point, since kicking removes the client's entities,
+    /// possibly including the messenger whose receiver is reporting the violation.
+    pub fn report_violation(&mut self, client: &ClientId, violation: Violation) -> ViolationPolicy {
+        let policy = self.get_resource::<ViolationPolicies>().map_or(ViolationPolicy::RubberBand, |p| p.get(violation.check));
+        log!(WARN, "client {client} failed check {}: {} ({policy:?})", violation.check, violation.reason);
+        if policy == ViolationPolicy::Kick {
+            let (client, reason) = (*client, format!("failed check {}", violation.check));
+            self.commands().add(move |engine| { engine.kick_client(&client, &reason); });
+        }
+        policy
+    }
+}
+
+impl Messenger {
+    /// Like [`Messenger::register_receiver`], but messages are checked by `validate` first.
+    /// Failed messages are reported with [`Engine::report_violation`] and dropped,
+    /// unless the policy of the check is [`ViolationPolicy::Log`].
+    pub fn register_validated_receiver<F, V, M>(&mut self, f: F, validate: V)
+        where F: Fn(&EntityId, &mut Engine, &ClientId, M) + 'static,
+              V: Fn(&EntityId, &Engine, &ClientId, &M) -> Validation + 'static,
+              M: SerBin + DeBin {
+        let m = move |id: &EntityId, engine: &mut Engine, sender: &ClientId, data: &Vec<u8>| {
+            let message = match M::deserialize_bin(data) {
+                Ok(message) => message,
+                Err(e) => {
+                    engine.report_violation(sender, Violation::new("message", format!("malformed message: {e:?}")));
+                    return
+                }
+            };
+            if let Err(violation) = validate(id, engine, sender, &message) {
+                if engine.report_violation(sender, violation) != ViolationPolicy::Log {
+                    return
+                }
+            }
+            f(id, engine, sender, message)
+        };
+        self.insert_receiver(type_to_id::<F>(), Box::new(m));
+    }
+
+    /// Like [`Messenger::on`], but calls are checked by `validate` first,
+    /// see [`Messenger::register_validated_receiver`].
+    pub fn on_validated<R: Rpc>(&mut self, f: impl Fn(&EntityId, &mut Engine, &ClientId, R::Args) + 'static,
+                                validate: impl Fn(&EntityId, &Engine, &ClientId, &R::Args) -> Validation + 'static) {
+        register_rpc::<R>();
+        let m = move |id: &EntityId, engine: &mut Engine, sender: &ClientId, data: &Vec<u8>| {
+            let Some(args) = decode_args::<R>(data) else {
+                engine.report_violation(sender, Violation::new("message", format!("malformed call of rpc {}", R::NAME)));
+                return
+            };
+            if let Err(violation) = validate(id, engine, sender, &args) {
+                if engine.report_violation(sender, violation) != ViolationPolicy::Log {
+                    return
+                }
+            }
+            f(id, engine, sender, args)
+        };
+        self.insert_receiver(R::id(), Box::new(m));
+    }
+}
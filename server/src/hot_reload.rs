@@ -0,0 +1,202 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::rc::Rc;
+use std::time::{Duration, Instant, SystemTime};
+use aeonetica_engine::{EntityId, Id, log, TypeId};
+use aeonetica_engine::error::{Error, ErrorResult, Fatality};
+use aeonetica_engine::error::builtin::ModError;
+use aeonetica_engine::manifest::resolve_load_order;
+use aeonetica_engine::networking::SendMode;
+use aeonetica_engine::networking::server_packets::{ServerMessage, ServerPacket};
+use aeonetica_engine::util::nullable::Nullable::Value;
+use aeonetica_engine::util::unzip_archive;
+use crate::ecs::Engine;
+use crate::ecs::events::ConnectionListener;
+use crate::ecs::messaging::Messenger;
+use crate::server_runtime::{client_zip_info, load_mod, mod_dir, mod_zip, unpack_mod};
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+/// A changed zip is only picked up once it has not been written to for this long,
+/// so that a mod does not get reloaded from a half written archive.
+const SETTLE_TIME: Duration = Duration::from_millis(500);
+/// New versions of mods are unpacked and loaded here before they replace the running version.
+/// Every reload gets its own directory, since a library cannot be loaded again from the path of a loaded one.
+const RELOAD_DIR: &str = "runtime/reload";
+
+/// Watches `mods/*.zip` of all mods in the modstack for changes.
+pub(crate) struct ModWatcher {
+    last_check: Instant,
+    modified: HashMap<String, SystemTime>
+}
+
+fn modified(name_path: &str) -> Option<SystemTime> {
+    let (path, _) = name_path.split_once(':').unwrap();
+    std::fs::metadata(mod_zip(path)).and_then(|m| m.modified()).ok()
+}
+
+impl ModWatcher {
+    pub(crate) fn new(load_order: &[String]) -> Self {
+        // left over from the last run, none of these libraries are loaded yet
+        let _ = std::fs::remove_dir_all(RELOAD_DIR);
+        Self {
+            last_check: Instant::now(),
+            modified: load_order.iter().filter_map(|name_path| Some((name_path.clone(), modified(name_path)?))).collect()
+        }
+    }
+
+    /// Returns the name_paths of all mods whose zip changed since the last call.
+    fn changed_mods(&mut self) -> Vec<String> {
+        if self.last_check.elapsed() < CHECK_INTERVAL {
+            return vec![]
+        }
+        self.last_check = Instant::now();
+        let now = SystemTime::now();
+        let mut changed = vec![];
+        for (name_path, last) in self.modified.iter_mut() {
+            if let Some(m) = modified(name_path) {
+                if m != *last && now.duration_since(m).map(|d| d >= SETTLE_TIME).unwrap_or(false) {
+                    *last = m;
+                    changed.push(name_path.clone());
+                }
+            }
+        }
+        changed
+    }
+}
+
+impl Engine {
+    /// Reloads all mods whose zip changed. Does nothing if the server was not started with `--hot-reload`.
+    pub(crate) fn reload_changed_mods(&mut self) {
+        let mut changed = match self.runtime.mod_watcher.as_mut() {
+            Some(watcher) => watcher.changed_mods(),
+            None => return
+        };
+        changed.sort_by_key(|name_path| self.runtime.load_order.iter().position(|n| n == name_path));
+        for name_path in changed {
+            log!("mod {name_path} changed, reloading...");
+            match self.reload_mod(&name_path) {
+                Ok(()) => log!("reloaded mod {name_path}"),
+                Err(mut e) => {
+                    e.add_info(format!("could not reload mod {name_path}"));
+                    e.log()
+                }
+            }
+        }
+    }
+
+    /// Loads the new version of the mod, then removes everything the old one created,
+    /// swaps the libraries and starts the new one.
+    /// If the new version cannot be loaded, the old one keeps running untouched.
+    ///
+    /// Clients are told to re-download the mod before the new instance is started,
+    /// so that client handles of the new instance are created from the new client library.
+    fn reload_mod(&mut self, name_path: &str) -> ErrorResult<()> {
+        let (path, _) = name_path.split_once(':').unwrap();
+        let staged = format!("{RELOAD_DIR}/{}/{path}", self.tick);
+        let manifest = unpack_mod(name_path, &staged, &self.runtime.supported_mod_targets)?;
+        let mut manifests = self.runtime.manifests.clone();
+        manifests.insert(name_path.to_string(), manifest);
+        if resolve_load_order(&manifests)? != self.runtime.load_order {
+            return Err(Error::new(ModError("mod dependencies changed the load order, restart the server to apply".to_string()), Fatality::DEFAULT, false))
+        }
+        let mut m = load_mod(name_path, &staged)?;
+        m.init(&self.runtime.mod_profile.modstack[name_path]);
+        // clients download the client zips from the mod's usual directory,
+        // the server library there is not touched since it lives in the inner server zip
+        unzip_archive(File::open(mod_zip(path))?, mod_dir(path))?;
+        self.runtime.manifests = manifests;
+
+        if let Some(index) = self.runtime.loaded_mods.iter().position(|m| &*m.name_path == name_path) {
+            let old = self.runtime.loaded_mods.remove(index);
+            self.remove_mod_content(&old.name_path);
+            // unloads the old library
+            drop(old);
+        }
+        self.notify_clients_of_reload(name_path);
+
+        let owner = m.name_path.clone();
+        self.as_mod(Some(owner.clone()), |engine| m.start(engine));
+        let position = self.runtime.load_order.iter().position(|n| n == name_path);
+        let index = self.runtime.loaded_mods.iter()
+            .take_while(|loaded| self.runtime.load_order.iter().position(|n| *n == *loaded.name_path) < position)
+            .count();
+        self.runtime.loaded_mods.insert(index, m);
+
+        // the new instance missed the joins of clients that are already logged in
+        let clients = self.clients.iter().copied().collect::<Vec<_>>();
+        self.for_each_module_of_type::<ConnectionListener, _>(|engine, eid, listener| {
+            if engine.current_mod.as_ref() == Some(&owner) {
+                for client in &clients {
+                    (listener.on_join)(eid, engine, client)
+                }
+            }
+        });
+        Ok(())
+    }
+
+    /// Removes all entities, modules, receivers, resources, tasks, commands, queued events and replicators owned by the mod.
+    pub(crate) fn remove_mod_content(&mut self, owner: &Rc<str>) {
+        let entities = self.iter()
+            .filter(|(_, e)| e.owner.as_ref() == Some(owner))
+            .map(|(id, _)| *id)
+            .collect::<Vec<EntityId>>();
+        for id in &entities {
+            self.remove_entity(id);
+        }
+        let modules = self.iter()
+            .flat_map(|(id, e)| e.module_owners.iter()
+                .filter(move |(_, o)| *o == owner)
+                .map(move |(ty, _)| (*id, *ty)))
+            .collect::<Vec<(EntityId, TypeId)>>();
+        for (id, ty) in &modules {
+            if let Value(e) = self.mut_entity(id) {
+                e.remove_module_type(ty);
+            }
+        }
+        // module columns created by this mod use code from its library
+        let columns = self.storage.columns.iter()
+            .filter(|(_, c)| c.owner.as_ref() == Some(owner))
+            .map(|(ty, _)| *ty)
+            .collect::<Vec<TypeId>>();
+        for ty in &columns {
+            let ids = self.storage.storage(ty).map(|s| s.ids()).unwrap_or_default();
+            if !ids.is_empty() {
+                log!(WARN, "removing {} module(s) of type {} added by other mods, since their storage was created by mod {owner}", ids.len(), ty.info());
+            }
+            for id in &ids {
+                if let Value(e) = self.mut_entity(id) {
+                    e.remove_module_type(ty);
+                }
+            }
+            self.storage.columns.remove(ty);
+        }
+        // receivers the mod registered on messengers of other mods
+        let messengers = self.storage.column::<Messenger>()
+            .map(|c| c.iter().map(|(id, _)| *id).collect::<Vec<EntityId>>())
+            .unwrap_or_default();
+        let mut functions = 0;
+        for id in &messengers {
+            if let Value(messenger) = self.mut_module_of::<Messenger>(id) {
+                functions += messenger.remove_functions_of(owner);
+            }
+        }
+        let resources = self.resources.len();
+        self.resources.retain(|_, r| r.owner.as_ref() != Some(owner));
+        self.tasks.remove_owned(owner);
+        self.commands.retain(|(o, _)| o.as_ref() != Some(owner));
+        self.queued_events.retain(|(o, _)| o.as_ref() != Some(owner));
+        self.replicators.retain(|_, (_, o)| o.as_ref() != Some(owner));
+        log!(DEBUG, "removed {} entities, {} foreign modules, {} module columns, {functions} receivers and {} resources of mod {owner}", entities.len(), modules.len(), columns.len(), resources - self.resources.len());
+    }
+
+    fn notify_clients_of_reload(&self, name_path: &str) {
+        let ns = self.runtime.ns.borrow();
+        for (id, client) in ns.clients.iter() {
+            let flags = self.runtime.mod_profile.modstack[name_path].clone();
+            let _ = ns.send(id, &ServerPacket {
+                conv_id: Id::new(),
+                message: ServerMessage::ReloadMod(client_zip_info(name_path, flags, &client.mod_target)),
+            }, SendMode::Safe);
+        }
+    }
+}
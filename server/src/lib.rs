@@ -3,15 +3,26 @@
 #![feature(trait_alias)]
 #![feature(generic_const_exprs)]
 #![feature(associated_const_equality)]
+#![feature(test)]
+extern crate test;
+
+#[cfg(test)]
+mod tests;
 
 use std::ops::{Deref, DerefMut};
+use std::rc::Rc;
 use aeonetica_engine::libloading::Library;
 use crate::ecs::Engine;
 
 pub mod ecs;
 mod networking;
 mod server_runtime;
+mod hot_reload;
 pub mod server;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(any(test, feature = "test-util"))]
+pub mod test_util;
 
 pub trait ServerMod {
     #[allow(unused_variables)]
@@ -20,16 +31,39 @@ pub trait ServerMod {
     fn start(&mut self, engine: &mut Engine) {}
 }
 
+/// Fingerprint of the server side of the mod boundary, mods with a different one are not loaded.
+pub fn abi_fingerprint() -> u64 {
+    use aeonetica_engine::abi::{fingerprint, layout_of};
+    fingerprint(&[
+        layout_of::<Box<dyn ServerMod>>(),
+        layout_of::<Engine>(),
+        layout_of::<ecs::entity::Entity>(),
+        layout_of::<ecs::messaging::Messenger>(),
+        layout_of::<aeonetica_engine::Id>()
+    ])
+}
+
 pub struct ServerModBox {
+    pub(crate) name_path: Rc<str>,
     server_mod: Box<dyn ServerMod>,
-    _library: Library
+    /// `None` for wasm mods
+    _library: Option<Library>
 }
 
 impl ServerModBox {
-    pub fn new(server_mod: Box<dyn ServerMod>, library: Library) -> Self{
+    pub fn new(name_path: &str, server_mod: Box<dyn ServerMod>, library: Library) -> Self{
+        Self {
+            name_path: Rc::from(name_path),
+            server_mod,
+            _library: Some(library),
+        }
+    }
+
+    pub fn without_library(name_path: &str, server_mod: Box<dyn ServerMod>) -> Self {
         Self {
+            name_path: Rc::from(name_path),
             server_mod,
-            _library: library,
+            _library: None,
         }
     }
 }
@@ -7,14 +7,14 @@ use aeonetica_engine::networking::client_packets::{ClientMessage, ClientPacket};
 use aeonetica_engine::networking::server_packets::{ServerInfo, ServerMessage, ServerPacket};
 use aeonetica_engine::{ENGINE_VERSION, MAX_CLIENT_TIMEOUT};
 use aeonetica_engine::{log, Id};
+use aeonetica_engine::networking::rpc::rpc_name;
+use aeonetica_engine::util::type_to_id;
 use aeonetica_engine::networking::{MOD_DOWNLOAD_CHUNK_SIZE, NetResult, SendMode};
-use aeonetica_engine::sha2;
-use aeonetica_engine::sha2::Digest;
 use crate::ecs::Engine;
 use crate::ecs::events::ConnectionListener;
 use crate::ecs::messaging::Messenger;
 use crate::networking::ClientHandle;
-use crate::server_runtime::mod_client_zip;
+use crate::server_runtime::{client_zip_info, mod_client_zip};
 
 impl Engine {
     pub(crate) fn handle_queued(&mut self) -> ErrorResult<()> {
@@ -70,7 +70,8 @@ impl Engine {
                     } else {
                         self.runtime.ns.borrow_mut().clients.insert(packet.client_id, ClientHandle {
                             last_seen: std::time::Instant::now(),
-                            client_addr: *addr
+                            client_addr: *addr,
+                            mod_target: client_info.mod_target.clone()
                         });
                         self.runtime.ns.borrow().send(&packet.client_id, &ServerPacket{
                             conv_id: packet.conv_id,
@@ -78,15 +79,9 @@ impl Engine {
                                 server_version: ENGINE_VERSION.to_string(),
                                 mod_profile: self.runtime.mod_profile.profile.clone(),
                                 mod_version: self.runtime.mod_profile.version.clone(),
-                                mods: self.runtime.mod_profile.modstack.iter().map(|(name_path, flags)| {
-                                    let (name, path) = name_path.split_once(':').unwrap();
-                                    let client_path = mod_client_zip(path, name, &client_info.mod_target);
-                                    let size = std::fs::metadata(&client_path).unwrap().len();
-                                    let mut file = File::open(&client_path).unwrap();
-                                    let mut hasher = sha2::Sha256::default();
-                                    std::io::copy(&mut file, &mut hasher).unwrap();
-                                    let digest = hasher.finalize();
-                                    (name_path.clone(), flags.clone(), format!("{digest:X}"), size)
+                                mods: self.runtime.load_order.iter().map(|name_path| {
+                                    let flags = &self.runtime.mod_profile.modstack[name_path];
+                                    client_zip_info(name_path, flags.clone(), &client_info.mod_target)
                                 }).collect(),
                             }))
                         }, SendMode::Safe)?;
@@ -128,20 +123,52 @@ impl Engine {
                 if self.clients.contains(&packet.client_id) {
                     log!("client logged out: {}", packet.client_id);
                     self.for_each_module_of_type::<ConnectionListener, _>(|engine, id, m| (m.on_leave)(id, engine, &packet.client_id));
+                    self.remove_client_entities(&packet.client_id);
+                    self.drop_client_replies(&packet.client_id);
                     self.clients.remove(&packet.client_id);
                     let mut ns = self.runtime.ns.borrow_mut();
                     ns.clients.remove(&packet.client_id);
-                    ns.tcp.lock().unwrap().remove(addr);
+                    ns.disconnect(addr);
                 }
             }
             ClientMessage::ModMessage(eid, rid, data) => {
                 let mut_engine_ref = unsafe { &mut *(self as *mut Self) };
-                if let Some(m) = self.get_module_of::<Messenger>(eid).ref_option() {
-                    if let Some(f) = m.receiver_functions.get(rid) {
-                        f(eid, mut_engine_ref, &packet.client_id, data)
+                if let Some(e) = self.get_entity(eid) {
+                    let owner = self.module_owner(eid, &type_to_id::<Messenger>());
+                    if let Some(m) = e.get_module::<Messenger>().ref_option() {
+                        if let Some(f) = m.receiver_functions.get(rid) {
+                            mut_engine_ref.as_mod(owner, |engine| f(eid, engine, &packet.client_id, data))
+                        } else {
+                            log!(ERROR, "client {} called rpc {rid} ({}) which entity {eid} has no receiver for", packet.client_id, rpc_name(rid).as_deref().unwrap_or("unknown"));
+                        }
                     }
                 }
             }
+            ClientMessage::ModRequest(eid, rid, data) => {
+                let mut_engine_ref = unsafe { &mut *(self as *mut Self) };
+                let owner = self.module_owner(eid, &type_to_id::<Messenger>());
+                let reply = match self.get_entity(eid).and_then(|e| e.get_module::<Messenger>().option()) {
+                    Some(m) => match m.request_functions.get(rid) {
+                        Some(f) => mut_engine_ref.as_mod(owner, |engine| f(eid, engine, &packet.client_id, data)),
+                        None => Err(format!("entity {eid} has no handler for request {rid} ({})", rpc_name(rid).as_deref().unwrap_or("unknown")))
+                    }
+                    None => Err(format!("entity {eid} has no messenger"))
+                };
+                if let Err(e) = &reply {
+                    log!(ERROR, "request of client {} failed: {e}", packet.client_id);
+                }
+                self.runtime.ns.borrow().send(&packet.client_id, &ServerPacket {
+                    conv_id: packet.conv_id,
+                    message: ServerMessage::ModReply(*eid, reply.into())
+                }, SendMode::Safe)?;
+            }
+            ClientMessage::ModReply(_eid, reply) => {
+                let reply = match reply {
+                    NetResult::Ok(data) => Ok(data.clone()),
+                    NetResult::Err(e) => Err(e.clone())
+                };
+                self.receive_reply(&packet.client_id, &packet.conv_id, reply);
+            }
             _ => ()
         }
         Ok(())
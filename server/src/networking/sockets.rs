@@ -0,0 +1,110 @@
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, UdpSocket};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use aeonetica_engine::error::ErrorResult;
+use aeonetica_engine::log;
+use aeonetica_engine::networking::{MAX_PACKET_SIZE, SendMode};
+use aeonetica_engine::networking::transport::ServerTransport;
+
+/// Quick packets are sent over udp, safe packets over a tcp connection per client.
+pub(crate) struct SocketTransport {
+    udp: UdpSocket,
+    tcp: Arc<Mutex<HashMap<SocketAddr, Arc<Mutex<Vec<Vec<u8>>>>>>>,
+    received: Arc<Mutex<Vec<(SocketAddr, Vec<u8>)>>>
+}
+
+impl SocketTransport {
+    pub(crate) fn bind(addr: &str) -> ErrorResult<Self> {
+        let socket = UdpSocket::bind(addr)?;
+        let sock = socket.try_clone()?;
+        let received = Arc::new(Mutex::new(vec![]));
+        let tcp_sockets = Arc::new(Mutex::new(HashMap::new()));
+        let recv = received.clone();
+        let recv_tcp = received.clone();
+        let tcp = tcp_sockets.clone();
+        std::thread::spawn(move || {
+            let mut buf = [0u8; MAX_PACKET_SIZE];
+            loop {
+                match sock.recv_from(&mut buf) {
+                    Ok((len, src)) => recv.lock().unwrap().push((src, buf[..len].to_vec())),
+                    Err(_e) => {}
+                }
+            }
+        });
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(false).unwrap();
+        std::thread::spawn(move || {
+            for mut stream in listener.incoming().flatten() {
+                stream.set_nonblocking(false).unwrap();
+                let addr = stream.peer_addr().unwrap();
+                let queue = Arc::new(Mutex::new(vec![]));
+                tcp.lock().unwrap().insert(addr, queue.clone());
+                let recv_tcp_inner = recv_tcp.clone();
+                let mut write_stream = stream.try_clone().unwrap();
+                thread::spawn(move || {
+                    loop {
+                        let r = (||{
+                            let mut size = [0u8; 4];
+                            stream.read_exact(&mut size)?;
+                            let size = u32::from_le_bytes(size);
+                            let mut buffer: Vec<u8> = vec![0; size as usize];
+                            stream.read_exact(&mut buffer[..])?;
+                            recv_tcp_inner.lock().unwrap().push((addr, buffer));
+                            Ok::<_, std::io::Error>(())
+                        })();
+                        if r.is_err() { break }
+                    }
+                    log!("terminated tcp connection with {}", addr)
+                });
+                thread::spawn(move || {
+                    loop {
+                        let mut queued: Vec<Vec<u8>> = vec![];
+                        {
+                            let mut q = queue.lock().unwrap();
+                            std::mem::swap(&mut queued, &mut q);
+                        }
+                        if queued.len() > 0 {
+                            for msg in queued {
+                                write_stream.write(&(msg.len() as u32).to_le_bytes()).unwrap();
+                                write_stream.write(&msg[..]).unwrap();
+                            }
+                        }
+                    }
+                });
+            }
+        });
+        Ok(Self {
+            udp: socket,
+            tcp: tcp_sockets,
+            received
+        })
+    }
+}
+
+impl ServerTransport for SocketTransport {
+    fn receive(&mut self) -> Vec<(SocketAddr, Vec<u8>)> {
+        std::mem::take(&mut self.received.lock().unwrap() as &mut Vec<(SocketAddr, Vec<u8>)>)
+    }
+
+    fn send(&self, addr: SocketAddr, data: Vec<u8>, mode: SendMode) -> ErrorResult<()> {
+        match mode {
+            SendMode::Quick => {
+                let sock = self.udp.try_clone()?;
+                std::thread::spawn(move || sock.send_to(&data[..], addr));
+            }
+            SendMode::Safe => {
+                if let Some(tcp_queue) = self.tcp.lock().unwrap().get_mut(&addr) {
+                    let mut queue = tcp_queue.lock().unwrap();
+                    queue.push(data);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn disconnect(&self, addr: &SocketAddr) {
+        self.tcp.lock().unwrap().remove(addr);
+    }
+}
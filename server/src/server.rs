@@ -1,24 +1,91 @@
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::{Duration, Instant};
+use aeonetica_engine::networking::transport::ServerTransport;
 use aeonetica_engine::time::Time;
 use aeonetica_engine::{log};
 use crate::ecs::Engine;
-use crate::server_runtime::ServerRuntime;
+use crate::networking::NetworkServer;
+use crate::ServerModBox;
+use crate::server_runtime::{mod_client_zip, ServerRuntime};
 
 const TPS: usize = 20;
 const FULL_SEC: usize = 1_000_000_000;
 
-pub fn run(ip: &str) {
-    let runtime = ServerRuntime::create(ip).map_err(|e| {
+pub fn run(ip: &str, hot_reload: bool) {
+    let runtime = ServerRuntime::create(ip, hot_reload).map_err(|e| {
         e.log_exit();
     }).unwrap();
+    run_engine(runtime, &AtomicBool::new(false))
+}
 
+/// Runs a server without sockets, e.g. the one of a singleplayer game, which serves the clients
+/// connected to `transport` until `stop` is set. The mods are loaded from `mods/mods.ron` as usual.
+pub fn run_local(transport: impl ServerTransport + 'static, stop: &AtomicBool) {
+    let runtime = ServerRuntime::create_with(|| Ok(NetworkServer::with_transport(Box::new(transport))), false).map_err(|e| {
+        e.log_exit();
+    }).unwrap();
+    run_engine(runtime, stop)
+}
+
+/// Like [`run_local`], but with mods linked into the executable, see [`start_linked`].
+pub fn run_linked(mods: Vec<ServerModBox>, transport: impl ServerTransport + 'static, stop: &AtomicBool) {
+    run_engine(ServerRuntime::linked(mods, NetworkServer::with_transport(Box::new(transport))), stop)
+}
+
+/// Starts a server with mods linked into the executable instead of the ones in `mods/mods.ron`,
+/// ticked by the caller with [`Engine::run_tick`]. Lets tests run mods and simulated clients in one process,
+/// see `aeonetica_engine::networking::memory`. `ServerMod::init` is left to the caller.
+/// The engine is boxed because its entities point to it.
+pub fn start_linked(mods: Vec<ServerModBox>, transport: impl ServerTransport + 'static) -> Box<Engine> {
+    start_engine(ServerRuntime::linked(mods, NetworkServer::with_transport(Box::new(transport))))
+}
+
+/// The client zip of a mod the server unpacked into its runtime directory,
+/// which the client of a singleplayer game uses instead of downloading it.
+pub fn unpacked_client_zip(name_path: &str, mod_target: &str) -> String {
+    let (path, name) = name_path.split_once(':').unwrap();
+    mod_client_zip(path, name, mod_target)
+}
+
+/// Entities keep a pointer to the engine, so it gets its final address before any mod creates one.
+fn start_engine(runtime: ServerRuntime) -> Box<Engine> {
     log!("running start for all mods");
-    let mut engine = Engine::new(runtime);
+    let mut engine = Box::new(Engine::new(runtime));
 
-    let mut_engine_ref = unsafe { &mut *(&mut engine as *mut Engine) };
+    let mut_engine_ref = unsafe { &mut *(&mut *engine as *mut Engine) };
     engine.runtime.loaded_mods.iter_mut().for_each(|m| {
-        m.start(mut_engine_ref);
+        mut_engine_ref.as_mod(Some(m.name_path.clone()), |engine| m.start(engine));
     });
+    engine.apply_commands();
+    engine
+}
+
+impl Engine {
+    /// Handles the received packets and runs the modules and systems once.
+    pub fn run_tick(&mut self, time: Time) {
+        let _ = self.handle_queued().map_err(|e| {
+            log!(ERROR, "{e}")
+        });
+
+        self.timeout_inactive();
+        self.apply_commands();
+
+        self.for_each_module(|engine, id, m| m.tick_dyn(id, engine, time));
+        self.apply_commands();
+        self.run_tasks();
+        self.apply_commands();
+        self.dispatch_events();
+        self.apply_commands();
+        self.update_interest();
+        self.replicate();
+        self.reload_changed_mods();
+
+        self.tick += 1;
+    }
+}
+
+fn run_engine(runtime: ServerRuntime, stop: &AtomicBool) {
+    let mut engine = start_engine(runtime);
 
     let mut time_nanos = 0;
     let mut time = Time {
@@ -29,22 +96,12 @@ pub fn run(ip: &str) {
 
     println!("\x1b[38;5;200mServer successfully set up and ready for clients to connect\x1b[0m");
 
-    loop {
+    while !stop.load(Ordering::Relaxed) {
         let t = Instant::now();
 
-        let _ = engine.handle_queued().map_err(|e| {
-            log!(ERROR, "{e}")
-        });
-
-        engine.timeout_inactive();
-
-        engine.for_each_module(|engine, id, m| m.tick_dyn(id, engine, time));
-        engine.run_tasks();
+        engine.run_tick(time);
 
         let delta_time_nanos_intermediate = t.elapsed().as_nanos();
-        
-
-        engine.tick += 1;
 
         if (delta_time_nanos_intermediate as usize) < 1_000_000_000 / TPS {
             let to_wait = 1_000_000_000 / TPS - delta_time_nanos_intermediate as usize;
@@ -59,4 +116,5 @@ pub fn run(ip: &str) {
         time.time = time_nanos as f32 / FULL_SEC as f32;
         //println!("time.delta = {}  percentage = {}", time.delta, time.delta / (1.0 / TPS as f32) * 100.0);
     }
+    log!("server stopped after {}s", time.time);
 }
\ No newline at end of file
@@ -1,36 +1,57 @@
 use std::cell::RefCell;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs::File;
 use std::io::Read;
 use std::path::Path;
 use std::rc::Rc;
 use aeonetica_engine::error::builtin::ModError;
-use aeonetica_engine::libloading::{Library, Symbol};
-use aeonetica_engine::{log, nanoserde};
+use aeonetica_engine::libloading::Library;
+use aeonetica_engine::abi::{mod_entry, Side};
+use aeonetica_engine::{log, nanoserde, sha2};
+use aeonetica_engine::sha2::Digest;
 use aeonetica_engine::error::*;
 use aeonetica_engine::nanoserde::{DeBin, DeRon, SerBin, SerRon};
 use aeonetica_engine::util::unzip_archive;
+use aeonetica_engine::manifest::{MANIFEST_FILE, ModManifest, resolve_load_order};
+use aeonetica_engine::networking::server_packets::ModInfo;
+use aeonetica_engine::signing::{ModSignature, signature_file};
 use crate::{ServerMod, ServerModBox};
 use crate::networking::NetworkServer;
+use crate::hot_reload::ModWatcher;
 
 
 mod paths_util {
-    #[cfg(target_os = "windows")]
-    pub(crate) const MOD_FILE_EXTENSION: &str = "dll";
-    #[cfg(target_os = "linux")]
-    pub(crate) const MOD_FILE_EXTENSION: &str = "so";
+    use aeonetica_engine::mod_layout;
 
-    pub(crate) fn server_lib(path: &str, name: &str) -> String {
-        format!("runtime/{path}/server/{name}_server.{MOD_FILE_EXTENSION}")
+    /// Where the mod is unpacked to, see [`super::unpack_mod`].
+    pub(crate) fn mod_dir(path: &str) -> String {
+        format!("runtime/{path}")
+    }
+    pub(crate) fn server_lib(dir: &str, name: &str) -> String {
+        format!("{dir}/server/{}", mod_layout::server_lib(name))
     }
     pub(crate) fn mod_zip(path: &str) -> String {
         format!("mods/{path}.zip")
     }
-    pub(crate) fn mod_server_zip(path: &str, name: &str) -> String {
-        format!("runtime/{path}/{name}_server.zip")
+    pub(crate) fn mod_manifest(dir: &str) -> String {
+        format!("{dir}/{}", super::MANIFEST_FILE)
+    }
+    pub(crate) fn mod_server_zip(dir: &str, name: &str) -> String {
+        format!("{dir}/{}", mod_layout::server_zip(name))
+    }
+    pub(crate) fn server_wasm(dir: &str, name: &str) -> String {
+        format!("{dir}/server/{}", mod_layout::server_wasm(name))
     }
     pub(crate) fn mod_client_zip(path: &str, name: &str, target: &str) -> String {
-        format!("runtime/{path}/{name}_client-{target}.zip")
+        client_zip_in(&mod_dir(path), name, target)
+    }
+    /// Wasm mods have one client zip for every target.
+    pub(crate) fn client_zip_in(dir: &str, name: &str, target: &str) -> String {
+        let wasm = format!("{dir}/{}", mod_layout::client_zip(name, mod_layout::WASM_MOD_TARGET));
+        if std::path::Path::new(&wasm).exists() {
+            return wasm
+        }
+        format!("{dir}/{}", mod_layout::client_zip(name, target))
     }
 }
 
@@ -38,6 +59,10 @@ pub(crate) use paths_util::*;
 
 pub struct ServerRuntime {
     pub(crate) mod_profile: ModProfile,
+    /// name_paths of the modstack in dependency order
+    pub(crate) load_order: Vec<String>,
+    pub(crate) manifests: BTreeMap<String, ModManifest>,
+    pub(crate) mod_watcher: Option<ModWatcher>,
     pub(crate) supported_mod_targets: HashSet<String>,
     pub(crate) loaded_mods: Vec<ServerModBox>,
     pub(crate) ns: Rc<RefCell<NetworkServer>>
@@ -52,52 +77,145 @@ pub struct ModProfile {
 }
 
 impl ServerRuntime {
-    pub(crate) fn create(addr: &str) -> ErrorResult<ServerRuntime> {
+    pub(crate) fn create(addr: &str, hot_reload: bool) -> ErrorResult<ServerRuntime> {
+        Self::create_with(|| NetworkServer::start(addr), hot_reload)
+    }
+
+    /// Loads the mods before `network` is started.
+    pub(crate) fn create_with(network: impl FnOnce() -> ErrorResult<NetworkServer>, hot_reload: bool) -> ErrorResult<ServerRuntime> {
         let mut data = String::new();
         File::open("mods/mods.ron")?.read_to_string(&mut data)?;
         let profile: ModProfile = DeRon::deserialize_ron(&data)?;
         let mod_targets = HashSet::from_iter(profile.mod_targets.clone().unwrap_or(vec![aeonetica_engine::MOD_TARGET.to_string()]).iter().cloned());
-        let mut mods = vec![];
         log!("loading mods for targets {mod_targets:?}");
-        for item in &profile.modstack {
-            log!("loading mod {} ...", item.0);
-            let mut m = load_mod(item.0, &mod_targets)
+        let mut manifests = BTreeMap::new();
+        for name_path in profile.modstack.keys() {
+            let (path, _) = name_path.split_once(':').unwrap();
+            let manifest = unpack_mod(name_path, &mod_dir(path), &mod_targets)
                 .map_err(|mut e| {
-                    e.add_info(format!("could not load mod {}", item.0));
+                    e.add_info(format!("could not load mod {name_path}"));
                     e
                 })?;
-            m.init(item.1);
+            manifests.insert(name_path.clone(), manifest);
+        }
+        let load_order = resolve_load_order(&manifests)?;
+        log!("resolved mod load order: {load_order:?}");
+        let mut mods = vec![];
+        for name_path in &load_order {
+            log!("loading mod {name_path} ...");
+            let (path, _) = name_path.split_once(':').unwrap();
+            let mut m = load_mod(name_path, &mod_dir(path))
+                .map_err(|mut e| {
+                    e.add_info(format!("could not load mod {name_path}"));
+                    e
+                })?;
+            m.init(&profile.modstack[name_path]);
             mods.push(m);
-            log!("loaded mod {}", item.0)
+            log!("loaded mod {name_path}")
         }
         log!("successfully loaded {} mods from profile {} v{}", mods.len(), profile.profile, profile.version);
+        let mod_watcher = hot_reload.then(|| {
+            log!("watching mods for changes");
+            ModWatcher::new(&load_order)
+        });
         Ok(ServerRuntime {
             supported_mod_targets: mod_targets,
             mod_profile: profile,
+            load_order,
+            manifests,
+            mod_watcher,
             loaded_mods: mods,
-            ns: Rc::new(RefCell::new(NetworkServer::start(addr)?))
+            ns: Rc::new(RefCell::new(network()?))
         })
     }
+
+    /// A runtime for mods linked into the executable, which are already initialized.
+    /// They are not announced to clients, so clients have to bring them along as well.
+    pub(crate) fn linked(mods: Vec<ServerModBox>, network: NetworkServer) -> ServerRuntime {
+        ServerRuntime {
+            supported_mod_targets: HashSet::from([aeonetica_engine::MOD_TARGET.to_string()]),
+            mod_profile: ModProfile {
+                profile: "linked".to_string(),
+                version: aeonetica_engine::ENGINE_VERSION.to_string(),
+                mod_targets: None,
+                modstack: Default::default()
+            },
+            load_order: vec![],
+            manifests: Default::default(),
+            mod_watcher: None,
+            loaded_mods: mods,
+            ns: Rc::new(RefCell::new(network))
+        }
+    }
 }
 
-pub(crate) fn load_mod(name_path: &str, supported_mod_targets: &HashSet<String>) -> ErrorResult<ServerModBox> {
+/// Unpacks the mod archives into `dir`, usually [`mod_dir`], and reads the mod manifest.
+pub(crate) fn unpack_mod(name_path: &str, dir: &str, supported_mod_targets: &HashSet<String>) -> ErrorResult<ModManifest> {
     let (path, name) = name_path.split_once(':').unwrap();
 
-    unzip_archive(File::open(mod_zip(path))?, format!("runtime/{path}"))?;
-    unzip_archive(File::open(mod_server_zip(path, name))?, format!("runtime/{path}/server"))?;
+    unzip_archive(File::open(mod_zip(path))?, dir)?;
+    unzip_archive(File::open(mod_server_zip(dir, name))?, format!("{dir}/server"))?;
 
     for target in supported_mod_targets {
-        if !Path::new(&mod_client_zip(path, name, target)).exists() {
+        if !Path::new(&client_zip_in(dir, name, target)).exists() {
             Err(Error::new(ModError(format!("Mod {name_path} does not support target advertised in mods.ron: {target}\n(of {supported_mod_targets:?})")), Fatality::FATAL, false))?;
         }
     }
 
-    let server_lib_file = server_lib(path, name);
+    let manifest = ModManifest::load(mod_manifest(dir))?;
+    if manifest.name != name {
+        Err(Error::new(ModError(format!("Mod {name_path} has manifest for mod {}", manifest.name)), Fatality::FATAL, false))?;
+    }
+    Ok(manifest)
+}
+
+/// Loads the server side of a mod unpacked into `dir`.
+pub(crate) fn load_mod(name_path: &str, dir: &str) -> ErrorResult<ServerModBox> {
+    let (_, name) = name_path.split_once(':').unwrap();
+    if Path::new(&server_wasm(dir, name)).exists() {
+        return load_wasm_mod(name_path, &server_wasm(dir, name))
+    }
+
+    let server_lib_file = server_lib(dir, name);
     log!(DEBUG, "loading lib: {}", server_lib_file);
     let server_lib = unsafe { Library::new(server_lib_file)
         .map_err(|e| Error::new(ModError(format!("could not load mod: {e}")), Fatality::FATAL, false))? };
-    let _create_mod_server: Symbol<fn() -> Box<dyn ServerMod>> = unsafe { server_lib.get("_create_mod_server".as_ref())
-        .map_err(|e| Error::new(ModError(format!("could not load mod: {e}")), Fatality::FATAL, false))? };
-    let mod_server = _create_mod_server();
-    Ok(ServerModBox::new(mod_server, server_lib))
+    let create_mod_server = mod_entry(&server_lib, Side::Server, crate::abi_fingerprint(), name_path)?;
+    let mod_server = *unsafe { Box::from_raw(create_mod_server() as *mut Box<dyn ServerMod>) };
+    Ok(ServerModBox::new(name_path, mod_server, server_lib))
+}
+
+#[cfg(feature = "wasm")]
+fn load_wasm_mod(name_path: &str, file: &str) -> ErrorResult<ServerModBox> {
+    log!(DEBUG, "loading wasm mod: {file}");
+    let server_mod = crate::wasm::WasmServerMod::load(name_path, &std::fs::read(file)?)?;
+    Ok(ServerModBox::without_library(name_path, Box::new(server_mod)))
+}
+
+#[cfg(not(feature = "wasm"))]
+fn load_wasm_mod(name_path: &str, _file: &str) -> ErrorResult<ServerModBox> {
+    Err(Error::new(ModError(format!("could not load mod {name_path}: it is a wasm mod, but the server was built without the wasm feature")), Fatality::FATAL, false))
+}
+
+/// Returns the hash, size and signature of the client zip of a mod for the given target.
+/// The signature is forwarded as is, clients check it against their trusted publishers.
+pub(crate) fn client_zip_info(name_path: &str, flags: Vec<String>, mod_target: &str) -> ModInfo {
+    let (name, path) = name_path.split_once(':').unwrap();
+    let client_path = mod_client_zip(path, name, mod_target);
+    let size = std::fs::metadata(&client_path).unwrap().len();
+    let mut file = File::open(&client_path).unwrap();
+    let mut hasher = sha2::Sha256::default();
+    std::io::copy(&mut file, &mut hasher).unwrap();
+    let digest = hasher.finalize();
+    let signature = ModSignature::load(signature_file(&client_path)).unwrap_or_else(|e| {
+        log!(WARN, "ignoring signature of mod {name_path}: {e}");
+        None
+    });
+    ModInfo {
+        name_path: name_path.to_string(),
+        flags,
+        hash: format!("{digest:X}"),
+        size,
+        signature
+    }
 }
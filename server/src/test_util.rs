@@ -0,0 +1,56 @@
+//! Helpers for tests of the server and of mods, enabled with the `test-util` feature.
+use aeonetica_engine::{ClientId, ENGINE_VERSION, Id, MOD_TARGET};
+use aeonetica_engine::nanoserde::{DeBin, SerBin};
+use aeonetica_engine::networking::SendMode;
+use aeonetica_engine::networking::client_packets::{ClientInfo, ClientMessage, ClientPacket};
+use aeonetica_engine::networking::memory::{MemoryClientTransport, MemoryConnector};
+use aeonetica_engine::networking::server_packets::{ServerMessage, ServerPacket};
+use aeonetica_engine::networking::transport::ClientTransport;
+
+/// A client that sends and receives raw packets, without a client runtime.
+/// Connect it to a server started with [`crate::server::start_linked`].
+pub struct SimulatedClient {
+    pub id: ClientId,
+    pub transport: MemoryClientTransport
+}
+
+impl SimulatedClient {
+    pub fn connect(connector: &MemoryConnector) -> Self {
+        Self { id: Id::new(), transport: connector.connect() }
+    }
+
+    /// Connects, registers and logs in, without downloading any mods.
+    pub fn join(connector: &MemoryConnector) -> Self {
+        let client = Self::connect(connector);
+        client.register();
+        client
+    }
+
+    /// Registers and logs in, handled by the server on its next tick.
+    pub fn register(&self) {
+        self.send(ClientMessage::Register(ClientInfo {
+            client_id: self.id,
+            client_version: ENGINE_VERSION.to_string(),
+            mod_target: MOD_TARGET.to_string()
+        }));
+        self.send(ClientMessage::Login);
+    }
+
+    pub fn send(&self, message: ClientMessage) {
+        self.send_in(Id::new(), message)
+    }
+
+    /// Sends the message in the conversation `conv_id`, like a reply.
+    pub fn send_in(&self, conv_id: Id, message: ClientMessage) {
+        let packet = ClientPacket { client_id: self.id, conv_id, message };
+        self.transport.send(packet.serialize_bin(), SendMode::Safe).unwrap();
+    }
+
+    pub fn received_packets(&mut self) -> Vec<ServerPacket> {
+        self.transport.receive().iter().map(|data| ServerPacket::deserialize_bin(data).unwrap()).collect()
+    }
+
+    pub fn received(&mut self) -> Vec<ServerMessage> {
+        self.received_packets().into_iter().map(|packet| packet.message).collect()
+    }
+}
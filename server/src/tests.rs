@@ -0,0 +1,864 @@
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::ops::{Coroutine, CoroutineState};
+use std::pin::Pin;
+use std::rc::Rc;
+use std::time::Duration;
+use test::{Bencher, black_box};
+use aeonetica_engine::{ClientId, EntityId, Id};
+use aeonetica_engine::nanoserde::SerBin;
+use aeonetica_engine::networking::NetResult;
+use aeonetica_engine::networking::client_packets::ClientMessage;
+use aeonetica_engine::networking::memory::memory_transport;
+use aeonetica_engine::networking::server_packets::ServerMessage;
+use aeonetica_engine::networking::messaging::ClientEntity;
+use aeonetica_engine::networking::rpc::{RequestError, Rpc};
+use aeonetica_engine::math::vector::Vector2;
+use aeonetica_engine::time::Time;
+use aeonetica_engine::util::id_map::IdMap;
+use aeonetica_engine::util::type_to_id;
+use crate::ecs::Engine;
+use crate::ecs::events::{Dispatch, EventListener};
+use crate::ecs::interest::{Interest, Viewer};
+use crate::ecs::messaging::Messenger;
+use crate::ecs::requests::PendingReply;
+use crate::ecs::module::{Module, ModuleDyn};
+use crate::ecs::scheduling::{Event, TaskHandle, WaitFor, Yielder};
+use crate::ecs::storage::{Column, ModuleStorage};
+use crate::ecs::validation::{check_range, ViolationPolicies, ViolationPolicy};
+use crate::server::start_linked;
+use crate::{ServerMod, ServerModBox};
+use crate::test_util::SimulatedClient;
+
+const ENTITY_COUNT: usize = 10_000;
+
+struct Position(f32, f32);
+struct Velocity(f32, f32);
+
+impl Module for Position {}
+impl Module for Velocity {}
+
+/// The module layout before the module storage: a map of boxed modules per entity.
+fn boxed_entities() -> (Vec<EntityId>, IdMap<IdMap<Box<dyn ModuleDyn>>>) {
+    let mut entities = IdMap::<IdMap<Box<dyn ModuleDyn>>>::default();
+    let ids = (0..ENTITY_COUNT).map(|i| {
+        let id = Id::new();
+        let mut modules = IdMap::<Box<dyn ModuleDyn>>::default();
+        modules.insert(type_to_id::<Position>(), Box::new(Position(i as f32, 0.0)));
+        if i % 2 == 0 {
+            modules.insert(type_to_id::<Velocity>(), Box::new(Velocity(1.0, 1.0)));
+        }
+        entities.insert(id, modules);
+        id
+    }).collect();
+    (ids, entities)
+}
+
+fn module_storage() -> (Vec<EntityId>, ModuleStorage) {
+    let mut storage = ModuleStorage::default();
+    let ids = (0..ENTITY_COUNT).map(|i| {
+        let id = Id::new();
+        storage.insert(id, Position(i as f32, 0.0), &None);
+        if i % 2 == 0 {
+            storage.insert(id, Velocity(1.0, 1.0), &None);
+        }
+        id
+    }).collect();
+    (ids, storage)
+}
+
+#[bench]
+fn bench_iter_boxed_modules(b: &mut Bencher) {
+    let (_, mut entities) = boxed_entities();
+    b.iter(|| {
+        // what for_each_module_of_type used to do
+        for id in entities.keys().cloned().collect::<Vec<_>>() {
+            if let Some(m) = entities.get_mut(&id).unwrap().get_mut(&type_to_id::<Position>()) {
+                let position = unsafe { &mut *(&mut **m as *mut dyn ModuleDyn as *mut Position) };
+                position.0 += 1.0;
+            }
+        }
+    });
+}
+
+#[bench]
+fn bench_iter_module_column(b: &mut Bencher) {
+    let (_, mut storage) = module_storage();
+    b.iter(|| {
+        let column = storage.column_mut::<Position>().unwrap();
+        column.lock();
+        for i in 0..column.dense_len() {
+            if let Some((_, position)) = column.at_mut(i) {
+                position.0 += 1.0;
+            }
+        }
+        column.unlock();
+    });
+}
+
+#[bench]
+fn bench_get_boxed_modules(b: &mut Bencher) {
+    let (ids, entities) = boxed_entities();
+    b.iter(|| {
+        ids.iter().filter_map(|id| entities.get(id)?.get(&type_to_id::<Velocity>())
+            .map(|m| unsafe { &*(&**m as *const dyn ModuleDyn as *const Velocity) }.0))
+            .sum::<f32>()
+    });
+}
+
+#[bench]
+fn bench_get_module_column(b: &mut Bencher) {
+    let (ids, storage) = module_storage();
+    b.iter(|| {
+        ids.iter().filter_map(|id| storage.get::<Velocity>(id).map(|v| v.0)).sum::<f32>()
+    });
+}
+
+#[bench]
+fn bench_insert_boxed_modules(b: &mut Bencher) {
+    b.iter(|| black_box(boxed_entities()));
+}
+
+#[bench]
+fn bench_insert_module_column(b: &mut Bencher) {
+    b.iter(|| black_box(module_storage()));
+}
+
+#[test]
+fn column_defers_changes_while_locked() {
+    let ids = (0..4).map(|_| Id::new()).collect::<Vec<_>>();
+    let mut column = Column::new();
+    for (i, id) in ids[..3].iter().enumerate() {
+        assert!(column.insert(*id, i));
+    }
+    assert!(!column.insert(ids[0], 10));
+
+    column.lock();
+    assert!(column.remove(&ids[0]));
+    assert!(column.insert(ids[3], 3));
+    // nothing moves while locked
+    assert_eq!(column.dense_len(), 3);
+    assert!(column.at_mut(0).is_none());
+    assert_eq!(column.get(&ids[0]), None);
+    assert_eq!(column.get(&ids[3]), Some(&3));
+    column.unlock();
+
+    assert_eq!(column.dense_len(), 3);
+    for (i, id) in ids.iter().enumerate().skip(1) {
+        assert_eq!(column.get(id), Some(&i));
+    }
+    let mut values = column.iter().map(|(_, v)| *v).collect::<Vec<_>>();
+    values.sort();
+    assert_eq!(values, vec![1, 2, 3]);
+}
+
+#[test]
+fn column_swap_remove_keeps_lookup() {
+    let ids = (0..3).map(|_| Id::new()).collect::<Vec<_>>();
+    let mut column = Column::new();
+    for (i, id) in ids.iter().enumerate() {
+        column.insert(*id, i);
+    }
+    assert!(column.remove(&ids[0]));
+    assert!(!column.remove(&ids[0]));
+    assert_eq!(column.get(&ids[1]), Some(&1));
+    assert_eq!(column.get(&ids[2]), Some(&2));
+    *column.get_mut(&ids[2]).unwrap() = 20;
+    assert_eq!(column.at_mut(0).map(|(id, v)| (id, *v)), Some((ids[2], 20)));
+}
+
+#[test]
+fn column_inserts_do_not_move_modules() {
+    let first = Id::new();
+    let mut column = Column::new();
+    column.insert(first, 0);
+    // what a mod holds while adding modules through the entity's engine pointer
+    let module = column.get_mut(&first).unwrap() as *mut usize;
+    for i in 1..1000 {
+        column.insert(Id::new(), i);
+    }
+    unsafe { *module += 7; }
+    assert_eq!(column.get(&first), Some(&7));
+}
+
+const TICK: Time = Time { time: 0.0, delta: 0.05, raw_delta: 0.05 };
+
+#[test]
+fn memory_clients_register_and_log_out() {
+    let (transport, connector) = memory_transport();
+    let mut engine = start_linked(vec![], transport);
+    let mut clients = (0..3).map(|_| SimulatedClient::connect(&connector)).collect::<Vec<_>>();
+    for client in &clients {
+        client.register();
+    }
+    engine.run_tick(TICK);
+
+    for client in &mut clients {
+        assert!(engine.clients().any(|c| *c == client.id));
+        let received = client.received();
+        assert!(matches!(received.as_slice(), [ServerMessage::RegisterResponse(NetResult::Ok(info))] if info.mods.is_empty()));
+    }
+
+    clients[0].send(ClientMessage::Logout);
+    clients[1].send(ClientMessage::Ping("ping".to_string()));
+    engine.run_tick(TICK);
+
+    assert!(!engine.clients().any(|c| *c == clients[0].id));
+    assert_eq!(engine.clients().count(), 2);
+    assert!(matches!(clients[1].received().as_slice(), [ServerMessage::Pong(msg)] if msg == "ping"));
+    // logged out clients are not sent anything anymore
+    clients[0].send(ClientMessage::Ping("ping".to_string()));
+    engine.run_tick(TICK);
+    assert!(clients[0].received().is_empty());
+}
+
+/// An engine without mods or clients.
+fn engine() -> Box<Engine> {
+    let (transport, _) = memory_transport();
+    start_linked(vec![], transport)
+}
+
+/// Creates a tagged entity with a module on start, like the world mod does.
+struct SpawningMod;
+
+impl ServerMod for SpawningMod {
+    fn start(&mut self, engine: &mut Engine) {
+        let id = engine.new_entity();
+        engine.mut_entity(&id).unwrap().add_module(Position(1.0, 2.0));
+        engine.tag_entity(id, "spawned");
+    }
+}
+
+#[test]
+fn entities_created_on_start_can_access_their_modules() {
+    let (transport, _) = memory_transport();
+    let mut engine = start_linked(vec![ServerModBox::without_library("test:spawning", Box::new(SpawningMod))], transport);
+    let id = *engine.get_entity_id_by_tag("spawned").unwrap();
+    let position = engine.get_entity(&id).unwrap().get_module::<Position>().unwrap();
+    assert_eq!((position.0, position.1), (1.0, 2.0));
+
+    engine.mut_entity(&id).unwrap().mut_module::<Position>().unwrap().0 = 3.0;
+    engine.run_tick(TICK);
+    assert_eq!(engine.get_entity(&id).unwrap().get_module::<Position>().unwrap().0, 3.0);
+}
+
+fn spawn(engine: &mut Engine, position: Position, velocity: Option<Velocity>) -> EntityId {
+    let id = engine.new_entity();
+    let entity = engine.mut_entity(&id).unwrap();
+    entity.add_module(position);
+    if let Some(velocity) = velocity {
+        entity.add_module(velocity);
+    }
+    id
+}
+
+#[test]
+fn query_matches_required_and_optional_modules() {
+    let mut engine = engine();
+    let moving = spawn(&mut engine, Position(0.0, 0.0), Some(Velocity(1.0, 2.0)));
+    let resting = spawn(&mut engine, Position(5.0, 5.0), None);
+    let empty = engine.new_entity();
+
+    for (position, velocity) in engine.query::<(&mut Position, &Velocity)>() {
+        position.0 += velocity.0;
+        position.1 += velocity.1;
+    }
+    assert_eq!(engine.get_module_of::<Position>(&moving).unwrap().1, 2.0);
+    assert_eq!(engine.get_module_of::<Position>(&resting).unwrap().1, 5.0);
+
+    let mut matched = engine.query::<(EntityId, &Position, Option<&Velocity>)>()
+        .map(|(id, _, velocity)| (id, velocity.is_some()))
+        .collect::<Vec<_>>();
+    matched.sort_by_key(|(id, _)| id.into_u64());
+    let mut expected = vec![(moving, true), (resting, false)];
+    expected.sort_by_key(|(id, _)| id.into_u64());
+    assert_eq!(matched, expected);
+    // without required modules every entity matches
+    assert_eq!(engine.query::<(EntityId, Option<&Velocity>)>().count(), 3);
+
+    assert!(engine.query_entity::<&Velocity>(&resting).is_none());
+    assert!(engine.query_entity::<EntityId>(&empty).is_some());
+    let (a, b) = engine.query_two::<&mut Position, &Position>(&moving, &resting).unwrap();
+    a.0 = b.0;
+    assert_eq!(engine.get_module_of::<Position>(&moving).unwrap().0, 5.0);
+}
+
+#[test]
+#[should_panic]
+fn query_rejects_aliasing_accesses() {
+    let mut engine = engine();
+    spawn(&mut engine, Position(0.0, 0.0), None);
+    let _ = engine.query::<(&Position, &mut Position)>().count();
+}
+
+#[test]
+fn commands_apply_at_sync_points_in_order() {
+    let mut engine = engine();
+    let old = spawn(&mut engine, Position(0.0, 0.0), None);
+    let id = engine.commands().spawn()
+        .insert_module(Position(1.0, 1.0))
+        .tag("spawned")
+        .id();
+    engine.commands()
+        .despawn(old)
+        .add(move |engine| {
+            // queued while applying, still applied in the same sync point
+            engine.commands().insert_module(id, Velocity(2.0, 0.0));
+        });
+    assert!(!engine.entity_exists(&id));
+    assert!(engine.entity_exists(&old));
+
+    engine.apply_commands();
+    assert!(!engine.entity_exists(&old));
+    assert_eq!(engine.get_module_of::<Position>(&id).unwrap().0, 1.0);
+    assert_eq!(engine.get_module_of::<Velocity>(&id).unwrap().0, 2.0);
+    assert_eq!(*engine.get_entity_id_by_tag("spawned").unwrap(), id);
+
+    // a second module of the same type is ignored, commands on removed entities do nothing
+    engine.commands().insert_module(id, Position(9.0, 9.0)).despawn(id).remove_module::<Velocity>(id);
+    engine.run_tick(TICK);
+    assert!(!engine.entity_exists(&id));
+}
+
+struct Damage(u32);
+
+impl Event for Damage {
+    const CANCELLABLE: bool = true;
+}
+
+/// Damage that reached the last listener.
+#[derive(Default)]
+struct Hits(Vec<u32>);
+
+fn double_damage(_id: &EntityId, _engine: &mut Engine, event: &mut Dispatch<Damage>) {
+    event.0 *= 2;
+}
+
+fn cap_damage(_id: &EntityId, _engine: &mut Engine, event: &mut Dispatch<Damage>) {
+    if event.0 > 10 {
+        event.cancel()
+    }
+}
+
+fn record_damage(_id: &EntityId, engine: &mut Engine, event: &mut Dispatch<Damage>) {
+    engine.mut_resource::<Hits>().unwrap().0.push(event.0);
+}
+
+#[test]
+fn event_listeners_run_in_order_and_cancel() {
+    let mut engine = engine();
+    engine.add_default_resource::<Hits>();
+    let listeners = [
+        EventListener::<Damage>::with_order(2, record_damage),
+        EventListener::<Damage>::with_order(1, cap_damage),
+        EventListener::<Damage>::new(double_damage)
+    ];
+    for listener in listeners {
+        let id = engine.new_entity();
+        engine.mut_entity(&id).unwrap().add_module(listener);
+    }
+
+    assert_eq!(engine.fire_now(Damage(3)).map(|d| d.0), Some(6));
+    assert!(engine.fire_now(Damage(6)).is_none());
+    assert_eq!(engine.get_resource::<Hits>().unwrap().0, vec![6]);
+
+    // fired events are delivered at the end of the tick
+    engine.fire(Damage(1));
+    engine.fire(Damage(2));
+    assert_eq!(engine.get_resource::<Hits>().unwrap().0.len(), 1);
+    engine.run_tick(TICK);
+    assert_eq!(engine.get_resource::<Hits>().unwrap().0, vec![6, 2, 4]);
+}
+
+trait Counter {
+    fn count(&self) -> u32;
+    fn increment(&mut self);
+}
+
+#[derive(Default)]
+struct SimpleCounter(u32);
+
+impl Counter for SimpleCounter {
+    fn count(&self) -> u32 {
+        self.0
+    }
+
+    fn increment(&mut self) {
+        self.0 += 1
+    }
+}
+
+#[test]
+fn services_are_looked_up_through_their_trait() {
+    let mut engine = engine();
+    let error = engine.service::<dyn Counter>().err().unwrap();
+    assert!(format!("{error:?}").contains("Counter"), "{error:?}");
+    assert!(!engine.has_service::<dyn Counter>());
+
+    assert!(engine.provide_service::<dyn Counter>(Box::<SimpleCounter>::default()));
+    assert!(!engine.provide_service::<dyn Counter>(Box::<SimpleCounter>::default()));
+    engine.service_mut::<dyn Counter>().unwrap().increment();
+    assert_eq!(engine.service::<dyn Counter>().unwrap().count(), 1);
+    // the service is not a resource of its concrete type
+    assert!(!engine.has_resource::<SimpleCounter>());
+
+    assert_eq!(engine.remove_service::<dyn Counter>().map(|c| c.count()), Some(1));
+    assert!(engine.service::<dyn Counter>().is_err());
+    assert!(engine.remove_service::<dyn Counter>().is_none());
+}
+
+/// A task that calls `step` whenever it is resumed and then waits for the next condition,
+/// written out instead of as a coroutine closure so the tests do not depend on coroutine syntax.
+struct StepTask<F: FnMut(&mut Engine)> {
+    waits: VecDeque<WaitFor>,
+    step: F
+}
+
+fn step_task<F: FnMut(&mut Engine) + Unpin>(waits: Vec<WaitFor>, step: F) -> StepTask<F> {
+    StepTask { waits: waits.into(), step }
+}
+
+impl<'a, F: FnMut(&mut Engine) + Unpin> Coroutine<&'a mut Engine> for StepTask<F> {
+    type Yield = Yielder<'a>;
+    type Return = ();
+
+    fn resume(self: Pin<&mut Self>, engine: &'a mut Engine) -> CoroutineState<Yielder<'a>, ()> {
+        let task = self.get_mut();
+        (task.step)(engine);
+        match task.waits.pop_front() {
+            Some(wait) => CoroutineState::Yielded(engine.yield_fn(wait)),
+            None => CoroutineState::Complete(())
+        }
+    }
+}
+
+fn counting_task(runs: &Rc<Cell<u32>>, waits: Vec<WaitFor>) -> StepTask<impl FnMut(&mut Engine) + Unpin> {
+    let runs = runs.clone();
+    step_task(waits, move |_| runs.set(runs.get() + 1))
+}
+
+#[test]
+fn task_handles_finish_and_cancel() {
+    let mut engine = engine();
+    let runs = Rc::new(Cell::new(0));
+    let finishing = engine.queue_task(counting_task(&runs, vec![WaitFor::next_tick(), WaitFor::next_tick()]));
+    let cancelled_runs = Rc::new(Cell::new(0));
+    let cancelled = engine.queue_task(counting_task(&cancelled_runs, (0..5).map(|_| WaitFor::ticks(2)).collect()));
+    let entity = engine.new_entity();
+    let owned = engine.queue_entity_task(&entity, counting_task(&Rc::default(), (0..5).map(|_| WaitFor::next_tick()).collect()));
+
+    engine.run_tick(TICK);
+    assert_eq!((runs.get(), cancelled_runs.get()), (1, 1));
+    assert!(cancelled.cancel(&mut engine));
+    assert!(!cancelled.cancel(&mut engine));
+    assert!(cancelled.is_finished(&engine));
+    assert!(engine.remove_entity(&entity));
+    assert!(owned.is_finished(&engine));
+
+    for _ in 0..4 {
+        engine.run_tick(TICK);
+    }
+    assert_eq!((runs.get(), cancelled_runs.get()), (3, 1));
+    assert!(finishing.is_finished(&engine));
+}
+
+#[test]
+fn tasks_wait_for_conditions_events_and_timeouts() {
+    let mut engine = engine();
+    let until_runs = Rc::new(Cell::new(0));
+    engine.queue_task(counting_task(&until_runs, vec![WaitFor::until(|e: &Engine| e.has_resource::<Hits>())]));
+    let received = Rc::new(Cell::new(None));
+    let task_received = received.clone();
+    engine.queue_task(step_task(
+        vec![WaitFor::event::<Damage>(), WaitFor::event_or_timeout::<Damage>(Duration::ZERO)],
+        move |e| task_received.set(e.received_event::<Damage>().ref_option().map(|d| d.0))
+    ));
+
+    engine.run_tick(TICK);
+    engine.run_tick(TICK);
+    assert_eq!(until_runs.get(), 1);
+    engine.add_default_resource::<Hits>();
+    engine.run_tick(TICK);
+    assert_eq!(until_runs.get(), 2);
+
+    engine.fire_now(Damage(7));
+    assert_eq!(received.get(), Some(7));
+    // the timeout passes before the next event
+    engine.run_tick(TICK);
+    assert_eq!(received.get(), None);
+}
+
+/// Two tasks that wait for `wait` and cancel both of them once they are resumed,
+/// returns how often they were resumed.
+fn cancelling_tasks(engine: &mut Engine, wait: impl Fn() -> WaitFor) -> Rc<Cell<u32>> {
+    let resumed = Rc::new(Cell::new(0));
+    let tasks: Rc<RefCell<Vec<TaskHandle>>> = Default::default();
+    for _ in 0..2 {
+        let (resumed, tasks_ref) = (resumed.clone(), tasks.clone());
+        let mut started = false;
+        let handle = engine.queue_task(step_task(vec![wait()], move |engine| {
+            if !std::mem::replace(&mut started, true) {
+                return
+            }
+            resumed.set(resumed.get() + 1);
+            for task in tasks_ref.borrow().iter() {
+                task.cancel(engine);
+            }
+        }));
+        tasks.borrow_mut().push(handle);
+    }
+    resumed
+}
+
+#[test]
+fn tasks_cancelled_by_a_sibling_are_not_resumed() {
+    let mut engine = engine();
+    let resumed = cancelling_tasks(&mut engine, WaitFor::event::<Damage>);
+    engine.run_tick(TICK);
+    engine.fire_now(Damage(1));
+    assert_eq!(resumed.get(), 1);
+
+    let resumed = cancelling_tasks(&mut engine, || WaitFor::event_or_timeout::<Damage>(Duration::ZERO));
+    engine.run_tick(TICK);
+    engine.run_tick(TICK);
+    assert_eq!(resumed.get(), 1);
+}
+
+#[derive(Default)]
+struct Removed(u32);
+
+struct Tracked;
+
+impl Module for Tracked {
+    fn remove(_id: &EntityId, engine: &mut Engine) where Self: Sized {
+        engine.mut_resource::<Removed>().unwrap().0 += 1;
+    }
+}
+
+#[test]
+fn removing_a_parent_removes_its_children() {
+    let mut engine = engine();
+    engine.add_default_resource::<Removed>();
+    let root = engine.new_entity();
+    let child = engine.new_child_entity(&root).unwrap();
+    let grandchild = engine.new_child_entity(&child).unwrap();
+    let other = engine.new_entity();
+    for id in [root, child, grandchild] {
+        engine.mut_entity(&id).unwrap().add_module(Tracked);
+    }
+
+    assert_eq!(engine.descendants_of(&root), vec![child, grandchild]);
+    assert!(engine.is_ancestor_of(&root, &grandchild));
+    // no cycles
+    assert!(!engine.set_parent(&root, Some(grandchild)));
+    assert!(engine.set_parent(&grandchild, Some(other)));
+    assert!(engine.set_parent(&grandchild, Some(child)));
+    assert!(engine.children_of(&other).is_empty());
+
+    assert!(engine.remove_entity(&root));
+    assert_eq!(engine.get_resource::<Removed>().unwrap().0, 3);
+    assert!(!engine.entity_exists(&child) && !engine.entity_exists(&grandchild));
+    assert!(engine.entity_exists(&other));
+}
+
+#[test]
+fn client_entities_are_removed_when_the_client_leaves() {
+    let (transport, connector) = memory_transport();
+    let mut engine = start_linked(vec![], transport);
+    let client = SimulatedClient::connect(&connector);
+    client.register();
+    engine.run_tick(TICK);
+
+    let player = engine.new_entity();
+    assert!(engine.tie_to_client(&player, Some(client.id)));
+    let item = engine.new_child_entity(&player).unwrap();
+    let kept = engine.new_entity();
+    assert_eq!(engine.client_entities(&client.id), vec![player]);
+
+    client.send(ClientMessage::Logout);
+    engine.run_tick(TICK);
+    assert!(!engine.entity_exists(&player) && !engine.entity_exists(&item));
+    assert!(engine.entity_exists(&kept));
+}
+
+struct TestHandle;
+
+impl ClientEntity for TestHandle {}
+
+#[test]
+fn clients_enter_and_leave_interest() {
+    let (transport, connector) = memory_transport();
+    let mut engine = start_linked(vec![], transport);
+    let mut clients = (0..2).map(|_| SimulatedClient::connect(&connector)).collect::<Vec<_>>();
+    for client in &clients {
+        client.register();
+    }
+    engine.run_tick(TICK);
+
+    let entity = engine.new_entity();
+    let e = engine.mut_entity(&entity).unwrap();
+    e.add_module(Messenger::new::<TestHandle>());
+    e.add_module(Interest::with_hysteresis(Vector2::new(0.0, 0.0), 10.0, 2.0));
+    // added by hand, never removed by the interest system
+    e.mut_module::<Messenger>().add_client(clients[1].id);
+    let viewer = engine.new_entity();
+    engine.mut_entity(&viewer).unwrap().add_module(Viewer::new(clients[0].id, Vector2::new(40.0, 0.0)));
+
+    let move_viewer = |engine: &mut Engine, x: f32| {
+        engine.mut_module_of::<Viewer>(&viewer).position = Vector2::new(x, 0.0);
+        engine.run_tick(TICK);
+        engine.get_module_of::<Messenger>(&entity).has_client(&clients[0].id)
+    };
+    assert!(!move_viewer(&mut engine, 40.0));
+    assert!(move_viewer(&mut engine, 9.0));
+    // within the hysteresis
+    assert!(move_viewer(&mut engine, 11.0));
+    assert!(!move_viewer(&mut engine, 13.0));
+    // only clients within the radius enter
+    assert!(!move_viewer(&mut engine, 11.0));
+    assert!(move_viewer(&mut engine, -5.0));
+
+    let received = clients[0].received();
+    let handles = received.iter().filter_map(|m| match m {
+        ServerMessage::AddClientHandle(id, _) if *id == entity => Some(true),
+        ServerMessage::RemoveClientHandle(id) if *id == entity => Some(false),
+        _ => None
+    }).collect::<Vec<_>>();
+    assert_eq!(handles, vec![true, false, true]);
+    assert!(engine.get_module_of::<Messenger>(&entity).has_client(&clients[1].id));
+}
+
+aeonetica_engine::rpc!(SetSpeed: f32);
+
+#[derive(Default)]
+struct Speed(f32);
+
+#[test]
+fn violations_are_dropped_logged_or_kicked() {
+    let (transport, connector) = memory_transport();
+    let mut engine = start_linked(vec![], transport);
+    let mut client = SimulatedClient::connect(&connector);
+    client.register();
+    engine.run_tick(TICK);
+    client.received();
+
+    engine.add_default_resource::<Speed>();
+    let entity = engine.new_entity();
+    let e = engine.mut_entity(&entity).unwrap();
+    e.add_module(Messenger::new::<TestHandle>());
+    e.mut_module::<Messenger>().on_validated::<SetSpeed>(
+        |_, engine, _, speed| engine.mut_resource::<Speed>().unwrap().0 = speed,
+        |_, _, _, speed| check_range("speed", *speed, 0.0, 10.0)
+    );
+    let set_speed = |engine: &mut Engine, speed: f32| {
+        client.send(ClientMessage::ModMessage(entity, SetSpeed::id(), speed.serialize_bin()));
+        engine.run_tick(TICK);
+        engine.get_resource::<Speed>().unwrap().0
+    };
+
+    // the bounds are within the range
+    assert_eq!(set_speed(&mut engine, 10.0), 10.0);
+    assert_eq!(set_speed(&mut engine, 0.0), 0.0);
+    // rubber-banded by default: the message is dropped and the state stays
+    assert_eq!(set_speed(&mut engine, 10.5), 0.0);
+    assert_eq!(set_speed(&mut engine, -1.0), 0.0);
+
+    let mut policies = ViolationPolicies::default();
+    policies.set("speed", ViolationPolicy::Log);
+    engine.add_resource(policies);
+    assert_eq!(set_speed(&mut engine, 20.0), 20.0);
+
+    engine.mut_resource::<ViolationPolicies>().unwrap().set("speed", ViolationPolicy::Kick);
+    client.send(ClientMessage::ModMessage(entity, SetSpeed::id(), 30.0f32.serialize_bin()));
+    assert!(engine.handle_queued().is_ok());
+    // the kick waits for the next sync point instead of running inside the receiver
+    assert!(engine.is_client_logged_in(&client.id));
+    assert_eq!(engine.get_resource::<Speed>().unwrap().0, 20.0);
+    engine.apply_commands();
+    assert!(!engine.is_client_logged_in(&client.id));
+    assert!(client.received().iter().any(|m| matches!(m, ServerMessage::Kick(_))));
+}
+
+aeonetica_engine::request!(AskName: () => String);
+
+type Reply = Rc<RefCell<Option<Result<String, RequestError>>>>;
+
+/// Asks the client for its name and stores the reply.
+struct AskTask {
+    client: ClientId,
+    timeout: Duration,
+    pending: Option<PendingReply<AskName>>,
+    reply: Reply
+}
+
+impl<'a> Coroutine<&'a mut Engine> for AskTask {
+    type Yield = Yielder<'a>;
+    type Return = ();
+
+    fn resume(self: Pin<&mut Self>, engine: &'a mut Engine) -> CoroutineState<Yielder<'a>, ()> {
+        let task = self.get_mut();
+        match task.pending.take() {
+            None => {
+                let pending = engine.request_client::<AskName>(&Id::new(), &task.client, ());
+                let wait = pending.wait(task.timeout);
+                task.pending = Some(pending);
+                CoroutineState::Yielded(engine.yield_fn(wait))
+            }
+            Some(pending) => {
+                *task.reply.borrow_mut() = Some(engine.take_reply(&pending));
+                CoroutineState::Complete(())
+            }
+        }
+    }
+}
+
+fn ask(engine: &mut Engine, client: &ClientId, timeout: Duration) -> (TaskHandle, Reply) {
+    let reply = Reply::default();
+    let handle = engine.queue_task(AskTask { client: *client, timeout, pending: None, reply: reply.clone() });
+    engine.run_tick(TICK);
+    (handle, reply)
+}
+
+fn requests(client: &mut SimulatedClient) -> Vec<Id> {
+    client.received_packets().into_iter()
+        .filter(|packet| matches!(packet.message, ServerMessage::ModRequest(..)))
+        .map(|packet| packet.conv_id)
+        .collect()
+}
+
+#[test]
+fn pending_replies_are_dropped_once_nobody_waits() {
+    let (transport, connector) = memory_transport();
+    let mut engine = start_linked(vec![], transport);
+    let mut client = SimulatedClient::connect(&connector);
+    client.register();
+    engine.run_tick(TICK);
+    client.received();
+    let answer = |client: &SimulatedClient, conv_id: Id| {
+        client.send_in(conv_id, ClientMessage::ModReply(Id::new(), NetResult::Ok("bob".to_string().serialize_bin())))
+    };
+
+    let (_, reply) = ask(&mut engine, &client.id, Duration::from_secs(60));
+    let [conv_id] = requests(&mut client)[..] else { panic!("expected one request") };
+    answer(&client, conv_id);
+    engine.run_tick(TICK);
+    assert_eq!(reply.borrow_mut().take(), Some(Ok("bob".to_string())));
+    assert!(engine.pending_replies.is_empty());
+
+    // timed out, a late reply is ignored
+    let (_, reply) = ask(&mut engine, &client.id, Duration::ZERO);
+    assert_eq!(engine.pending_replies.len(), 1);
+    engine.run_tick(TICK);
+    assert_eq!(reply.borrow_mut().take(), Some(Err(RequestError::Timeout)));
+    assert!(engine.pending_replies.is_empty());
+    let [conv_id] = requests(&mut client)[..] else { panic!("expected one request") };
+    answer(&client, conv_id);
+    engine.run_tick(TICK);
+    assert!(engine.pending_replies.is_empty());
+
+    let (handle, _) = ask(&mut engine, &client.id, Duration::from_secs(60));
+    assert!(handle.cancel(&mut engine));
+    assert!(engine.pending_replies.is_empty());
+
+    let (_, reply) = ask(&mut engine, &client.id, Duration::from_secs(60));
+    client.send(ClientMessage::Logout);
+    engine.run_tick(TICK);
+    assert!(engine.pending_replies.is_empty());
+    assert!(reply.borrow().is_none());
+}
+
+#[cfg(feature = "wasm")]
+mod wasm {
+    use aeonetica_engine::Id;
+    use aeonetica_engine::wasm_abi::WASM_ABI_VERSION;
+    use crate::ecs::Engine;
+    use crate::wasm::{call_with_engine, WasmServerMod};
+    use super::engine;
+
+    /// Exports the host functions under test, so the tests can call them with any arguments.
+    fn guest() -> WasmServerMod {
+        let code = wat::parse_str(format!(r#"(module
+            (import "aeonetica" "log" (func $log (param i32 i32)))
+            (import "aeonetica" "new_entity" (func $new_entity (result i64)))
+            (import "aeonetica" "remove_entity" (func $remove_entity (param i64) (result i32)))
+            (import "aeonetica" "add_messenger" (func $add_messenger (param i64 i64) (result i32)))
+            (memory (export "memory") 1)
+            (func (export "aeonetica_abi_version") (result i32) i32.const {WASM_ABI_VERSION})
+            (func (export "aeonetica_alloc") (param i32) (result i32) i32.const 0)
+            (func (export "new_entity") (result i64) call $new_entity)
+            (func (export "remove_entity") (param i64) (result i32) local.get 0 call $remove_entity)
+            (func (export "add_messenger") (param i64) (result i32) local.get 0 i64.const 7 call $add_messenger)
+            (func (export "log") (param i32 i32) local.get 0 local.get 1 call $log)
+        )"#)).unwrap();
+        WasmServerMod::load("test:wasm", &code).unwrap()
+    }
+
+    fn call(guest: &WasmServerMod, engine: &mut Engine, name: &str, entity: i64) -> i32 {
+        call_with_engine::<_, i32>(&guest.instance, engine, name, entity).unwrap().unwrap()
+    }
+
+    #[test]
+    fn guests_only_change_their_own_entities() {
+        let mut engine = engine();
+        let guest = guest();
+        let other = guest();
+        let own = call_with_engine::<_, i64>(&guest.instance, &mut engine, "new_entity", ()).unwrap().unwrap();
+        let own_id = Id::from_u64(own as u64);
+        let foreign = engine.new_entity();
+        let foreign_id = foreign.into_u64() as i64;
+        assert!(engine.entity_exists(&own_id));
+
+        assert_eq!(call(&guest, &mut engine, "add_messenger", foreign_id), 0);
+        assert_eq!(call(&other, &mut engine, "add_messenger", own), 0);
+        assert_eq!(call(&guest, &mut engine, "add_messenger", own), 1);
+        assert_eq!(call(&guest, &mut engine, "remove_entity", foreign_id), 0);
+        assert_eq!(call(&other, &mut engine, "remove_entity", own), 0);
+        engine.apply_commands();
+        assert!(engine.entity_exists(&foreign));
+
+        // removals wait for the next sync point
+        assert_eq!(call(&guest, &mut engine, "remove_entity", own), 1);
+        assert!(engine.entity_exists(&own_id));
+        engine.apply_commands();
+        assert!(!engine.entity_exists(&own_id));
+        assert_eq!(call(&guest, &mut engine, "remove_entity", own), 0);
+    }
+
+    #[test]
+    fn guest_memory_is_bounds_checked() {
+        let mut engine = engine();
+        let guest = guest();
+        let log = |engine: &mut Engine, ptr: i32, len: i32| call_with_engine::<_, ()>(&guest.instance, engine, "log", (ptr, len)).is_ok();
+        assert!(log(&mut engine, 0, 4));
+        assert!(log(&mut engine, 65536 - 4, 4));
+        assert!(!log(&mut engine, 65536 - 4, 8));
+        assert!(!log(&mut engine, 0, i32::MAX));
+        assert!(!log(&mut engine, i32::MAX, i32::MAX));
+        assert!(!log(&mut engine, -1, 4));
+        assert!(!log(&mut engine, 0, -1));
+    }
+}
+
+#[test]
+fn reloaded_mods_leave_no_receivers_behind() {
+    let mut engine = engine();
+    let (player, worms): (Rc<str>, Rc<str>) = ("test:player".into(), "test:worms".into());
+    let id = engine.as_mod(Some(player.clone()), |engine| {
+        let id = engine.new_entity();
+        engine.mut_entity(&id).unwrap().add_module(Messenger::new::<TestHandle>());
+        engine.mut_module_of::<Messenger>(&id).unwrap().on::<SetSpeed>(|_, _, _, _| ());
+        id
+    });
+    // another mod answers requests through the player's messenger
+    engine.as_mod(Some(worms.clone()), |engine| {
+        engine.mut_module_of::<Messenger>(&id).unwrap().on_request::<AskName>(|_, _, _, _| "worm".to_string());
+    });
+
+    engine.remove_mod_content(&worms);
+    let messenger = engine.get_module_of::<Messenger>(&id).unwrap();
+    assert!(messenger.receiver_functions.contains_key(&SetSpeed::id()));
+    assert!(!messenger.request_functions.contains_key(&AskName::id()));
+
+    engine.remove_mod_content(&player);
+    assert!(!engine.entity_exists(&id));
+}
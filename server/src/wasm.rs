@@ -0,0 +1,263 @@
+use std::cell::RefCell;
+use std::rc::{Rc, Weak};
+use aeonetica_engine::{ClientId, EntityId, Id, log};
+use aeonetica_engine::error::{Error, ErrorResult, Fatality};
+use aeonetica_engine::error::builtin::ModError;
+use aeonetica_engine::networking::rpc::rpc_id;
+use aeonetica_engine::time::Time;
+use aeonetica_engine::util::id_map::IdSet;
+use aeonetica_engine::wasm_abi::{FUEL_PER_CALL, IMPORT_MODULE, send_mode, WASM_ABI_VERSION};
+use wasmi::{Caller, Config, Extern, Instance, Linker, Store, WasmParams, WasmResults};
+use wasmi::core::Trap;
+use crate::ServerMod;
+use crate::ecs::{Engine, Module};
+use crate::ecs::messaging::Messenger;
+
+pub use wasmi;
+
+/// A sandboxed wasm mod, see `aeonetica_engine::wasm_abi` for what it can call.
+/// `T` is the state of the host functions of the client or the server.
+pub struct WasmInstance<T> {
+    pub store: Store<T>,
+    instance: Instance
+}
+
+impl<T: 'static> WasmInstance<T> {
+    /// Instantiates the guest with the common host functions and the ones added by `link`.
+    pub fn new(name_path: &str, code: &[u8], state: T, link: impl FnOnce(&mut Linker<T>) -> Result<(), wasmi::errors::LinkerError>) -> ErrorResult<Self> {
+        let error = |e: String| Error::new(ModError(format!("could not load wasm mod {name_path}: {e}")), Fatality::FATAL, false);
+        let mut config = Config::default();
+        config.consume_fuel(true);
+        let engine = wasmi::Engine::new(&config);
+        let module = wasmi::Module::new(&engine, code).map_err(|e| error(e.to_string()))?;
+        let mut store = Store::new(&engine, state);
+        let mut linker = Linker::new(&engine);
+        link_common(&mut linker, name_path).and_then(|_| link(&mut linker)).map_err(|e| error(e.to_string()))?;
+        store.add_fuel(FUEL_PER_CALL).map_err(|e| error(e.to_string()))?;
+        let instance = linker.instantiate(&mut store, &module)
+            .and_then(|i| i.start(&mut store))
+            .map_err(|e| error(e.to_string()))?;
+        let mut instance = Self { store, instance };
+        let version = instance.call::<(), i32>("aeonetica_abi_version", ())?
+            .ok_or_else(|| error("it exports no aeonetica_abi_version".to_string()))?;
+        if version != WASM_ABI_VERSION {
+            return Err(error(format!("it has wasm abi version {version}, but the engine expects {WASM_ABI_VERSION}")))
+        }
+        Ok(instance)
+    }
+
+    /// Calls an export of the guest, `None` if the guest does not export it.
+    /// Every call gets [`FUEL_PER_CALL`] instructions.
+    pub fn call<P: WasmParams, R: WasmResults>(&mut self, name: &str, params: P) -> ErrorResult<Option<R>> {
+        if self.instance.get_export(&self.store, name).is_none() {
+            return Ok(None)
+        }
+        let error = |e: String| Error::new(ModError(format!("wasm mod failed in {name}: {e}")), Fatality::DEFAULT, false);
+        let remaining = self.store.consume_fuel(0).unwrap_or(0);
+        self.store.add_fuel(FUEL_PER_CALL.saturating_sub(remaining)).map_err(|e| error(e.to_string()))?;
+        let func = self.instance.get_typed_func::<P, R>(&self.store, name).map_err(|e| error(e.to_string()))?;
+        func.call(&mut self.store, params).map(Some).map_err(|e| error(e.to_string()))
+    }
+
+    /// Copies `data` into memory the guest allocated, returns the pointer and length to pass to it.
+    pub fn write(&mut self, data: &[u8]) -> ErrorResult<(i32, i32)> {
+        let len = data.len() as i32;
+        let ptr = self.call::<i32, i32>("aeonetica_alloc", len)?
+            .ok_or_else(|| Error::new(ModError("wasm mod exports no aeonetica_alloc".to_string()), Fatality::DEFAULT, false))?;
+        let memory = self.instance.get_memory(&self.store, "memory")
+            .ok_or_else(|| Error::new(ModError("wasm mod exports no memory".to_string()), Fatality::DEFAULT, false))?;
+        memory.write(&mut self.store, ptr as usize, data)
+            .map_err(|e| Error::new(ModError(format!("could not write to wasm mod memory: {e}")), Fatality::DEFAULT, false))?;
+        Ok((ptr, len))
+    }
+
+    /// Passes the flags to `aeonetica_init`.
+    pub fn init(&mut self, flags: &[String]) -> ErrorResult<()> {
+        let (ptr, len) = self.write(flags.join("\n").as_bytes())?;
+        self.call::<(i32, i32), ()>("aeonetica_init", (ptr, len))?;
+        Ok(())
+    }
+}
+
+/// Reads `len` bytes at `ptr` from the memory of the calling guest.
+/// Nothing is allocated for ranges outside of its memory.
+pub fn read_guest<T>(caller: &Caller<T>, ptr: i32, len: i32) -> Result<Vec<u8>, Trap> {
+    let memory = caller.get_export("memory").and_then(Extern::into_memory)
+        .ok_or_else(|| Trap::new("guest exports no memory"))?;
+    let start = usize::try_from(ptr).map_err(|_| Trap::new(format!("guest passed the invalid pointer {ptr}")))?;
+    let end = usize::try_from(len).ok().and_then(|len| start.checked_add(len))
+        .ok_or_else(|| Trap::new(format!("guest passed the invalid length {len}")))?;
+    memory.data(caller).get(start..end)
+        .map(<[u8]>::to_vec)
+        .ok_or_else(|| Trap::new(format!("guest passed {start}..{end}, which is outside of its memory")))
+}
+
+fn link_common<T: 'static>(linker: &mut Linker<T>, name_path: &str) -> Result<(), wasmi::errors::LinkerError> {
+    let name_path = name_path.to_string();
+    linker.func_wrap(IMPORT_MODULE, "log", move |caller: Caller<T>, ptr: i32, len: i32| -> Result<(), Trap> {
+        log!("[{name_path}] {}", String::from_utf8_lossy(&read_guest(&caller, ptr, len)?));
+        Ok(())
+    })?;
+    linker.func_wrap(IMPORT_MODULE, "rpc_id", |caller: Caller<T>, ptr: i32, len: i32| -> Result<i64, Trap> {
+        let name = read_guest(&caller, ptr, len)?;
+        Ok(rpc_id(&String::from_utf8_lossy(&name)).into_u64() as i64)
+    })?;
+    Ok(())
+}
+
+fn id(id: i64) -> Id {
+    Id::from_u64(id as u64)
+}
+
+fn ok(success: bool) -> i32 {
+    success as i32
+}
+
+pub(crate) struct ServerHost {
+    /// only set while the guest is called
+    engine: *mut Engine,
+    instance: Weak<RefCell<WasmInstance<ServerHost>>>,
+    /// the entities the guest created, the only ones it may change
+    entities: IdSet
+}
+
+fn engine<'a>(caller: &Caller<ServerHost>) -> Result<&'a mut Engine, Trap> {
+    unsafe { caller.data().engine.as_mut() }.ok_or_else(|| Trap::new("the engine is only available while the mod is called by it"))
+}
+
+/// The engine, if the calling guest created `entity`.
+fn owned_entity<'a>(caller: &Caller<ServerHost>, entity: i64) -> Result<Option<(&'a mut Engine, EntityId)>, Trap> {
+    let entity = id(entity);
+    let engine = engine(caller)?;
+    Ok(caller.data().entities.contains(&entity).then_some((engine, entity)))
+}
+
+/// Calls the guest with access to `engine`.
+pub(crate) fn call_with_engine<P: WasmParams, R: WasmResults>(instance: &Rc<RefCell<WasmInstance<ServerHost>>>, engine: &mut Engine, name: &str, params: P) -> ErrorResult<Option<R>> {
+    let mut instance = instance.borrow_mut();
+    instance.store.data_mut().engine = engine;
+    let result = instance.call(name, params);
+    instance.store.data_mut().engine = std::ptr::null_mut();
+    result
+}
+
+fn receive(instance: &Rc<RefCell<WasmInstance<ServerHost>>>, engine: &mut Engine, entity: &EntityId, sender: &ClientId, rid: Id, data: &[u8]) -> ErrorResult<()> {
+    let (ptr, len) = instance.borrow_mut().write(data)?;
+    let params = (entity.into_u64() as i64, sender.into_u64() as i64, rid.into_u64() as i64, ptr, len);
+    call_with_engine::<_, ()>(instance, engine, "aeonetica_receive", params)?;
+    Ok(())
+}
+
+/// Ticks the guest for every entity it added this module to.
+struct WasmModule {
+    instance: Weak<RefCell<WasmInstance<ServerHost>>>
+}
+
+impl Module for WasmModule {
+    fn tick(id: &EntityId, engine: &mut Engine, time: Time) where Self: Sized {
+        let Some(instance) = engine.get_module_of::<Self>(id).option().and_then(|m| m.instance.upgrade()) else { return };
+        if let Err(e) = call_with_engine::<_, ()>(&instance, engine, "aeonetica_tick", (id.into_u64() as i64, time.delta)) {
+            e.log()
+        }
+    }
+}
+
+fn link_server(linker: &mut Linker<ServerHost>) -> Result<(), wasmi::errors::LinkerError> {
+    linker.func_wrap(IMPORT_MODULE, "new_entity", |mut caller: Caller<ServerHost>| -> Result<i64, Trap> {
+        let entity = engine(&caller)?.new_entity();
+        caller.data_mut().entities.insert(entity);
+        Ok(entity.into_u64() as i64)
+    })?;
+    linker.func_wrap(IMPORT_MODULE, "remove_entity", |mut caller: Caller<ServerHost>, entity: i64| -> Result<i32, Trap> {
+        let Some((engine, entity)) = owned_entity(&caller, entity)? else { return Ok(0) };
+        caller.data_mut().entities.remove(&entity);
+        if !engine.entity_exists(&entity) {
+            return Ok(0)
+        }
+        // the entity might own the module or messenger that called the guest
+        engine.commands().despawn(entity);
+        Ok(1)
+    })?;
+    linker.func_wrap(IMPORT_MODULE, "add_module", |caller: Caller<ServerHost>, entity: i64| -> Result<i32, Trap> {
+        let instance = caller.data().instance.clone();
+        let Some((engine, entity)) = owned_entity(&caller, entity)? else { return Ok(0) };
+        Ok(ok(engine.mut_entity(&entity).option().is_some_and(|e| e.add_module(WasmModule { instance }))))
+    })?;
+    linker.func_wrap(IMPORT_MODULE, "add_messenger", |caller: Caller<ServerHost>, entity: i64, handle_type: i64| -> Result<i32, Trap> {
+        let messenger = Messenger::with_handle_type(id(handle_type));
+        let Some((engine, entity)) = owned_entity(&caller, entity)? else { return Ok(0) };
+        Ok(ok(engine.mut_entity(&entity).option().is_some_and(|e| e.add_module(messenger))))
+    })?;
+    linker.func_wrap(IMPORT_MODULE, "on", |caller: Caller<ServerHost>, entity: i64, rpc: i64| -> Result<i32, Trap> {
+        let instance = caller.data().instance.clone();
+        let Some((engine, entity)) = owned_entity(&caller, entity)? else { return Ok(0) };
+        let Some(messenger) = engine.mut_module_of::<Messenger>(&entity).option() else { return Ok(0) };
+        let rid = id(rpc);
+        messenger.insert_receiver(rid, Box::new(move |entity: &EntityId, engine: &mut Engine, sender: &ClientId, data: &Vec<u8>| {
+            let Some(instance) = instance.upgrade() else { return };
+            if let Err(e) = receive(&instance, engine, entity, sender, rid, data) {
+                e.log()
+            }
+        }));
+        Ok(1)
+    })?;
+    linker.func_wrap(IMPORT_MODULE, "send", |caller: Caller<ServerHost>, entity: i64, rpc: i64, ptr: i32, len: i32, mode: i32| -> Result<(), Trap> {
+        let data = read_guest(&caller, ptr, len)?;
+        let Some((engine, entity)) = owned_entity(&caller, entity)? else { return Ok(()) };
+        if let Some(messenger) = engine.get_module_of::<Messenger>(&entity).option() {
+            for client in messenger.clients() {
+                messenger.send_raw(client, id(rpc), data.clone(), send_mode(mode));
+            }
+        }
+        Ok(())
+    })?;
+    linker.func_wrap(IMPORT_MODULE, "send_to", |caller: Caller<ServerHost>, entity: i64, client: i64, rpc: i64, ptr: i32, len: i32, mode: i32| -> Result<(), Trap> {
+        let data = read_guest(&caller, ptr, len)?;
+        let Some((engine, entity)) = owned_entity(&caller, entity)? else { return Ok(()) };
+        if let Some(messenger) = engine.get_module_of::<Messenger>(&entity).option() {
+            messenger.send_raw(&id(client), id(rpc), data, send_mode(mode));
+        }
+        Ok(())
+    })?;
+    linker.func_wrap(IMPORT_MODULE, "add_client", |caller: Caller<ServerHost>, entity: i64, client: i64| -> Result<i32, Trap> {
+        let Some((engine, entity)) = owned_entity(&caller, entity)? else { return Ok(0) };
+        Ok(ok(engine.mut_module_of::<Messenger>(&entity).option().is_some_and(|m| m.add_client(id(client)))))
+    })?;
+    linker.func_wrap(IMPORT_MODULE, "remove_client", |caller: Caller<ServerHost>, entity: i64, client: i64| -> Result<i32, Trap> {
+        let Some((engine, entity)) = owned_entity(&caller, entity)? else { return Ok(0) };
+        Ok(ok(engine.mut_module_of::<Messenger>(&entity).option().is_some_and(|m| m.remove_client(&id(client)))))
+    })?;
+    Ok(())
+}
+
+/// The server side of a wasm mod.
+pub(crate) struct WasmServerMod {
+    pub(crate) instance: Rc<RefCell<WasmInstance<ServerHost>>>
+}
+
+impl WasmServerMod {
+    pub(crate) fn load(name_path: &str, code: &[u8]) -> ErrorResult<Self> {
+        let host = ServerHost {
+            engine: std::ptr::null_mut(),
+            instance: Weak::new(),
+            entities: Default::default()
+        };
+        let instance = Rc::new(RefCell::new(WasmInstance::new(name_path, code, host, link_server)?));
+        instance.borrow_mut().store.data_mut().instance = Rc::downgrade(&instance);
+        Ok(Self { instance })
+    }
+}
+
+impl ServerMod for WasmServerMod {
+    fn init(&mut self, flags: &Vec<String>) {
+        if let Err(e) = self.instance.borrow_mut().init(flags) {
+            e.log()
+        }
+    }
+
+    fn start(&mut self, engine: &mut Engine) {
+        if let Err(e) = call_with_engine::<_, ()>(&self.instance, engine, "aeonetica_start", ()) {
+            e.log()
+        }
+    }
+}